@@ -0,0 +1,125 @@
+//! Benchmarking for Mollusk program execution.
+//!
+//! `Mollusk::bench_instruction` runs an instruction repeatedly and summarizes
+//! the compute units consumed and the wall-clock time taken. The summary can
+//! be written to a markdown file, where each run is compared against the
+//! previous one, making regressions easy to spot in CI.
+//!
+//! For benching many instructions at once, see the `mollusk-svm-bencher`
+//! crate.
+
+use std::{path::Path, time::Duration};
+
+/// The summarized results of running an instruction repeatedly.
+#[derive(Debug, PartialEq, Eq)]
+pub struct BenchResult {
+    /// The number of times the instruction was run.
+    pub iterations: usize,
+    /// The fewest compute units consumed by a single run.
+    pub compute_units_min: u64,
+    /// The most compute units consumed by a single run.
+    pub compute_units_max: u64,
+    /// The mean compute units consumed per run.
+    pub compute_units_mean: u64,
+    /// The shortest wall-clock time taken by a single run.
+    pub time_min: Duration,
+    /// The longest wall-clock time taken by a single run.
+    pub time_max: Duration,
+    /// The mean wall-clock time taken per run.
+    pub time_mean: Duration,
+}
+
+impl BenchResult {
+    /// Summarize a set of runs, each a tuple of the compute units consumed and
+    /// the wall-clock time taken. Panics if `runs` is empty.
+    pub(crate) fn new(runs: &[(u64, Duration)]) -> Self {
+        assert!(!runs.is_empty(), "Cannot bench zero iterations");
+        let iterations = runs.len();
+        let units = runs.iter().map(|(units, _)| *units);
+        let times = runs.iter().map(|(_, time)| *time);
+        Self {
+            iterations,
+            compute_units_min: units.clone().min().unwrap(),
+            compute_units_max: units.clone().max().unwrap(),
+            compute_units_mean: units.sum::<u64>() / iterations as u64,
+            time_min: times.clone().min().unwrap(),
+            time_max: times.clone().max().unwrap(),
+            time_mean: times.sum::<Duration>() / iterations as u32,
+        }
+    }
+
+    fn rows(&self) -> [(&'static str, u64); 6] {
+        [
+            ("CUs (min)", self.compute_units_min),
+            ("CUs (max)", self.compute_units_max),
+            ("CUs (mean)", self.compute_units_mean),
+            ("Time (min, us)", self.time_min.as_micros() as u64),
+            ("Time (max, us)", self.time_max.as_micros() as u64),
+            ("Time (mean, us)", self.time_mean.as_micros() as u64),
+        ]
+    }
+
+    /// Write the results to a markdown file as a table.
+    ///
+    /// If the file already exists, its most recent table is used as the
+    /// baseline, and the delta of each metric is included. The new table is
+    /// prepended to the file, but only if the compute units changed, since
+    /// wall-clock times vary from run to run.
+    pub fn write_markdown<P: AsRef<Path>>(&self, path: P) {
+        let path = path.as_ref();
+        let existing_content = if path.exists() {
+            std::fs::read_to_string(path)
+                .unwrap_or_else(|err| panic!("Failed to read {}: {}", path.display(), err))
+        } else {
+            String::new()
+        };
+        let baseline = parse_last_md_table(&existing_content);
+
+        let mut compute_units_changed = baseline.is_empty();
+        let mut md_table = format!(
+            "#### Iterations: {}\n\n| Metric | Value | Delta |\n|--------|-------|-------|\n",
+            self.iterations
+        );
+        for (metric, value) in self.rows() {
+            let delta = match baseline.iter().find(|(name, _)| *name == metric) {
+                Some((_, previous)) => {
+                    let delta = value as i64 - *previous as i64;
+                    if delta != 0 && metric.starts_with("CUs") {
+                        compute_units_changed = true;
+                    }
+                    match delta {
+                        0 => "--".to_string(),
+                        delta if delta > 0 => format!("+{}", delta),
+                        delta => delta.to_string(),
+                    }
+                }
+                None => "- new -".to_string(),
+            };
+            md_table.push_str(&format!("| {} | {} | {} |\n", metric, value, delta));
+        }
+
+        if compute_units_changed {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).unwrap();
+            }
+            md_table.push('\n');
+            md_table.push_str(&existing_content);
+            std::fs::write(path, md_table)
+                .unwrap_or_else(|err| panic!("Failed to write {}: {}", path.display(), err));
+        }
+    }
+}
+
+fn parse_last_md_table(content: &str) -> Vec<(&str, u64)> {
+    content
+        .lines()
+        .skip(4)
+        .take_while(|line| !line.starts_with("####") && !line.is_empty())
+        .filter_map(|line| {
+            let mut parts = line.split('|').skip(1).map(str::trim);
+            let metric = parts.next()?;
+            let value = parts.next()?.parse().ok()?;
+            Some((metric, value))
+        })
+        .collect()
+}
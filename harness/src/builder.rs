@@ -0,0 +1,120 @@
+//! Builder for configuring a Mollusk test environment in one expression.
+
+use {
+    crate::{error::MolluskError, file, program, Mollusk},
+    solana_compute_budget::compute_budget::ComputeBudget,
+    solana_sdk::{
+        bpf_loader_upgradeable, clock::Slot, feature_set::FeatureSet, pubkey::Pubkey, rent::Rent,
+    },
+    std::path::{Path, PathBuf},
+};
+
+/// A builder for a `Mollusk` instance.
+///
+/// Created with `Mollusk::builder()`. Any configuration not provided falls
+/// back to the value used by `Mollusk::default()`.
+pub struct MolluskBuilder {
+    compute_budget: ComputeBudget,
+    feature_set: FeatureSet,
+    program: Option<(Pubkey, &'static str)>,
+    rent: Option<Rent>,
+    search_paths: Vec<PathBuf>,
+    slot: Option<Slot>,
+}
+
+impl MolluskBuilder {
+    pub(crate) fn new() -> Self {
+        Self {
+            compute_budget: ComputeBudget::default(),
+            feature_set: FeatureSet::all_enabled(),
+            program: None,
+            rent: None,
+            search_paths: Vec::new(),
+            slot: None,
+        }
+    }
+
+    /// Set the program to test.
+    ///
+    /// The program's ELF file is loaded from the default search paths, and
+    /// any added with `search_path`, when `build` is called.
+    pub fn program(mut self, program_id: &Pubkey, program_name: &'static str) -> Self {
+        self.program = Some((*program_id, program_name));
+        self
+    }
+
+    /// Set the compute budget.
+    pub fn compute_budget(mut self, compute_budget: ComputeBudget) -> Self {
+        self.compute_budget = compute_budget;
+        self
+    }
+
+    /// Set the feature set.
+    pub fn feature_set(mut self, feature_set: FeatureSet) -> Self {
+        self.feature_set = feature_set;
+        self
+    }
+
+    /// Set the `Rent` sysvar.
+    pub fn rent(mut self, rent: Rent) -> Self {
+        self.rent = Some(rent);
+        self
+    }
+
+    /// Add a directory to search for program ELF files, after the default
+    /// search paths.
+    pub fn search_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.search_paths.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Set the slot of the `Clock` sysvar, as if calling `warp_to_slot`.
+    pub fn clock_slot(mut self, slot: Slot) -> Self {
+        self.slot = Some(slot);
+        self
+    }
+
+    /// Build the `Mollusk` instance.
+    ///
+    /// If a program was provided, its ELF file is loaded here, using the
+    /// configured compute budget and feature set. Panics if the ELF file
+    /// cannot be loaded. See `try_build` for a non-panicking version.
+    pub fn build(self) -> Mollusk {
+        self.try_build().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Build the `Mollusk` instance, returning an error if the program's ELF
+    /// file cannot be loaded.
+    ///
+    /// As with `Mollusk::try_new`, if the file is not found, the error lists
+    /// every directory searched.
+    pub fn try_build(self) -> Result<Mollusk, MolluskError> {
+        let mut mollusk = Mollusk {
+            compute_budget: self.compute_budget,
+            feature_set: self.feature_set,
+            search_paths: self.search_paths,
+            ..Default::default()
+        };
+        mollusk
+            .program_cache
+            .recompile_programs(&mollusk.compute_budget, &mollusk.feature_set);
+
+        if let Some(rent) = self.rent {
+            mollusk.sysvars.rent = rent;
+        }
+
+        if let Some(slot) = self.slot {
+            mollusk.warp_to_slot(slot);
+        }
+
+        if let Some((program_id, program_name)) = self.program {
+            let elf =
+                file::try_load_program_elf_with_search_paths(program_name, &mollusk.search_paths)?;
+            mollusk.program_id = program_id;
+            mollusk.program_account = program::program_account(&program_id);
+            mollusk.try_add_program_with_elf(&program_id, &bpf_loader_upgradeable::id(), &elf)?;
+        }
+
+        Ok(mollusk)
+    }
+}
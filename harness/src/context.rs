@@ -0,0 +1,154 @@
+//! Stateful test context for Mollusk.
+//!
+//! Mollusk's main API requires the caller to provide every account for every
+//! instruction. For integration-style tests, where state should accumulate
+//! across many instructions, the `MolluskContext` instead owns an account
+//! store. Each instruction's accounts are loaded from the store, and the
+//! resulting accounts are written back to it after execution.
+//!
+//! The store can be any implementation of `AccountStore`, such as one that
+//! lazily loads accounts from an RPC endpoint or a snapshot file. By default,
+//! accounts are kept in memory, in a `HashMap`.
+
+use {
+    crate::{
+        result::{self, Check, CheckFailure, InstructionResult, ProgramResult},
+        Mollusk,
+    },
+    solana_sdk::{account::AccountSharedData, instruction::Instruction, pubkey::Pubkey},
+    std::collections::HashMap,
+};
+
+/// A source of accounts for a `MolluskContext`, which also receives the
+/// resulting accounts of every successful instruction.
+pub trait AccountStore {
+    /// Load an account, returning `None` if the store has no such account.
+    fn load(&self, pubkey: &Pubkey) -> Option<AccountSharedData>;
+
+    /// Store an account, replacing any existing account.
+    fn store(&mut self, pubkey: Pubkey, account: AccountSharedData);
+}
+
+impl AccountStore for HashMap<Pubkey, AccountSharedData> {
+    fn load(&self, pubkey: &Pubkey) -> Option<AccountSharedData> {
+        self.get(pubkey).cloned()
+    }
+
+    fn store(&mut self, pubkey: Pubkey, account: AccountSharedData) {
+        self.insert(pubkey, account);
+    }
+}
+
+/// A Mollusk test environment with an owned account store.
+pub struct MolluskContext<S: AccountStore = HashMap<Pubkey, AccountSharedData>> {
+    pub mollusk: Mollusk,
+    account_store: S,
+}
+
+impl MolluskContext {
+    /// Create a new context with an empty, in-memory account store.
+    pub fn new(mollusk: Mollusk) -> Self {
+        Self::with_account_store(mollusk, HashMap::new())
+    }
+
+    /// Remove an account from the account store, returning it if it existed.
+    pub fn remove_account(&mut self, pubkey: &Pubkey) -> Option<AccountSharedData> {
+        self.account_store.remove(pubkey)
+    }
+}
+
+impl<S: AccountStore> MolluskContext<S> {
+    /// Create a new context backed by the provided account store.
+    pub fn with_account_store(mollusk: Mollusk, account_store: S) -> Self {
+        Self {
+            mollusk,
+            account_store,
+        }
+    }
+
+    /// Get a reference to the account store.
+    pub fn account_store(&self) -> &S {
+        &self.account_store
+    }
+
+    /// Get a mutable reference to the account store.
+    pub fn account_store_mut(&mut self) -> &mut S {
+        &mut self.account_store
+    }
+
+    /// Get an account from the account store.
+    pub fn get_account(&self, pubkey: &Pubkey) -> Option<AccountSharedData> {
+        self.account_store.load(pubkey)
+    }
+
+    /// Set an account in the account store, replacing any existing account.
+    pub fn set_account(&mut self, pubkey: &Pubkey, account: AccountSharedData) {
+        self.account_store.store(*pubkey, account);
+    }
+
+    /// Process an instruction using the accounts in the account store.
+    ///
+    /// Accounts referenced by the instruction but missing from the store are
+    /// provided as zeroed (default) accounts. If the instruction succeeds, the
+    /// resulting accounts are written back to the store. Failed instructions
+    /// leave the store untouched, just like a failed transaction on-chain.
+    pub fn process_instruction(&mut self, instruction: &Instruction) -> InstructionResult {
+        let accounts = self.load_accounts(instruction);
+        self.process_instruction_with_accounts(instruction, &accounts)
+    }
+
+    fn load_accounts(&self, instruction: &Instruction) -> Vec<(Pubkey, AccountSharedData)> {
+        instruction
+            .accounts
+            .iter()
+            .map(|meta| {
+                let account = self.account_store.load(&meta.pubkey).unwrap_or_default();
+                (meta.pubkey, account)
+            })
+            .collect()
+    }
+
+    fn process_instruction_with_accounts(
+        &mut self,
+        instruction: &Instruction,
+        accounts: &[(Pubkey, AccountSharedData)],
+    ) -> InstructionResult {
+        let result = self.mollusk.process_instruction(instruction, accounts);
+
+        if result.program_result == ProgramResult::Success {
+            for (pubkey, account) in result.resulting_accounts.iter() {
+                self.account_store.store(*pubkey, account.clone());
+            }
+        }
+
+        result
+    }
+
+    /// Process an instruction using the accounts in the account store, then
+    /// perform checks on the result. Panics if any checks fail.
+    pub fn process_and_validate_instruction(
+        &mut self,
+        instruction: &Instruction,
+        checks: &[Check],
+    ) -> InstructionResult {
+        let accounts = self.load_accounts(instruction);
+        let result = self.process_instruction_with_accounts(instruction, &accounts);
+        if let Err(failures) = result.run_checks(checks, &accounts, &self.mollusk.sysvars.rent) {
+            result::panic_with_check_failures(&failures, &result);
+        }
+        result
+    }
+
+    /// Process an instruction using the accounts in the account store, then
+    /// perform checks on the result, returning all failed checks.
+    pub fn try_process_and_validate_instruction(
+        &mut self,
+        instruction: &Instruction,
+        checks: &[Check],
+    ) -> Result<InstructionResult, Vec<CheckFailure>> {
+        let accounts = self.load_accounts(instruction);
+        let result = self.process_instruction_with_accounts(instruction, &accounts);
+        result.run_checks(checks, &accounts, &self.mollusk.sysvars.rent)?;
+        Ok(result)
+    }
+}
@@ -0,0 +1,81 @@
+//! Errors returned by the Mollusk API.
+
+use {
+    crate::file::FileError,
+    solana_sdk::{pubkey::Pubkey, system_instruction::MAX_PERMITTED_DATA_LENGTH},
+    std::fmt,
+};
+
+/// An error encountered while setting up a Mollusk test environment, or
+/// preparing an instruction to run in it.
+#[derive(Debug)]
+pub enum MolluskError {
+    /// A program's ELF file could not be loaded.
+    File(FileError),
+    /// A program's ELF failed to load into the runtime, for example due to a
+    /// relocation error or a bad entrypoint, as a `.so` built for the wrong
+    /// target or against an outdated toolchain might.
+    InvalidElf { program_id: Pubkey, error: String },
+    /// The runtime environment a program is loaded into could not be
+    /// created, for example because a syscall override's name collides with
+    /// another syscall.
+    InvalidEnvironment { program_id: Pubkey, error: String },
+    /// No account was provided for these keys, referenced by the
+    /// instruction's account metas.
+    MissingAccounts(Vec<Pubkey>),
+    /// An account's data exceeds the runtime's limit of
+    /// `MAX_PERMITTED_DATA_LENGTH` bytes.
+    AccountDataTooLarge { pubkey: Pubkey, len: usize },
+    /// A transaction's message failed sanitization, or loads addresses from a
+    /// lookup table that was not provided or is invalid.
+    InvalidTransaction(String),
+}
+
+impl fmt::Display for MolluskError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File(err) => write!(f, "Failed to load program: {}", err),
+            Self::InvalidElf { program_id, error } => {
+                write!(f, "Invalid ELF for program {}: {}", program_id, error)
+            }
+            Self::InvalidEnvironment { program_id, error } => write!(
+                f,
+                "Failed to create runtime environment for program {}: {}",
+                program_id, error
+            ),
+            Self::MissingAccounts(pubkeys) => {
+                let pubkeys = pubkeys.iter().map(Pubkey::to_string).collect::<Vec<_>>();
+                write!(
+                    f,
+                    "Accounts referenced by the instruction were not provided: {}",
+                    pubkeys.join(", ")
+                )
+            }
+            Self::AccountDataTooLarge { pubkey, len } => write!(
+                f,
+                "Account {} has {} bytes of data, exceeding the maximum of {} bytes",
+                pubkey, len, MAX_PERMITTED_DATA_LENGTH
+            ),
+            Self::InvalidTransaction(error) => write!(f, "Invalid transaction: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for MolluskError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::File(err) => Some(err),
+            Self::InvalidElf { .. }
+            | Self::InvalidEnvironment { .. }
+            | Self::MissingAccounts(_)
+            | Self::AccountDataTooLarge { .. }
+            | Self::InvalidTransaction(_) => None,
+        }
+    }
+}
+
+impl From<FileError> for MolluskError {
+    fn from(err: FileError) -> Self {
+        Self::File(err)
+    }
+}
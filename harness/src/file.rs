@@ -14,16 +14,76 @@
 //! * `SBF_OUT_DIR`
 //! * The current working directory
 //!
+//! Additional search paths can be provided per `Mollusk` instance with
+//! `Mollusk::add_search_path`.
+//!
+//! Account fixtures can also be loaded from JSON files, in the format produced
+//! by `solana account --output json`.
+//!
 //! Since these functions are intended for the local filesystem and for testing
 //! purposes, most of them will panic if the file is not found or if there is an
 //! error reading the file.
 
-use std::{
-    fs::File,
-    io::Read,
-    path::{Path, PathBuf},
+use {
+    base64::{prelude::BASE64_STANDARD, Engine},
+    serde::Deserialize,
+    solana_sdk::{
+        account::{Account, AccountSharedData},
+        pubkey::Pubkey,
+    },
+    std::{
+        fmt,
+        fs::File,
+        io::{self, Read},
+        path::{Path, PathBuf},
+        str::FromStr,
+    },
 };
 
+/// An error encountered while loading a file from the local filesystem.
+#[derive(Debug)]
+pub enum FileError {
+    /// The file was not found in any of the searched directories.
+    NotFound {
+        file_name: String,
+        searched_dirs: Vec<PathBuf>,
+    },
+    /// The file was found, but could not be read.
+    Io { path: PathBuf, error: io::Error },
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound {
+                file_name,
+                searched_dirs,
+            } => {
+                write!(f, "\"{}\" not found. Searched:", file_name)?;
+                for dir in searched_dirs {
+                    write!(f, "\n  - {}", dir.display())?;
+                }
+                write!(
+                    f,
+                    "\nIf this is your program, make sure it was built with `cargo build-sbf`."
+                )
+            }
+            Self::Io { path, error } => {
+                write!(f, "Failed to read \"{}\": {}", path.display(), error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::NotFound { .. } => None,
+            Self::Io { error, .. } => Some(error),
+        }
+    }
+}
+
 fn default_shared_object_dirs() -> Vec<PathBuf> {
     let mut search_path = vec![PathBuf::from("tests/fixtures")];
 
@@ -42,14 +102,30 @@ fn default_shared_object_dirs() -> Vec<PathBuf> {
     search_path
 }
 
-fn find_file(filename: &str) -> Option<PathBuf> {
-    for dir in default_shared_object_dirs() {
+fn find_file(filename: &str, search_paths: &[PathBuf]) -> Result<PathBuf, FileError> {
+    let mut searched_dirs = default_shared_object_dirs();
+    searched_dirs.extend_from_slice(search_paths);
+    for dir in searched_dirs.iter() {
         let candidate = dir.join(filename);
         if candidate.exists() {
-            return Some(candidate);
+            return Ok(candidate);
         }
     }
-    None
+    Err(FileError::NotFound {
+        file_name: filename.to_string(),
+        searched_dirs,
+    })
+}
+
+fn try_read_file(path: &Path) -> Result<Vec<u8>, FileError> {
+    let io_error = |error| FileError::Io {
+        path: path.to_path_buf(),
+        error,
+    };
+    let mut file = File::open(path).map_err(io_error)?;
+    let mut file_data = Vec::new();
+    file.read_to_end(&mut file_data).map_err(io_error)?;
+    Ok(file_data)
 }
 
 /// Read the contents of a file into a `Vec<u8>`.
@@ -75,9 +151,119 @@ pub fn read_file<P: AsRef<Path>>(path: P) -> Vec<u8> {
 /// * The current working directory
 ///
 /// The name of the program ELF file is expected to be `{program_name}.so`.
+///
+/// Panics with a message listing the searched directories if the file cannot
+/// be found. See `try_load_program_elf` for a non-panicking version.
 pub fn load_program_elf(program_name: &str) -> Vec<u8> {
+    try_load_program_elf(program_name).unwrap_or_else(|err| panic!("{}", err))
+}
+
+/// Load a program ELF file from the local filesystem by program name,
+/// returning an error if it cannot be found or read.
+///
+/// The search paths and file name are the same as for `load_program_elf`.
+pub fn try_load_program_elf(program_name: &str) -> Result<Vec<u8>, FileError> {
+    try_load_program_elf_with_search_paths(program_name, &[])
+}
+
+/// Load a program ELF file from the local filesystem by program name, also
+/// searching the provided directories.
+///
+/// The default search paths are checked first, so `SBF_OUT_DIR` and friends
+/// continue to take precedence. The provided directories are then checked in
+/// order.
+pub fn try_load_program_elf_with_search_paths(
+    program_name: &str,
+    search_paths: &[PathBuf],
+) -> Result<Vec<u8>, FileError> {
     let file_name = format!("{program_name}.so");
-    let program_file = find_file(&file_name)
-        .unwrap_or_else(|| panic!("Program file data not available for \"{}\"", file_name,));
-    read_file(program_file)
+    let program_file = find_file(&file_name, search_paths)?;
+    try_read_file(&program_file)
+}
+
+/// An account as output by `solana account --output json`.
+#[derive(Deserialize)]
+struct JsonKeyedAccount {
+    pubkey: String,
+    account: JsonAccount,
+}
+
+/// An account as output by `solana account --output json`, or as returned by
+/// the `getAccountInfo` RPC method with `base64` encoding.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct JsonAccount {
+    lamports: u64,
+    /// A tuple of the encoded data and its encoding, ie. `["...", "base64"]`.
+    data: (String, String),
+    owner: String,
+    executable: bool,
+    rent_epoch: u64,
+}
+
+fn parse_pubkey(value: &str, source: &str) -> Pubkey {
+    Pubkey::from_str(value)
+        .unwrap_or_else(|err| panic!("Invalid pubkey \"{}\" in \"{}\": {}", value, source, err))
+}
+
+impl JsonAccount {
+    /// Convert to an account, panicking if any field is invalid. `source`
+    /// names where the account came from, for error messages.
+    pub(crate) fn into_account(self, source: &str) -> AccountSharedData {
+        let owner = parse_pubkey(&self.owner, source);
+
+        let (encoded_data, encoding) = &self.data;
+        if encoding != "base64" {
+            panic!(
+                "Unsupported account data encoding \"{}\" in \"{}\"",
+                encoding, source
+            );
+        }
+        let data = BASE64_STANDARD
+            .decode(encoded_data)
+            .unwrap_or_else(|err| panic!("Invalid base64 data in \"{}\": {}", source, err));
+
+        AccountSharedData::from(Account {
+            lamports: self.lamports,
+            data,
+            owner,
+            executable: self.executable,
+            rent_epoch: self.rent_epoch,
+        })
+    }
+}
+
+impl JsonKeyedAccount {
+    fn into_keyed_account(self, path: &Path) -> (Pubkey, AccountSharedData) {
+        let source = path.display().to_string();
+        let pubkey = parse_pubkey(&self.pubkey, &source);
+        (pubkey, self.account.into_account(&source))
+    }
+}
+
+/// Load an account from a JSON file, in the format produced by
+/// `solana account --output json`.
+///
+/// Only `base64` data encoding is supported.
+pub fn load_account_json<P: AsRef<Path>>(path: P) -> (Pubkey, AccountSharedData) {
+    let path = path.as_ref();
+    let file_data = read_file(path);
+    let keyed_account: JsonKeyedAccount = serde_json::from_slice(&file_data)
+        .unwrap_or_else(|err| panic!("Failed to parse \"{}\": {}", path.display(), err));
+    keyed_account.into_keyed_account(path)
+}
+
+/// Load a list of accounts from a JSON file containing an array of accounts,
+/// each in the format produced by `solana account --output json`.
+///
+/// Only `base64` data encoding is supported.
+pub fn load_accounts_json<P: AsRef<Path>>(path: P) -> Vec<(Pubkey, AccountSharedData)> {
+    let path = path.as_ref();
+    let file_data = read_file(path);
+    let keyed_accounts: Vec<JsonKeyedAccount> = serde_json::from_slice(&file_data)
+        .unwrap_or_else(|err| panic!("Failed to parse \"{}\": {}", path.display(), err));
+    keyed_accounts
+        .into_iter()
+        .map(|keyed_account| keyed_account.into_keyed_account(path))
+        .collect()
 }
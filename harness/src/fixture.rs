@@ -0,0 +1,126 @@
+//! Execution fixtures for Mollusk.
+//!
+//! A fixture captures everything needed to replay an instruction's execution:
+//! the environment configuration (compute unit limit, active features, and
+//! sysvars), the instruction and its input accounts, and the effects observed
+//! when it was executed.
+//!
+//! Fixtures can be written to and read from the filesystem in bincode format,
+//! which makes it possible to record an execution once and replay it later,
+//! asserting the program behaves the same way.
+//!
+//! With the `fixture-proto` feature, fixtures can also be encoded with the
+//! protobuf schema in `proto/fixture.proto`, for sharing with other SVM test
+//! tools. See the `proto` module.
+//!
+//! Like the `file` module, these functions are intended for testing purposes
+//! and will panic if a fixture cannot be read or written.
+
+#[cfg(feature = "fixture-proto")]
+pub mod proto;
+
+use {
+    crate::{
+        result::{self, Check, InstructionResult, ProgramResult},
+        sysvar::Sysvars,
+        Mollusk,
+    },
+    serde::{Deserialize, Serialize},
+    solana_sdk::{
+        account::{AccountSharedData, ReadableAccount},
+        instruction::Instruction,
+        pubkey::Pubkey,
+    },
+    std::{fs::File, path::Path},
+};
+
+/// A recorded instruction execution.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Fixture {
+    /// The compute unit limit of the environment.
+    pub compute_unit_limit: u64,
+    /// The IDs of all active features in the environment.
+    pub feature_set: Vec<Pubkey>,
+    /// The sysvars of the environment.
+    pub sysvars: Sysvars,
+    /// The instruction to execute.
+    pub instruction: Instruction,
+    /// The accounts provided to the instruction.
+    pub accounts: Vec<(Pubkey, AccountSharedData)>,
+    /// The number of compute units consumed by the instruction.
+    pub compute_units_consumed: u64,
+    /// The result code of the program's execution.
+    pub program_result: ProgramResult,
+    /// The resulting accounts after executing the instruction.
+    pub resulting_accounts: Vec<(Pubkey, AccountSharedData)>,
+}
+
+impl Fixture {
+    /// Create a fixture from an instruction executed by the provided Mollusk
+    /// instance.
+    pub fn from_execution(
+        mollusk: &Mollusk,
+        instruction: &Instruction,
+        accounts: &[(Pubkey, AccountSharedData)],
+        result: &InstructionResult,
+    ) -> Self {
+        let mut feature_set = mollusk
+            .feature_set
+            .active
+            .keys()
+            .copied()
+            .collect::<Vec<_>>();
+        feature_set.sort();
+        Self {
+            compute_unit_limit: mollusk.compute_budget.compute_unit_limit,
+            feature_set,
+            sysvars: mollusk.sysvars.clone(),
+            instruction: instruction.clone(),
+            accounts: accounts.to_vec(),
+            compute_units_consumed: result.compute_units_consumed,
+            program_result: result.program_result.clone(),
+            resulting_accounts: result.resulting_accounts.clone(),
+        }
+    }
+
+    /// Write the fixture to a file in bincode format.
+    pub fn dump<P: AsRef<Path>>(&self, path: P) {
+        let path = path.as_ref();
+        let file = File::create(path)
+            .unwrap_or_else(|err| panic!("Failed to create {}: {}", path.display(), err));
+        bincode::serialize_into(file, self)
+            .unwrap_or_else(|err| panic!("Failed to write fixture {}: {}", path.display(), err));
+    }
+
+    /// Load a fixture from a file in bincode format.
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .unwrap_or_else(|err| panic!("Failed to open {}: {}", path.display(), err));
+        bincode::deserialize_from(file)
+            .unwrap_or_else(|err| panic!("Failed to read fixture {}: {}", path.display(), err))
+    }
+
+    /// Assert that an instruction result matches the effects recorded in the
+    /// fixture. Compares the compute units consumed, the program result, and
+    /// every resulting account. Panics if any comparison fails.
+    pub fn assert_matches(&self, result: &InstructionResult) {
+        let mut checks = vec![
+            Check::compute_units(self.compute_units_consumed),
+            Check::program_result(self.program_result.clone()),
+        ];
+        for (pubkey, account) in self.resulting_accounts.iter() {
+            checks.push(
+                Check::account(pubkey)
+                    .data(account.data())
+                    .executable(account.executable())
+                    .lamports(account.lamports())
+                    .owner(account.owner())
+                    .build(),
+            );
+        }
+        if let Err(failures) = result.run_checks(&checks, &self.accounts, &self.sysvars.rent) {
+            result::panic_with_check_failures(&failures, result);
+        }
+    }
+}
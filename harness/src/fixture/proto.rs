@@ -0,0 +1,648 @@
+//! Protobuf encoding for fixtures.
+//!
+//! The message types below mirror the schema in `proto/fixture.proto`, so
+//! fixtures can be exchanged with other SVM test tools. Sysvars are encoded
+//! as accounts holding their on-chain (bincode) representation.
+//!
+//! Requires the `fixture-proto` feature.
+
+use {
+    super::Fixture,
+    crate::{result::ProgramResult as MolluskProgramResult, sysvar::Sysvars},
+    prost::{DecodeError, Message},
+    solana_sdk::{
+        account::{AccountSharedData, ReadableAccount, WritableAccount},
+        clock::Clock,
+        epoch_rewards::EpochRewards,
+        epoch_schedule::EpochSchedule,
+        instruction::{
+            AccountMeta as SdkAccountMeta, Instruction as SdkInstruction,
+            InstructionError as SdkInstructionError,
+        },
+        program_error::ProgramError,
+        pubkey::Pubkey,
+        rent::Rent,
+        slot_hashes::SlotHashes,
+        stake_history::StakeHistory,
+        sysvar::{self, last_restart_slot::LastRestartSlot, SysvarId},
+    },
+};
+
+/// The current version of the fixture protobuf schema.
+pub const FIXTURE_PROTO_VERSION: u32 = 1;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Account {
+    #[prost(bytes = "vec", tag = "1")]
+    pub address: Vec<u8>,
+    #[prost(uint64, tag = "2")]
+    pub lamports: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub data: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub owner: Vec<u8>,
+    #[prost(bool, tag = "5")]
+    pub executable: bool,
+    #[prost(uint64, tag = "6")]
+    pub rent_epoch: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct AccountMeta {
+    #[prost(bytes = "vec", tag = "1")]
+    pub pubkey: Vec<u8>,
+    #[prost(bool, tag = "2")]
+    pub is_signer: bool,
+    #[prost(bool, tag = "3")]
+    pub is_writable: bool,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Instruction {
+    #[prost(bytes = "vec", tag = "1")]
+    pub program_id: Vec<u8>,
+    #[prost(message, repeated, tag = "2")]
+    pub accounts: Vec<AccountMeta>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub data: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ComputeBudget {
+    #[prost(uint64, tag = "1")]
+    pub compute_unit_limit: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct FeatureSet {
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    pub features: Vec<Vec<u8>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, prost::Enumeration)]
+#[repr(i32)]
+pub enum InstructionErrorKind {
+    GenericError = 0,
+    InvalidArgument = 1,
+    InvalidInstructionData = 2,
+    InvalidAccountData = 3,
+    AccountDataTooSmall = 4,
+    InsufficientFunds = 5,
+    IncorrectProgramId = 6,
+    MissingRequiredSignature = 7,
+    AccountAlreadyInitialized = 8,
+    UninitializedAccount = 9,
+    UnbalancedInstruction = 10,
+    ModifiedProgramId = 11,
+    ExternalAccountLamportSpend = 12,
+    ExternalAccountDataModified = 13,
+    ReadonlyLamportChange = 14,
+    ReadonlyDataModified = 15,
+    DuplicateAccountIndex = 16,
+    ExecutableModified = 17,
+    RentEpochModified = 18,
+    NotEnoughAccountKeys = 19,
+    AccountDataSizeChanged = 20,
+    AccountNotExecutable = 21,
+    AccountBorrowFailed = 22,
+    AccountBorrowOutstanding = 23,
+    DuplicateAccountOutOfSync = 24,
+    Custom = 25,
+    InvalidError = 26,
+    ExecutableDataModified = 27,
+    ExecutableLamportChange = 28,
+    ExecutableAccountNotRentExempt = 29,
+    UnsupportedProgramId = 30,
+    CallDepth = 31,
+    MissingAccount = 32,
+    ReentrancyNotAllowed = 33,
+    MaxSeedLengthExceeded = 34,
+    InvalidSeeds = 35,
+    InvalidRealloc = 36,
+    ComputationalBudgetExceeded = 37,
+    PrivilegeEscalation = 38,
+    ProgramEnvironmentSetupFailure = 39,
+    ProgramFailedToComplete = 40,
+    ProgramFailedToCompile = 41,
+    Immutable = 42,
+    IncorrectAuthority = 43,
+    BorshIoError = 44,
+    AccountNotRentExempt = 45,
+    InvalidAccountOwner = 46,
+    ArithmeticOverflow = 47,
+    UnsupportedSysvar = 48,
+    IllegalOwner = 49,
+    MaxAccountsDataAllocationsExceeded = 50,
+    MaxAccountsExceeded = 51,
+    MaxInstructionTraceLengthExceeded = 52,
+    BuiltinProgramsMustConsumeComputeUnits = 53,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct InstructionError {
+    #[prost(enumeration = "InstructionErrorKind", tag = "1")]
+    pub kind: i32,
+    #[prost(uint32, tag = "2")]
+    pub custom: u32,
+    #[prost(string, tag = "3")]
+    pub borsh_io_error: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, prost::Enumeration)]
+#[repr(i32)]
+pub enum ProgramResultKind {
+    Success = 0,
+    Failure = 1,
+    UnknownError = 2,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ProgramResult {
+    #[prost(enumeration = "ProgramResultKind", tag = "1")]
+    pub kind: i32,
+    #[prost(uint64, tag = "2")]
+    pub program_error: u64,
+    #[prost(message, optional, tag = "3")]
+    pub instruction_error: Option<InstructionError>,
+}
+
+/// The top-level `Fixture` message.
+#[derive(Clone, PartialEq, Message)]
+pub struct ProtoFixture {
+    #[prost(uint32, tag = "1")]
+    pub version: u32,
+    #[prost(message, optional, tag = "2")]
+    pub compute_budget: Option<ComputeBudget>,
+    #[prost(message, optional, tag = "3")]
+    pub feature_set: Option<FeatureSet>,
+    #[prost(message, repeated, tag = "4")]
+    pub sysvars: Vec<Account>,
+    #[prost(message, optional, tag = "5")]
+    pub instruction: Option<Instruction>,
+    #[prost(message, repeated, tag = "6")]
+    pub accounts: Vec<Account>,
+    #[prost(uint64, tag = "7")]
+    pub compute_units_consumed: u64,
+    #[prost(message, optional, tag = "8")]
+    pub program_result: Option<ProgramResult>,
+    #[prost(message, repeated, tag = "9")]
+    pub resulting_accounts: Vec<Account>,
+}
+
+fn pubkey_from_bytes(bytes: &[u8]) -> Result<Pubkey, DecodeError> {
+    Pubkey::try_from(bytes)
+        .map_err(|_| DecodeError::new(format!("Invalid pubkey in fixture: {} bytes", bytes.len())))
+}
+
+impl From<&(Pubkey, AccountSharedData)> for Account {
+    fn from((pubkey, account): &(Pubkey, AccountSharedData)) -> Self {
+        Self {
+            address: pubkey.to_bytes().to_vec(),
+            lamports: account.lamports(),
+            data: account.data().to_vec(),
+            owner: account.owner().to_bytes().to_vec(),
+            executable: account.executable(),
+            rent_epoch: account.rent_epoch(),
+        }
+    }
+}
+
+impl TryFrom<&Account> for (Pubkey, AccountSharedData) {
+    type Error = DecodeError;
+
+    fn try_from(account: &Account) -> Result<Self, Self::Error> {
+        let mut shared = AccountSharedData::new(
+            account.lamports,
+            account.data.len(),
+            &pubkey_from_bytes(&account.owner)?,
+        );
+        shared.set_data_from_slice(&account.data);
+        shared.set_executable(account.executable);
+        shared.set_rent_epoch(account.rent_epoch);
+        Ok((pubkey_from_bytes(&account.address)?, shared))
+    }
+}
+
+impl From<&SdkInstruction> for Instruction {
+    fn from(instruction: &SdkInstruction) -> Self {
+        Self {
+            program_id: instruction.program_id.to_bytes().to_vec(),
+            accounts: instruction
+                .accounts
+                .iter()
+                .map(|meta| AccountMeta {
+                    pubkey: meta.pubkey.to_bytes().to_vec(),
+                    is_signer: meta.is_signer,
+                    is_writable: meta.is_writable,
+                })
+                .collect(),
+            data: instruction.data.clone(),
+        }
+    }
+}
+
+impl TryFrom<&Instruction> for SdkInstruction {
+    type Error = DecodeError;
+
+    fn try_from(instruction: &Instruction) -> Result<Self, Self::Error> {
+        Ok(Self {
+            program_id: pubkey_from_bytes(&instruction.program_id)?,
+            accounts: instruction
+                .accounts
+                .iter()
+                .map(|meta| {
+                    Ok(SdkAccountMeta {
+                        pubkey: pubkey_from_bytes(&meta.pubkey)?,
+                        is_signer: meta.is_signer,
+                        is_writable: meta.is_writable,
+                    })
+                })
+                .collect::<Result<_, DecodeError>>()?,
+            data: instruction.data.clone(),
+        })
+    }
+}
+
+impl From<&SdkInstructionError> for InstructionError {
+    fn from(error: &SdkInstructionError) -> Self {
+        let kind = match error {
+            SdkInstructionError::GenericError => InstructionErrorKind::GenericError,
+            SdkInstructionError::InvalidArgument => InstructionErrorKind::InvalidArgument,
+            SdkInstructionError::InvalidInstructionData => {
+                InstructionErrorKind::InvalidInstructionData
+            }
+            SdkInstructionError::InvalidAccountData => InstructionErrorKind::InvalidAccountData,
+            SdkInstructionError::AccountDataTooSmall => InstructionErrorKind::AccountDataTooSmall,
+            SdkInstructionError::InsufficientFunds => InstructionErrorKind::InsufficientFunds,
+            SdkInstructionError::IncorrectProgramId => InstructionErrorKind::IncorrectProgramId,
+            SdkInstructionError::MissingRequiredSignature => {
+                InstructionErrorKind::MissingRequiredSignature
+            }
+            SdkInstructionError::AccountAlreadyInitialized => {
+                InstructionErrorKind::AccountAlreadyInitialized
+            }
+            SdkInstructionError::UninitializedAccount => InstructionErrorKind::UninitializedAccount,
+            SdkInstructionError::UnbalancedInstruction => {
+                InstructionErrorKind::UnbalancedInstruction
+            }
+            SdkInstructionError::ModifiedProgramId => InstructionErrorKind::ModifiedProgramId,
+            SdkInstructionError::ExternalAccountLamportSpend => {
+                InstructionErrorKind::ExternalAccountLamportSpend
+            }
+            SdkInstructionError::ExternalAccountDataModified => {
+                InstructionErrorKind::ExternalAccountDataModified
+            }
+            SdkInstructionError::ReadonlyLamportChange => {
+                InstructionErrorKind::ReadonlyLamportChange
+            }
+            SdkInstructionError::ReadonlyDataModified => InstructionErrorKind::ReadonlyDataModified,
+            SdkInstructionError::DuplicateAccountIndex => {
+                InstructionErrorKind::DuplicateAccountIndex
+            }
+            SdkInstructionError::ExecutableModified => InstructionErrorKind::ExecutableModified,
+            SdkInstructionError::RentEpochModified => InstructionErrorKind::RentEpochModified,
+            SdkInstructionError::NotEnoughAccountKeys => InstructionErrorKind::NotEnoughAccountKeys,
+            SdkInstructionError::AccountDataSizeChanged => {
+                InstructionErrorKind::AccountDataSizeChanged
+            }
+            SdkInstructionError::AccountNotExecutable => InstructionErrorKind::AccountNotExecutable,
+            SdkInstructionError::AccountBorrowFailed => InstructionErrorKind::AccountBorrowFailed,
+            SdkInstructionError::AccountBorrowOutstanding => {
+                InstructionErrorKind::AccountBorrowOutstanding
+            }
+            SdkInstructionError::DuplicateAccountOutOfSync => {
+                InstructionErrorKind::DuplicateAccountOutOfSync
+            }
+            SdkInstructionError::Custom(_) => InstructionErrorKind::Custom,
+            SdkInstructionError::InvalidError => InstructionErrorKind::InvalidError,
+            SdkInstructionError::ExecutableDataModified => {
+                InstructionErrorKind::ExecutableDataModified
+            }
+            SdkInstructionError::ExecutableLamportChange => {
+                InstructionErrorKind::ExecutableLamportChange
+            }
+            SdkInstructionError::ExecutableAccountNotRentExempt => {
+                InstructionErrorKind::ExecutableAccountNotRentExempt
+            }
+            SdkInstructionError::UnsupportedProgramId => InstructionErrorKind::UnsupportedProgramId,
+            SdkInstructionError::CallDepth => InstructionErrorKind::CallDepth,
+            SdkInstructionError::MissingAccount => InstructionErrorKind::MissingAccount,
+            SdkInstructionError::ReentrancyNotAllowed => InstructionErrorKind::ReentrancyNotAllowed,
+            SdkInstructionError::MaxSeedLengthExceeded => {
+                InstructionErrorKind::MaxSeedLengthExceeded
+            }
+            SdkInstructionError::InvalidSeeds => InstructionErrorKind::InvalidSeeds,
+            SdkInstructionError::InvalidRealloc => InstructionErrorKind::InvalidRealloc,
+            SdkInstructionError::ComputationalBudgetExceeded => {
+                InstructionErrorKind::ComputationalBudgetExceeded
+            }
+            SdkInstructionError::PrivilegeEscalation => InstructionErrorKind::PrivilegeEscalation,
+            SdkInstructionError::ProgramEnvironmentSetupFailure => {
+                InstructionErrorKind::ProgramEnvironmentSetupFailure
+            }
+            SdkInstructionError::ProgramFailedToComplete => {
+                InstructionErrorKind::ProgramFailedToComplete
+            }
+            SdkInstructionError::ProgramFailedToCompile => {
+                InstructionErrorKind::ProgramFailedToCompile
+            }
+            SdkInstructionError::Immutable => InstructionErrorKind::Immutable,
+            SdkInstructionError::IncorrectAuthority => InstructionErrorKind::IncorrectAuthority,
+            SdkInstructionError::BorshIoError(_) => InstructionErrorKind::BorshIoError,
+            SdkInstructionError::AccountNotRentExempt => InstructionErrorKind::AccountNotRentExempt,
+            SdkInstructionError::InvalidAccountOwner => InstructionErrorKind::InvalidAccountOwner,
+            SdkInstructionError::ArithmeticOverflow => InstructionErrorKind::ArithmeticOverflow,
+            SdkInstructionError::UnsupportedSysvar => InstructionErrorKind::UnsupportedSysvar,
+            SdkInstructionError::IllegalOwner => InstructionErrorKind::IllegalOwner,
+            SdkInstructionError::MaxAccountsDataAllocationsExceeded => {
+                InstructionErrorKind::MaxAccountsDataAllocationsExceeded
+            }
+            SdkInstructionError::MaxAccountsExceeded => InstructionErrorKind::MaxAccountsExceeded,
+            SdkInstructionError::MaxInstructionTraceLengthExceeded => {
+                InstructionErrorKind::MaxInstructionTraceLengthExceeded
+            }
+            SdkInstructionError::BuiltinProgramsMustConsumeComputeUnits => {
+                InstructionErrorKind::BuiltinProgramsMustConsumeComputeUnits
+            }
+        };
+        Self {
+            kind: kind as i32,
+            custom: match error {
+                SdkInstructionError::Custom(code) => *code,
+                _ => 0,
+            },
+            borsh_io_error: match error {
+                SdkInstructionError::BorshIoError(message) => message.clone(),
+                _ => String::new(),
+            },
+        }
+    }
+}
+
+impl TryFrom<&InstructionError> for SdkInstructionError {
+    type Error = DecodeError;
+
+    fn try_from(error: &InstructionError) -> Result<Self, Self::Error> {
+        let kind = InstructionErrorKind::try_from(error.kind).map_err(|_| {
+            DecodeError::new(format!(
+                "Invalid instruction error kind in fixture: {}",
+                error.kind
+            ))
+        })?;
+        Ok(match kind {
+            InstructionErrorKind::GenericError => Self::GenericError,
+            InstructionErrorKind::InvalidArgument => Self::InvalidArgument,
+            InstructionErrorKind::InvalidInstructionData => Self::InvalidInstructionData,
+            InstructionErrorKind::InvalidAccountData => Self::InvalidAccountData,
+            InstructionErrorKind::AccountDataTooSmall => Self::AccountDataTooSmall,
+            InstructionErrorKind::InsufficientFunds => Self::InsufficientFunds,
+            InstructionErrorKind::IncorrectProgramId => Self::IncorrectProgramId,
+            InstructionErrorKind::MissingRequiredSignature => Self::MissingRequiredSignature,
+            InstructionErrorKind::AccountAlreadyInitialized => Self::AccountAlreadyInitialized,
+            InstructionErrorKind::UninitializedAccount => Self::UninitializedAccount,
+            InstructionErrorKind::UnbalancedInstruction => Self::UnbalancedInstruction,
+            InstructionErrorKind::ModifiedProgramId => Self::ModifiedProgramId,
+            InstructionErrorKind::ExternalAccountLamportSpend => Self::ExternalAccountLamportSpend,
+            InstructionErrorKind::ExternalAccountDataModified => Self::ExternalAccountDataModified,
+            InstructionErrorKind::ReadonlyLamportChange => Self::ReadonlyLamportChange,
+            InstructionErrorKind::ReadonlyDataModified => Self::ReadonlyDataModified,
+            InstructionErrorKind::DuplicateAccountIndex => Self::DuplicateAccountIndex,
+            InstructionErrorKind::ExecutableModified => Self::ExecutableModified,
+            InstructionErrorKind::RentEpochModified => Self::RentEpochModified,
+            InstructionErrorKind::NotEnoughAccountKeys => Self::NotEnoughAccountKeys,
+            InstructionErrorKind::AccountDataSizeChanged => Self::AccountDataSizeChanged,
+            InstructionErrorKind::AccountNotExecutable => Self::AccountNotExecutable,
+            InstructionErrorKind::AccountBorrowFailed => Self::AccountBorrowFailed,
+            InstructionErrorKind::AccountBorrowOutstanding => Self::AccountBorrowOutstanding,
+            InstructionErrorKind::DuplicateAccountOutOfSync => Self::DuplicateAccountOutOfSync,
+            InstructionErrorKind::Custom => Self::Custom(error.custom),
+            InstructionErrorKind::InvalidError => Self::InvalidError,
+            InstructionErrorKind::ExecutableDataModified => Self::ExecutableDataModified,
+            InstructionErrorKind::ExecutableLamportChange => Self::ExecutableLamportChange,
+            InstructionErrorKind::ExecutableAccountNotRentExempt => {
+                Self::ExecutableAccountNotRentExempt
+            }
+            InstructionErrorKind::UnsupportedProgramId => Self::UnsupportedProgramId,
+            InstructionErrorKind::CallDepth => Self::CallDepth,
+            InstructionErrorKind::MissingAccount => Self::MissingAccount,
+            InstructionErrorKind::ReentrancyNotAllowed => Self::ReentrancyNotAllowed,
+            InstructionErrorKind::MaxSeedLengthExceeded => Self::MaxSeedLengthExceeded,
+            InstructionErrorKind::InvalidSeeds => Self::InvalidSeeds,
+            InstructionErrorKind::InvalidRealloc => Self::InvalidRealloc,
+            InstructionErrorKind::ComputationalBudgetExceeded => Self::ComputationalBudgetExceeded,
+            InstructionErrorKind::PrivilegeEscalation => Self::PrivilegeEscalation,
+            InstructionErrorKind::ProgramEnvironmentSetupFailure => {
+                Self::ProgramEnvironmentSetupFailure
+            }
+            InstructionErrorKind::ProgramFailedToComplete => Self::ProgramFailedToComplete,
+            InstructionErrorKind::ProgramFailedToCompile => Self::ProgramFailedToCompile,
+            InstructionErrorKind::Immutable => Self::Immutable,
+            InstructionErrorKind::IncorrectAuthority => Self::IncorrectAuthority,
+            InstructionErrorKind::BorshIoError => Self::BorshIoError(error.borsh_io_error.clone()),
+            InstructionErrorKind::AccountNotRentExempt => Self::AccountNotRentExempt,
+            InstructionErrorKind::InvalidAccountOwner => Self::InvalidAccountOwner,
+            InstructionErrorKind::ArithmeticOverflow => Self::ArithmeticOverflow,
+            InstructionErrorKind::UnsupportedSysvar => Self::UnsupportedSysvar,
+            InstructionErrorKind::IllegalOwner => Self::IllegalOwner,
+            InstructionErrorKind::MaxAccountsDataAllocationsExceeded => {
+                Self::MaxAccountsDataAllocationsExceeded
+            }
+            InstructionErrorKind::MaxAccountsExceeded => Self::MaxAccountsExceeded,
+            InstructionErrorKind::MaxInstructionTraceLengthExceeded => {
+                Self::MaxInstructionTraceLengthExceeded
+            }
+            InstructionErrorKind::BuiltinProgramsMustConsumeComputeUnits => {
+                Self::BuiltinProgramsMustConsumeComputeUnits
+            }
+        })
+    }
+}
+
+impl From<&MolluskProgramResult> for ProgramResult {
+    fn from(result: &MolluskProgramResult) -> Self {
+        match result {
+            MolluskProgramResult::Success => Self {
+                kind: ProgramResultKind::Success as i32,
+                ..Default::default()
+            },
+            MolluskProgramResult::Failure(err) => Self {
+                kind: ProgramResultKind::Failure as i32,
+                program_error: u64::from(err.clone()),
+                ..Default::default()
+            },
+            MolluskProgramResult::UnknownError(err) => Self {
+                kind: ProgramResultKind::UnknownError as i32,
+                instruction_error: Some(err.into()),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl TryFrom<&ProgramResult> for MolluskProgramResult {
+    type Error = DecodeError;
+
+    fn try_from(result: &ProgramResult) -> Result<Self, Self::Error> {
+        match ProgramResultKind::try_from(result.kind) {
+            Ok(ProgramResultKind::Success) => Ok(Self::Success),
+            Ok(ProgramResultKind::Failure) => {
+                Ok(Self::Failure(ProgramError::from(result.program_error)))
+            }
+            Ok(ProgramResultKind::UnknownError) => {
+                let err = result
+                    .instruction_error
+                    .as_ref()
+                    .ok_or_else(|| DecodeError::new("Fixture is missing an instruction error"))?;
+                Ok(Self::UnknownError(err.try_into()?))
+            }
+            Err(_) => Err(DecodeError::new(format!(
+                "Invalid program result kind in fixture: {}",
+                result.kind
+            ))),
+        }
+    }
+}
+
+fn sysvar_account<T: serde::Serialize>(id: &Pubkey, sysvar: &T) -> Account {
+    Account {
+        address: id.to_bytes().to_vec(),
+        data: bincode::serialize(sysvar).unwrap(),
+        owner: sysvar::id().to_bytes().to_vec(),
+        ..Default::default()
+    }
+}
+
+fn sysvars_to_accounts(sysvars: &Sysvars) -> Vec<Account> {
+    vec![
+        sysvar_account(&Clock::id(), &sysvars.clock),
+        sysvar_account(&EpochRewards::id(), &sysvars.epoch_rewards),
+        sysvar_account(&EpochSchedule::id(), &sysvars.epoch_schedule),
+        sysvar_account(&LastRestartSlot::id(), &sysvars.last_restart_slot),
+        sysvar_account(&Rent::id(), &sysvars.rent),
+        sysvar_account(&SlotHashes::id(), &sysvars.slot_hashes),
+        sysvar_account(&StakeHistory::id(), &sysvars.stake_history),
+    ]
+}
+
+// Sysvars missing from the fixture keep their default values, and unknown
+// sysvars are ignored.
+fn sysvars_from_accounts(accounts: &[Account]) -> Result<Sysvars, DecodeError> {
+    fn decode<T: serde::de::DeserializeOwned>(data: &[u8]) -> Result<T, DecodeError> {
+        bincode::deserialize(data)
+            .map_err(|err| DecodeError::new(format!("Invalid sysvar data in fixture: {}", err)))
+    }
+
+    let mut sysvars = Sysvars::default();
+    for account in accounts {
+        let id = pubkey_from_bytes(&account.address)?;
+        if id == Clock::id() {
+            sysvars.clock = decode(&account.data)?;
+        } else if id == EpochRewards::id() {
+            sysvars.epoch_rewards = decode(&account.data)?;
+        } else if id == EpochSchedule::id() {
+            sysvars.epoch_schedule = decode(&account.data)?;
+        } else if id == LastRestartSlot::id() {
+            sysvars.last_restart_slot = decode(&account.data)?;
+        } else if id == Rent::id() {
+            sysvars.rent = decode(&account.data)?;
+        } else if id == SlotHashes::id() {
+            sysvars.slot_hashes = decode(&account.data)?;
+        } else if id == StakeHistory::id() {
+            sysvars.stake_history = decode(&account.data)?;
+        }
+    }
+    Ok(sysvars)
+}
+
+impl From<&Fixture> for ProtoFixture {
+    fn from(fixture: &Fixture) -> Self {
+        Self {
+            version: FIXTURE_PROTO_VERSION,
+            compute_budget: Some(ComputeBudget {
+                compute_unit_limit: fixture.compute_unit_limit,
+            }),
+            feature_set: Some(FeatureSet {
+                features: fixture
+                    .feature_set
+                    .iter()
+                    .map(|id| id.to_bytes().to_vec())
+                    .collect(),
+            }),
+            sysvars: sysvars_to_accounts(&fixture.sysvars),
+            instruction: Some((&fixture.instruction).into()),
+            accounts: fixture.accounts.iter().map(Into::into).collect(),
+            compute_units_consumed: fixture.compute_units_consumed,
+            program_result: Some((&fixture.program_result).into()),
+            resulting_accounts: fixture.resulting_accounts.iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl TryFrom<&ProtoFixture> for Fixture {
+    type Error = DecodeError;
+
+    fn try_from(proto: &ProtoFixture) -> Result<Self, Self::Error> {
+        if proto.version != FIXTURE_PROTO_VERSION {
+            return Err(DecodeError::new(format!(
+                "Unsupported fixture version: {} (expected {})",
+                proto.version, FIXTURE_PROTO_VERSION
+            )));
+        }
+        Ok(Self {
+            compute_unit_limit: proto
+                .compute_budget
+                .as_ref()
+                .map(|budget| budget.compute_unit_limit)
+                .unwrap_or_default(),
+            feature_set: proto
+                .feature_set
+                .as_ref()
+                .map(|set| {
+                    set.features
+                        .iter()
+                        .map(Vec::as_slice)
+                        .map(pubkey_from_bytes)
+                        .collect::<Result<_, DecodeError>>()
+                })
+                .transpose()?
+                .unwrap_or_default(),
+            sysvars: sysvars_from_accounts(&proto.sysvars)?,
+            instruction: proto
+                .instruction
+                .as_ref()
+                .ok_or_else(|| DecodeError::new("Fixture is missing an instruction"))?
+                .try_into()?,
+            accounts: proto
+                .accounts
+                .iter()
+                .map(TryInto::try_into)
+                .collect::<Result<_, DecodeError>>()?,
+            compute_units_consumed: proto.compute_units_consumed,
+            program_result: proto
+                .program_result
+                .as_ref()
+                .map(TryInto::try_into)
+                .transpose()?
+                .unwrap_or(MolluskProgramResult::Success),
+            resulting_accounts: proto
+                .resulting_accounts
+                .iter()
+                .map(TryInto::try_into)
+                .collect::<Result<_, DecodeError>>()?,
+        })
+    }
+}
+
+impl Fixture {
+    /// Encode the fixture using the protobuf schema in `proto/fixture.proto`.
+    pub fn to_proto_bytes(&self) -> Vec<u8> {
+        ProtoFixture::from(self).encode_to_vec()
+    }
+
+    /// Decode a fixture encoded with the protobuf schema in
+    /// `proto/fixture.proto`, returning an error if the bytes are not a valid
+    /// fixture, or if the fixture's schema version is unsupported.
+    pub fn from_proto_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        Self::try_from(&ProtoFixture::decode(bytes)?)
+    }
+}
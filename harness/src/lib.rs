@@ -26,36 +26,81 @@
 //! * `process_instruction`: Process an instruction and return the result.
 //! * `process_and_validate_instruction`: Process an instruction and perform a
 //!   series of checks on the result, panicking if any checks fail.
+//!
+//! For tests spanning multiple instructions, the `MolluskContext` wraps a
+//! `Mollusk` instance with an account store, so account state carries over
+//! from one instruction to the next.
 
+pub mod bench;
+pub mod builder;
+pub mod context;
+pub mod error;
 pub mod file;
+pub mod fixture;
+#[cfg(feature = "macros")]
+mod macros;
 pub mod program;
+pub mod record;
 pub mod result;
+#[cfg(feature = "rpc")]
+pub mod rpc;
 pub mod sysvar;
 
 use {
     crate::{
-        program::ProgramCache,
-        result::{Check, InstructionResult},
+        bench::BenchResult,
+        builder::MolluskBuilder,
+        error::MolluskError,
+        fixture::Fixture,
+        program::{ProgramCache, SyscallOverrides},
+        record::RecordedExecution,
+        result::{Check, CheckFailure, InstructionResult, ProgramResult},
         sysvar::Sysvars,
     },
-    solana_compute_budget::compute_budget::ComputeBudget,
+    borsh::BorshDeserialize,
+    solana_compute_budget::{
+        compute_budget::ComputeBudget,
+        compute_budget_processor::{
+            MAX_COMPUTE_UNIT_LIMIT, MAX_HEAP_FRAME_BYTES, MIN_HEAP_FRAME_BYTES,
+        },
+    },
     solana_program_runtime::{
         invoke_context::{EnvironmentConfig, InvokeContext},
+        log_collector::LogCollector,
         sysvar_cache::SysvarCache,
         timings::ExecuteTimings,
     },
     solana_sdk::{
-        account::AccountSharedData,
-        bpf_loader_upgradeable,
+        account::{
+            create_account_shared_data_with_fields, AccountSharedData, ReadableAccount,
+            WritableAccount,
+        },
+        address_lookup_table::state::AddressLookupTable,
+        bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+        clock::Clock,
+        compute_budget::{self, ComputeBudgetInstruction},
+        epoch_rewards::EpochRewards,
         feature_set::FeatureSet,
         fee::FeeStructure,
         hash::Hash,
-        instruction::Instruction,
+        instruction::{AccountMeta, Instruction, InstructionError, TRANSACTION_LEVEL_STACK_HEIGHT},
+        message::{
+            v0::{self, LoadedAddresses, MessageAddressTableLookup},
+            LegacyMessage, SanitizedMessage, SanitizedVersionedMessage, VersionedMessage,
+        },
+        native_loader,
         pubkey::Pubkey,
-        rent::Rent,
+        reserved_account_keys::ReservedAccountKeys,
+        system_instruction::MAX_PERMITTED_DATA_LENGTH,
+        transaction::VersionedTransaction,
         transaction_context::{InstructionAccount, TransactionContext},
     },
-    std::sync::Arc,
+    std::{
+        collections::HashMap,
+        path::{Path, PathBuf},
+        sync::{Arc, Once},
+        time::Instant,
+    },
 };
 
 const PROGRAM_ACCOUNTS_LEN: usize = 1;
@@ -65,6 +110,11 @@ const PROGRAM_INDICES: &[u16] = &[0];
 ///
 /// All fields can be manipulated through a handful of helper methods, but
 /// users can also directly access and modify them if they desire more control.
+///
+/// A configured instance can be cloned, for example to run independent
+/// instructions on separate threads. Clones share compiled programs, but each
+/// clone's configuration and program cache can be changed independently.
+#[derive(Clone)]
 pub struct Mollusk {
     pub compute_budget: ComputeBudget,
     pub feature_set: FeatureSet,
@@ -72,17 +122,56 @@ pub struct Mollusk {
     pub program_account: AccountSharedData,
     pub program_cache: ProgramCache,
     pub program_id: Pubkey,
+    /// Additional directories to search for program ELF files, after the
+    /// default search paths.
+    pub search_paths: Vec<PathBuf>,
     pub sysvars: Sysvars,
+    /// Skip the check that an account was provided for every account
+    /// referenced by an instruction, done by `try_process_instruction` and
+    /// the validating methods. Accounts not provided are then missing from
+    /// the instruction, as with `process_instruction`.
+    pub allow_missing_accounts: bool,
+    /// The blockhash of the environment, from which the System program
+    /// derives durable nonces. It's also the only entry of the deprecated
+    /// `RecentBlockhashes` sysvar.
+    pub blockhash: Hash,
+    // A pre-built sysvar cache, used in place of the one otherwise derived
+    // from `sysvars` for each instruction. See `set_sysvar_cache`.
+    sysvar_cache: Option<SysvarCache>,
 }
 
+#[rustfmt::skip]
+const DEFAULT_LOG_FILTER: &str =
+    "mollusk_svm=warn,\
+     solana_rbpf::vm=debug,\
+     solana_runtime::message_processor=debug,\
+     solana_runtime::system_instruction_processor=trace";
+
+// Guards installation of the logger, so it's installed at most once, and
+// never over a filter set with `Mollusk::set_log_filter`.
+static LOGGER_INIT: Once = Once::new();
+
 impl Default for Mollusk {
+    /// Create a Mollusk instance for the System program.
+    ///
+    /// The first instance created installs a global logger with verbose VM
+    /// debug logging, unless `RUST_LOG` is set or a filter was already set
+    /// with `Mollusk::set_log_filter`. See `with_logs_disabled` to skip the
+    /// logger entirely.
     fn default() -> Self {
-        #[rustfmt::skip]
-        solana_logger::setup_with_default(
-            "solana_rbpf::vm=debug,\
-             solana_runtime::message_processor=debug,\
-             solana_runtime::system_instruction_processor=trace",
-        );
+        LOGGER_INIT.call_once(|| solana_logger::setup_with_default(DEFAULT_LOG_FILTER));
+        Self::with_logs_disabled()
+    }
+}
+
+impl Mollusk {
+    /// Create a Mollusk instance for the System program, like
+    /// `Mollusk::default()`, without installing a logger.
+    ///
+    /// Log messages emitted by programs are still collected in each
+    /// `InstructionResult`. Only the runtime's own logging, which would be
+    /// printed by a global logger, is skipped.
+    pub fn with_logs_disabled() -> Self {
         let (program_id, program_account) = program::system_program();
         Self {
             compute_budget: ComputeBudget::default(),
@@ -91,32 +180,105 @@ impl Default for Mollusk {
             program_account,
             program_cache: ProgramCache::default(),
             program_id,
+            search_paths: Vec::new(),
             sysvars: Sysvars::default(),
+            allow_missing_accounts: false,
+            blockhash: Hash::default(),
+            sysvar_cache: None,
         }
     }
-}
 
-impl Mollusk {
+    /// Install a global logger with the provided `env_logger` filter, such as
+    /// `"error"` or `"solana_rbpf::vm=debug"`, replacing any logger installed
+    /// by Mollusk.
+    ///
+    /// Mollusk instances created afterwards don't install their own logger.
+    pub fn set_log_filter(filter: &str) {
+        LOGGER_INIT.call_once(|| ());
+        solana_logger::setup_with(filter);
+    }
+
     /// Create a new Mollusk instance for the provided program.
     ///
     /// Attempts the load the program's ELF file from the default search paths.
     /// Once loaded, adds the program to the program cache and updates the
     /// Mollusk instance with the program's ID and account.
+    ///
+    /// Panics if the program's ELF file cannot be found. See `try_new` for a
+    /// non-panicking version.
     pub fn new(program_id: &Pubkey, program_name: &'static str) -> Self {
+        Self::try_new(program_id, program_name).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Create a new Mollusk instance for the provided program, returning an
+    /// error if the program's ELF file cannot be loaded.
+    ///
+    /// If the file is not found, the error lists every directory searched. If
+    /// the ELF is invalid, the error includes the loader's reason.
+    pub fn try_new(program_id: &Pubkey, program_name: &'static str) -> Result<Self, MolluskError> {
+        let elf = file::try_load_program_elf(program_name)?;
         let mut mollusk = Self {
             program_id: *program_id,
             program_account: program::program_account(program_id),
             ..Default::default()
         };
-        mollusk.add_program(program_id, program_name);
+        mollusk.try_add_program_with_elf(program_id, &bpf_loader_upgradeable::id(), &elf)?;
+        Ok(mollusk)
+    }
+
+    /// Create a new Mollusk instance for the provided program, with the
+    /// environment starting at `slot` rather than genesis.
+    ///
+    /// See `Sysvars::new_at_slot` for how the sysvars are initialized. Panics
+    /// if the program's ELF file cannot be found.
+    pub fn new_at_slot(program_id: &Pubkey, program_name: &'static str, slot: u64) -> Self {
+        let mut mollusk = Self::new(program_id, program_name);
+        mollusk.sysvars = Sysvars::new_at_slot(slot);
+        mollusk
+    }
+
+    /// Create a Mollusk instance for the provided program and ELF, with the
+    /// same configuration as this one: its compute budget, feature set, fee
+    /// structure, and sysvars.
+    ///
+    /// Unlike `clone`, the program cache is fresh, holding only the default
+    /// builtins and the provided program, added under BPF Loader Upgradeable.
+    /// Useful for comparing two builds of a program side by side. Panics if
+    /// the ELF fails to load or verify.
+    pub fn clone_with_program(&self, program_id: &Pubkey, elf: &[u8]) -> Self {
+        let mut mollusk = Self {
+            compute_budget: self.compute_budget,
+            feature_set: self.feature_set.clone(),
+            fee_structure: self.fee_structure.clone(),
+            program_account: program::program_account(program_id),
+            program_cache: ProgramCache::default(),
+            program_id: *program_id,
+            search_paths: self.search_paths.clone(),
+            sysvars: self.sysvars.clone(),
+            allow_missing_accounts: self.allow_missing_accounts,
+            blockhash: self.blockhash,
+            sysvar_cache: self.sysvar_cache.clone(),
+        };
+        mollusk
+            .program_cache
+            .recompile_programs(&mollusk.compute_budget, &mollusk.feature_set);
+        mollusk.add_program_bytes(program_id, elf);
         mollusk
     }
 
+    /// Create a builder for configuring a new Mollusk instance.
+    pub fn builder() -> MolluskBuilder {
+        MolluskBuilder::new()
+    }
+
     /// Add a program to the test environment.
     ///
     /// If you intend to CPI to a program, this is likely what you want to use.
+    /// The program's ELF file is loaded from the default search paths, or any
+    /// added with `add_search_path`.
     pub fn add_program(&mut self, program_id: &Pubkey, program_name: &'static str) {
-        let elf = file::load_program_elf(program_name);
+        let elf = file::try_load_program_elf_with_search_paths(program_name, &self.search_paths)
+            .unwrap_or_else(|err| panic!("{}", err));
         self.program_cache.add_program(
             program_id,
             &bpf_loader_upgradeable::id(),
@@ -126,17 +288,332 @@ impl Mollusk {
         );
     }
 
+    /// Add a program to the test environment, overriding some of the
+    /// syscalls available to it, as with `add_program`.
+    ///
+    /// Useful for injecting faults, such as a failing CPI, or for stubbing
+    /// out syscalls the program depends on. See `SyscallOverrides`.
+    pub fn add_program_with_syscalls(
+        &mut self,
+        program_id: &Pubkey,
+        program_name: &'static str,
+        syscalls: SyscallOverrides,
+    ) {
+        let elf = file::try_load_program_elf_with_search_paths(program_name, &self.search_paths)
+            .unwrap_or_else(|err| panic!("{}", err));
+        self.program_cache.add_program_with_syscalls(
+            program_id,
+            &bpf_loader_upgradeable::id(),
+            &elf,
+            &self.compute_budget,
+            &self.feature_set,
+            syscalls,
+        );
+    }
+
+    /// Add a directory to search for program ELF files.
+    ///
+    /// Added directories are searched after the default search paths, in the
+    /// order they were added.
+    pub fn add_search_path<P: AsRef<Path>>(&mut self, path: P) {
+        self.search_paths.push(path.as_ref().to_path_buf());
+    }
+
     /// Add a program to the test environment using a provided ELF.
     ///
-    /// If you intend to CPI to a program, this is likely what you want to use.
+    /// The loader can be any of BPF Loader 2, BPF Loader Upgradeable, or
+    /// Loader v4. If you intend to CPI to a program, this is likely what you
+    /// want to use.
+    ///
+    /// Panics if the ELF fails to load or verify. See
+    /// `try_add_program_with_elf`.
     pub fn add_program_with_elf(&mut self, program_id: &Pubkey, loader_key: &Pubkey, elf: &[u8]) {
-        self.program_cache.add_program(
+        self.try_add_program_with_elf(program_id, loader_key, elf)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    /// Add a BPF Loader Upgradeable program to the test environment using a
+    /// provided ELF, such as one embedded with `include_bytes!`, without
+    /// relying on the search paths.
+    ///
+    /// Panics if the ELF fails to load or verify.
+    pub fn add_program_bytes(&mut self, program_id: &Pubkey, elf: &[u8]) {
+        self.add_program_with_elf(program_id, &bpf_loader_upgradeable::id(), elf);
+    }
+
+    /// Add a program to the test environment using a provided ELF, returning
+    /// `MolluskError::InvalidElf` with the loader's reason if the ELF fails
+    /// to load or verify, such as a relocation error or a bad entrypoint.
+    pub fn try_add_program_with_elf(
+        &mut self,
+        program_id: &Pubkey,
+        loader_key: &Pubkey,
+        elf: &[u8],
+    ) -> Result<(), MolluskError> {
+        self.program_cache.try_add_program(
             program_id,
             loader_key,
             elf,
             &self.compute_budget,
             &self.feature_set,
+        )
+    }
+
+    /// Upgrade a program in the test environment to a new ELF, as if it were
+    /// redeployed at the current slot of the `Clock` sysvar.
+    ///
+    /// Subsequent instructions, including those invoking the program through
+    /// CPI, execute the new ELF. The program keeps its loader. If it is the
+    /// program under test, its program account is recreated for the new ELF.
+    ///
+    /// Panics if the program was never added, or is a builtin.
+    pub fn upgrade_program(&mut self, program_id: &Pubkey, new_elf: &[u8]) {
+        self.program_cache.upgrade_program(
+            program_id,
+            new_elf,
+            self.sysvars.clock.slot,
+            &self.compute_budget,
+            &self.feature_set,
         );
+        if self.program_id == *program_id {
+            self.program_account = self.program_cache.program_account(program_id).unwrap();
+        }
+    }
+
+    /// Add a BPF Loader Upgradeable program to the test environment from its
+    /// program and program data accounts, such as those cloned from a
+    /// cluster.
+    ///
+    /// The ELF is read from the program data account, after the
+    /// `UpgradeableLoaderState::ProgramData` metadata. Panics if the program
+    /// account is not an upgradeable program account, or if the program data
+    /// account is not an upgradeable program data account.
+    pub fn add_program_from_accounts(
+        &mut self,
+        program_id: &Pubkey,
+        program_account: &AccountSharedData,
+        programdata_account: &AccountSharedData,
+    ) {
+        match bincode::deserialize(program_account.data()) {
+            Ok(UpgradeableLoaderState::Program { .. }) => (),
+            _ => panic!("{} is not an upgradeable program account", program_id),
+        }
+        let elf_offset = UpgradeableLoaderState::size_of_programdata_metadata();
+        match programdata_account
+            .data()
+            .get(..elf_offset)
+            .map(bincode::deserialize)
+        {
+            Some(Ok(UpgradeableLoaderState::ProgramData { .. })) => (),
+            _ => panic!("Invalid program data account for {}", program_id),
+        }
+        self.add_program_with_elf(
+            program_id,
+            &bpf_loader_upgradeable::id(),
+            &programdata_account.data()[elf_offset..],
+        );
+    }
+
+    /// Fetch an account from a live RPC endpoint, for use as an input
+    /// account.
+    ///
+    /// If the account is an executable program owned by one of the BPF
+    /// loaders, the program is also added to the program cache, fetching its
+    /// program data account if necessary.
+    ///
+    /// Panics if the request fails or the account does not exist.
+    #[cfg(feature = "rpc")]
+    pub fn clone_account_from_rpc(&mut self, url: &str, pubkey: &Pubkey) -> AccountSharedData {
+        let account = rpc::get_account(url, pubkey);
+        self.add_program_from_rpc(url, pubkey, &account);
+        account
+    }
+
+    /// Fetch a list of accounts from a live RPC endpoint, as with
+    /// `clone_account_from_rpc`, in as few requests as possible.
+    ///
+    /// Panics if any request fails or any account does not exist.
+    #[cfg(feature = "rpc")]
+    pub fn clone_accounts_from_rpc(
+        &mut self,
+        url: &str,
+        pubkeys: &[Pubkey],
+    ) -> Vec<(Pubkey, AccountSharedData)> {
+        let accounts = rpc::get_multiple_accounts(url, pubkeys)
+            .into_iter()
+            .zip(pubkeys)
+            .map(|(account, pubkey)| {
+                let account =
+                    account.unwrap_or_else(|| panic!("Account {} not found at {}", pubkey, url));
+                (*pubkey, account)
+            })
+            .collect::<Vec<_>>();
+        for (pubkey, account) in accounts.iter() {
+            self.add_program_from_rpc(url, pubkey, account);
+        }
+        accounts
+    }
+
+    #[cfg(feature = "rpc")]
+    fn add_program_from_rpc(&mut self, url: &str, pubkey: &Pubkey, account: &AccountSharedData) {
+        if !account.executable() {
+            return;
+        }
+        let owner = account.owner();
+        if *owner == bpf_loader_upgradeable::id() {
+            if let Ok(UpgradeableLoaderState::Program {
+                programdata_address,
+            }) = bincode::deserialize(account.data())
+            {
+                let programdata_account = rpc::get_account(url, &programdata_address);
+                self.add_program_from_accounts(pubkey, account, &programdata_account);
+            }
+        } else if *owner == solana_sdk::bpf_loader::id()
+            || *owner == solana_sdk::bpf_loader_deprecated::id()
+        {
+            self.add_program_with_elf(pubkey, owner, account.data());
+        }
+    }
+
+    /// Add the SPL Token program to the test environment.
+    ///
+    /// The program's ELF file is expected to be named `spl_token.so` and
+    /// located in one of the default search paths. It can be dumped from a
+    /// cluster with
+    /// `solana program dump TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA
+    /// spl_token.so`.
+    pub fn add_spl_token(&mut self) {
+        self.add_program(&program::spl_token::id(), "spl_token");
+    }
+
+    /// Add the Stake program to the test environment, so it can be invoked
+    /// directly or through CPI. Requires the `all-builtins` feature.
+    #[cfg(feature = "all-builtins")]
+    pub fn add_stake_program(&mut self) {
+        self.program_cache
+            .add_optional_builtin(&solana_sdk::stake::program::id());
+    }
+
+    /// Add the Vote program to the test environment, so it can be invoked
+    /// directly or through CPI. Requires the `all-builtins` feature.
+    #[cfg(feature = "all-builtins")]
+    pub fn add_vote_program(&mut self) {
+        self.program_cache
+            .add_optional_builtin(&solana_sdk::vote::program::id());
+    }
+
+    /// Add the Config program to the test environment, so it can be invoked
+    /// directly or through CPI. Requires the `all-builtins` feature.
+    #[cfg(feature = "all-builtins")]
+    pub fn add_config_program(&mut self) {
+        self.program_cache
+            .add_optional_builtin(&solana_sdk::config::program::id());
+    }
+
+    /// Add the Address Lookup Table program to the test environment, so it
+    /// can be invoked directly or through CPI. Requires the `all-builtins`
+    /// feature.
+    #[cfg(feature = "all-builtins")]
+    pub fn add_address_lookup_table_program(&mut self) {
+        self.program_cache
+            .add_optional_builtin(&solana_sdk::address_lookup_table::program::id());
+    }
+
+    /// Add every builtin program that is not loaded by default: the Stake,
+    /// Vote, Config, and Address Lookup Table programs. Together with the
+    /// defaults, this matches the native programs loaded by a validator.
+    /// Requires the `all-builtins` feature.
+    #[cfg(feature = "all-builtins")]
+    pub fn add_all_builtins(&mut self) {
+        program::optional_builtin_ids()
+            .for_each(|program_id| self.program_cache.add_optional_builtin(&program_id));
+    }
+
+    /// Remove a program from the test environment.
+    ///
+    /// Subsequent invocations of the program, directly or through CPI, will
+    /// fail as if the program was never added. If the removed program is the
+    /// program under test, Mollusk falls back to the System program, as with
+    /// `Mollusk::default()`.
+    pub fn remove_program(&mut self, program_id: &Pubkey) {
+        self.program_cache.remove_program(program_id);
+        if self.program_id == *program_id {
+            let (program_id, program_account) = program::system_program();
+            self.program_id = program_id;
+            self.program_account = program_account;
+        }
+    }
+
+    /// Activate a feature in the test environment.
+    ///
+    /// Programs already in the cache are recompiled, so their runtime
+    /// environment reflects the updated feature set.
+    pub fn activate_feature(&mut self, feature_id: &Pubkey) {
+        self.feature_set.activate(feature_id, 0);
+        self.program_cache
+            .recompile_programs(&self.compute_budget, &self.feature_set);
+    }
+
+    /// Deactivate a feature in the test environment.
+    ///
+    /// Programs already in the cache are recompiled, so their runtime
+    /// environment reflects the updated feature set.
+    pub fn deactivate_feature(&mut self, feature_id: &Pubkey) {
+        self.feature_set.deactivate(feature_id);
+        self.program_cache
+            .recompile_programs(&self.compute_budget, &self.feature_set);
+    }
+
+    /// Set the compute unit limit for subsequent instructions, leaving the
+    /// rest of the compute budget unchanged.
+    pub fn set_compute_unit_limit(&mut self, units: u64) {
+        self.compute_budget.compute_unit_limit = units;
+    }
+
+    /// Set the program heap size, in bytes, for subsequent instructions,
+    /// leaving the rest of the compute budget unchanged.
+    ///
+    /// On-chain, a requested heap frame must be a multiple of 1024 bytes,
+    /// between 32 KiB and 256 KiB. This is not enforced here.
+    pub fn set_heap_size(&mut self, bytes: u32) {
+        self.compute_budget.heap_size = bytes;
+    }
+
+    /// Set the blockhash of the environment, as exposed to programs through
+    /// the `RecentBlockhashes` sysvar, and used by the System program to
+    /// advance durable nonces.
+    pub fn set_blockhash(&mut self, blockhash: Hash) {
+        self.blockhash = blockhash;
+    }
+
+    /// Use a pre-built sysvar cache in place of the one derived from
+    /// `sysvars`, for every instruction processed with this instance's own
+    /// sysvars. Sysvars provided to `process_instruction_with_sysvars` or by
+    /// a fixture are used as usual.
+    ///
+    /// An escape hatch for sysvars `Sysvars` doesn't model. The cache is used
+    /// as-is, so any sysvar missing from it is unavailable to programs. While
+    /// it's set, changes to `sysvars`, such as by `warp_to_slot`, are not
+    /// visible to programs, though `sysvars` still provides the `Rent` used by
+    /// the transaction context and by checks. See `clear_sysvar_cache`.
+    pub fn set_sysvar_cache(&mut self, sysvar_cache: SysvarCache) {
+        self.sysvar_cache = Some(sysvar_cache);
+    }
+
+    /// Stop using the sysvar cache set with `set_sysvar_cache`, deriving one
+    /// from `sysvars` again.
+    pub fn clear_sysvar_cache(&mut self) {
+        self.sysvar_cache = None;
+    }
+
+    /// Set the lamports charged per signature, which the runtime exposes to
+    /// programs, such as the System program when it records the fee
+    /// calculator of a durable nonce account.
+    ///
+    /// Mollusk has no bank, so fees are never actually deducted from any
+    /// account.
+    pub fn set_lamports_per_signature(&mut self, lamports_per_signature: u64) {
+        self.fee_structure.lamports_per_signature = lamports_per_signature;
     }
 
     /// Warp the test environment to a slot by updating sysvars.
@@ -144,99 +621,1132 @@ impl Mollusk {
         self.sysvars.warp_to_slot(slot)
     }
 
+    /// Warp the test environment to the first slot of an epoch by updating
+    /// sysvars.
+    pub fn warp_to_epoch(&mut self, epoch: u64) {
+        self.sysvars.warp_to_epoch(epoch)
+    }
+
+    /// Set the Unix timestamp of the `Clock` sysvar.
+    pub fn set_clock_timestamp(&mut self, unix_timestamp: i64) {
+        self.sysvars.set_unix_timestamp(unix_timestamp)
+    }
+
+    /// Restore every sysvar to its default state, as for a new Mollusk
+    /// instance, undoing any warps or other changes.
+    pub fn reset_sysvars(&mut self) {
+        self.sysvars = Sysvars::default();
+    }
+
+    /// Restore the `Clock` sysvar to its default state, at slot zero with a
+    /// zero timestamp. The other sysvars, such as `SlotHashes`, are left
+    /// unchanged.
+    pub fn reset_clock(&mut self) {
+        self.sysvars.clock = Clock::default();
+    }
+
+    /// Set the `EpochRewards` sysvar, for testing programs that interact with
+    /// partitioned epoch rewards.
+    pub fn set_epoch_rewards(&mut self, epoch_rewards: EpochRewards) {
+        self.sysvars.set_epoch_rewards(epoch_rewards)
+    }
+
+    /// Set the slot of the `LastRestartSlot` sysvar.
+    pub fn set_last_restart_slot(&mut self, slot: u64) {
+        self.sysvars.set_last_restart_slot(slot)
+    }
+
+    /// Add an entry to the `SlotHashes` sysvar.
+    pub fn push_slot_hash(&mut self, slot: u64, hash: Hash) {
+        self.sysvars.push_slot_hash(slot, hash)
+    }
+
+    /// Advance the Unix timestamp of the `Clock` sysvar, simulating time
+    /// passing between instructions.
+    pub fn advance_timestamp(&mut self, seconds: i64) {
+        let unix_timestamp = self.sysvars.clock.unix_timestamp.saturating_add(seconds);
+        self.sysvars.set_unix_timestamp(unix_timestamp)
+    }
+
+    /// Get the key and account for a sysvar, holding its current state, for
+    /// programs that take the sysvar as an instruction account.
+    ///
+    /// See `Sysvars::sysvar_account`. Panics if `sysvar_id` is not a sysvar
+    /// Mollusk maintains.
+    pub fn sysvar_account(&self, sysvar_id: &Pubkey) -> (Pubkey, AccountSharedData) {
+        #[allow(deprecated)]
+        let account = if *sysvar_id == solana_sdk::sysvar::recent_blockhashes::id() {
+            let mut account =
+                create_account_shared_data_with_fields(&self.recent_blockhashes(), (0, 0));
+            account.set_lamports(self.sysvars.rent.minimum_balance(account.data().len()));
+            account
+        } else {
+            self.sysvars
+                .sysvar_account(sysvar_id)
+                .unwrap_or_else(|| panic!("{} is not a sysvar maintained by Mollusk", sysvar_id))
+        };
+        (*sysvar_id, account)
+    }
+
+    // The deprecated `RecentBlockhashes` sysvar, still required by the System
+    // program's durable nonce instructions, holding only the environment's
+    // blockhash.
+    #[allow(deprecated)]
+    fn recent_blockhashes(&self) -> solana_sdk::sysvar::recent_blockhashes::RecentBlockhashes {
+        use solana_sdk::sysvar::recent_blockhashes::{IterItem, RecentBlockhashes};
+        RecentBlockhashes::from_iter([IterItem(
+            0,
+            &self.blockhash,
+            self.fee_structure.lamports_per_signature,
+        )])
+    }
+
+    /// Create a rent-exempt, zero-initialized account owned by `owner`, using
+    /// the `Rent` sysvar configured on this instance.
+    pub fn rent_exempt_account(&self, owner: &Pubkey, space: usize) -> AccountSharedData {
+        let lamports = self.sysvars.rent.minimum_balance(space);
+        AccountSharedData::new(lamports, space, owner)
+    }
+
+    /// Create a rent-exempt account owned by `owner`, holding `data`, using
+    /// the `Rent` sysvar configured on this instance.
+    pub fn rent_exempt_account_with_data(&self, owner: &Pubkey, data: &[u8]) -> AccountSharedData {
+        let mut account = self.rent_exempt_account(owner, 0);
+        account.set_lamports(self.sysvars.rent.minimum_balance(data.len()));
+        account.set_data_from_slice(data);
+        account
+    }
+
     /// The main Mollusk API method.
     ///
     /// Process an instruction using the minified Solana Virtual Machine (SVM)
     /// environment. Simply returns the result.
+    ///
+    /// Accounts are not checked against the instruction's account metas. See
+    /// `try_process_instruction`. Panics if an account's data exceeds the
+    /// runtime's limit of `MAX_PERMITTED_DATA_LENGTH` bytes.
     pub fn process_instruction(
         &self,
         instruction: &Instruction,
         accounts: &[(Pubkey, AccountSharedData)],
     ) -> InstructionResult {
-        let mut compute_units_consumed = 0;
-        let mut timings = ExecuteTimings::default();
+        self.process_instruction_with_config(
+            &instruction.program_id,
+            &instruction.data,
+            &instruction.accounts,
+            accounts,
+            &self.compute_budget,
+            &self.feature_set,
+            &self.sysvars,
+        )
+    }
+
+    /// Process an instruction, as with `process_instruction`, taking ownership
+    /// of the accounts rather than copying them.
+    ///
+    /// An account's data is shared between copies until either is written,
+    /// so with borrowed accounts, a program writing to a large account copies
+    /// its data. Owned accounts avoid that copy, which matters when
+    /// repeatedly processing instructions against megabyte-sized accounts.
+    pub fn process_instruction_owned(
+        &self,
+        instruction: &Instruction,
+        accounts: Vec<(Pubkey, AccountSharedData)>,
+    ) -> InstructionResult {
+        self.execute_instruction(
+            &instruction.program_id,
+            &instruction.data,
+            &instruction.accounts,
+            accounts,
+            &self.compute_budget,
+            &self.feature_set,
+            &self.sysvars,
+            TRANSACTION_LEVEL_STACK_HEIGHT,
+        )
+        .0
+    }
+
+    /// Process an instruction as if invoked via CPI, at the provided height of
+    /// the invocation stack, where a top-level instruction has a height of 1.
+    ///
+    /// Below the top level, the program is pushed onto the stack as its own
+    /// callers, so `sol_get_stack_height` reports `height` without a real
+    /// calling program. Useful for testing logic that branches on CPI depth.
+    /// Panics if `height` is zero or exceeds the compute budget's
+    /// `max_instruction_stack_depth`.
+    pub fn process_instruction_at_stack_height(
+        &self,
+        instruction: &Instruction,
+        accounts: &[(Pubkey, AccountSharedData)],
+        height: usize,
+    ) -> InstructionResult {
+        let max_height = self.compute_budget.max_instruction_stack_depth;
+        if !(TRANSACTION_LEVEL_STACK_HEIGHT..=max_height).contains(&height) {
+            panic!(
+                "Stack height {} is outside the permitted range of {} to {}",
+                height, TRANSACTION_LEVEL_STACK_HEIGHT, max_height
+            );
+        }
+        self.execute_instruction(
+            &instruction.program_id,
+            &instruction.data,
+            &instruction.accounts,
+            accounts.to_vec(),
+            &self.compute_budget,
+            &self.feature_set,
+            &self.sysvars,
+            height,
+        )
+        .0
+    }
+
+    /// Process an instruction, recording the accounts provided, the result,
+    /// and the changes made to each account.
+    ///
+    /// A debugging aid for investigating a single execution. See
+    /// `RecordedExecution::write_json`.
+    pub fn process_instruction_recorded(
+        &self,
+        instruction: &Instruction,
+        accounts: &[(Pubkey, AccountSharedData)],
+    ) -> RecordedExecution {
+        let result = self.process_instruction(instruction, accounts);
+        RecordedExecution::new(accounts.to_vec(), result)
+    }
+
+    /// Process an instruction, first checking that an account was provided
+    /// for every account referenced by the instruction's account metas.
+    ///
+    /// Returns `MolluskError::MissingAccounts`, listing every key without an
+    /// account, unless `allow_missing_accounts` is set, or
+    /// `MolluskError::AccountDataTooLarge` if an account's data exceeds the
+    /// runtime's limit.
+    pub fn try_process_instruction(
+        &self,
+        instruction: &Instruction,
+        accounts: &[(Pubkey, AccountSharedData)],
+    ) -> Result<InstructionResult, MolluskError> {
+        let missing = self.missing_accounts(instruction, accounts);
+        if !missing.is_empty() {
+            return Err(MolluskError::MissingAccounts(missing));
+        }
+        check_account_data_len(accounts)?;
+        Ok(self.process_instruction(instruction, accounts))
+    }
 
-        let instruction_accounts = instruction
+    // The keys referenced by an instruction without a provided account, each
+    // listed once. Always empty if `allow_missing_accounts` is set.
+    fn missing_accounts(
+        &self,
+        instruction: &Instruction,
+        accounts: &[(Pubkey, AccountSharedData)],
+    ) -> Vec<Pubkey> {
+        let mut missing = Vec::new();
+        if self.allow_missing_accounts {
+            return missing;
+        }
+        for meta in instruction.accounts.iter() {
+            if !accounts.iter().any(|(pubkey, _)| *pubkey == meta.pubkey)
+                && !missing.contains(&meta.pubkey)
+            {
+                missing.push(meta.pubkey);
+            }
+        }
+        missing
+    }
+
+    /// Process an instruction, resolving each of its accounts by pubkey from
+    /// a map, rather than from a slice that must follow the order of the
+    /// instruction's account metas.
+    ///
+    /// Accounts missing from the map are provided as default (empty)
+    /// accounts, and a warning is logged for each.
+    pub fn process_instruction_with_account_map(
+        &self,
+        instruction: &Instruction,
+        accounts: &HashMap<Pubkey, AccountSharedData>,
+    ) -> InstructionResult {
+        let accounts = instruction
             .accounts
             .iter()
-            .enumerate()
-            .map(|(i, meta)| InstructionAccount {
-                index_in_callee: i as u16,
-                index_in_caller: i as u16,
-                index_in_transaction: (i + PROGRAM_ACCOUNTS_LEN) as u16,
-                is_signer: meta.is_signer,
-                is_writable: meta.is_writable,
+            .map(|meta| {
+                let account = accounts.get(&meta.pubkey).cloned().unwrap_or_else(|| {
+                    log::warn!(
+                        "Account {} was not provided, using a default account",
+                        meta.pubkey
+                    );
+                    AccountSharedData::default()
+                });
+                (meta.pubkey, account)
             })
             .collect::<Vec<_>>();
+        self.process_instruction(instruction, &accounts)
+    }
+
+    /// Process a chain of instructions in order, as if in a single
+    /// transaction.
+    ///
+    /// Each instruction sees the accounts as left by the previous ones.
+    /// Accounts referenced by an instruction but not provided are treated as
+    /// zeroed (default) accounts. Execution stops at the first failed
+    /// instruction.
+    ///
+    /// If an instruction references the `Instructions` sysvar, its account is
+    /// populated with the full chain of instructions and the index of the
+    /// executing instruction, so programs can introspect their siblings.
+    ///
+    /// Instructions to the compute budget program are not executed. Instead,
+    /// a requested compute unit limit or heap frame size applies to the rest
+    /// of the chain, and a compute unit price determines the
+    /// `prioritization_fee` reported. Other compute budget instructions have
+    /// no effect. As on-chain, the limit is capped at 1.4M units, and a chain
+    /// with an invalid compute budget instruction, such as a heap frame size
+    /// that isn't a multiple of 1KB between 32KB and 256KB, fails with
+    /// `InvalidInstructionData` before any instruction executes.
+    ///
+    /// Unlike on-chain, where the limit is transaction-wide and every
+    /// instruction draws from a single shared meter, each instruction is
+    /// metered separately against the limit.
+    ///
+    /// The returned result aggregates every instruction executed: compute
+    /// units, timings, and logs are summed or concatenated, the program
+    /// result and return data are those of the last instruction executed, and
+    /// the resulting accounts hold the final state of every account, in the
+    /// order provided.
+    ///
+    /// As with `process_instruction`, panics if an account's data exceeds the
+    /// runtime's limit of `MAX_PERMITTED_DATA_LENGTH` bytes.
+    pub fn process_instruction_chain(
+        &self,
+        instructions: &[Instruction],
+        accounts: &[(Pubkey, AccountSharedData)],
+    ) -> InstructionResult {
+        self.process_instruction_chain_with(instructions, accounts, |_, _, _| {})
+    }
+
+    // Process a chain of instructions, calling `inspect` with the index, input
+    // accounts, and result of each instruction executed.
+    fn process_instruction_chain_with(
+        &self,
+        instructions: &[Instruction],
+        accounts: &[(Pubkey, AccountSharedData)],
+        mut inspect: impl FnMut(usize, &[(Pubkey, AccountSharedData)], &InstructionResult),
+    ) -> InstructionResult {
+        let mut compute_budget = self.compute_budget;
+        let mut compute_unit_price = 0;
+        let mut result = InstructionResult {
+            resulting_accounts: accounts.to_vec(),
+            ..Default::default()
+        };
+        // As on-chain, a chain with an invalid compute budget instruction is
+        // rejected before any instruction executes.
+        if let Some(Err(err)) = instructions
+            .iter()
+            .filter(|instruction| instruction.program_id == compute_budget::id())
+            .map(|instruction| parse_compute_budget_instruction(&instruction.data))
+            .find(Result::is_err)
+        {
+            result.raw_result = Err(err);
+            result.program_result = result.raw_result.clone().into();
+            return result;
+        }
+        for (index, instruction) in instructions.iter().enumerate() {
+            if instruction.program_id == compute_budget::id() {
+                match parse_compute_budget_instruction(&instruction.data) {
+                    Ok(ComputeBudgetInstruction::SetComputeUnitLimit(units)) => {
+                        compute_budget.compute_unit_limit =
+                            u64::from(units.min(MAX_COMPUTE_UNIT_LIMIT));
+                    }
+                    Ok(ComputeBudgetInstruction::RequestHeapFrame(bytes)) => {
+                        compute_budget.heap_size = bytes;
+                    }
+                    Ok(ComputeBudgetInstruction::SetComputeUnitPrice(micro_lamports)) => {
+                        compute_unit_price = micro_lamports;
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            let instruction_accounts = instruction
+                .accounts
+                .iter()
+                .map(|meta| {
+                    let account = if meta.pubkey == solana_sdk::sysvar::instructions::id() {
+                        sysvar::instructions_sysvar_account(instructions, index as u16)
+                    } else {
+                        result
+                            .get_account(&meta.pubkey)
+                            .cloned()
+                            .unwrap_or_default()
+                    };
+                    (meta.pubkey, account)
+                })
+                .collect::<Vec<_>>();
+            let instruction_result = self.process_instruction_with_config(
+                &instruction.program_id,
+                &instruction.data,
+                &instruction.accounts,
+                &instruction_accounts,
+                &compute_budget,
+                &self.feature_set,
+                &self.sysvars,
+            );
+            inspect(index, &instruction_accounts, &instruction_result);
+            result.merge(instruction_result);
+            if result.program_result != ProgramResult::Success {
+                break;
+            }
+        }
+        result.prioritization_fee =
+            prioritization_fee(compute_unit_price, result.compute_units_consumed);
+        result
+    }
+
+    /// Process a chain of instructions, as with `process_instruction_chain`,
+    /// then perform checks on the result of each instruction, and on the
+    /// aggregated result. Panics if any checks fail.
+    ///
+    /// `checks` holds a slice of checks for each instruction, run against its
+    /// own result, optionally followed by one more slice run against the
+    /// aggregated result. Checks for instructions that weren't executed, such
+    /// as compute budget instructions or those after a failure, are not run.
+    pub fn process_and_validate_instruction_chain(
+        &self,
+        instructions: &[Instruction],
+        accounts: &[(Pubkey, AccountSharedData)],
+        checks: &[&[Check]],
+    ) -> InstructionResult {
+        let (result, failures) = self.run_instruction_chain_checks(instructions, accounts, checks);
+        if !failures.is_empty() {
+            result::panic_with_check_failures(&failures, &result);
+        }
+        result
+    }
+
+    /// Process a chain of instructions, then perform checks on the result of
+    /// each instruction, and on the aggregated result, as with
+    /// `process_and_validate_instruction_chain`.
+    ///
+    /// Every check is run, and all failures are returned. The description of
+    /// a failed check on an instruction's result is prefixed with the
+    /// instruction's index.
+    pub fn try_process_and_validate_instruction_chain(
+        &self,
+        instructions: &[Instruction],
+        accounts: &[(Pubkey, AccountSharedData)],
+        checks: &[&[Check]],
+    ) -> Result<InstructionResult, Vec<CheckFailure>> {
+        let (result, failures) = self.run_instruction_chain_checks(instructions, accounts, checks);
+        if failures.is_empty() {
+            Ok(result)
+        } else {
+            Err(failures)
+        }
+    }
+
+    fn run_instruction_chain_checks(
+        &self,
+        instructions: &[Instruction],
+        accounts: &[(Pubkey, AccountSharedData)],
+        checks: &[&[Check]],
+    ) -> (InstructionResult, Vec<CheckFailure>) {
+        assert!(
+            checks.len() == instructions.len() || checks.len() == instructions.len() + 1,
+            "Expected {} or {} slices of checks, got {}",
+            instructions.len(),
+            instructions.len() + 1,
+            checks.len(),
+        );
+        let mut failures = Vec::new();
+        let result = self.process_instruction_chain_with(
+            instructions,
+            accounts,
+            |index, instruction_accounts, instruction_result| {
+                if let Err(instruction_failures) = instruction_result.run_checks(
+                    checks[index],
+                    instruction_accounts,
+                    &self.sysvars.rent,
+                ) {
+                    failures.extend(instruction_failures.into_iter().map(|mut failure| {
+                        failure.check = format!("instruction {} {}", index, failure.check);
+                        failure
+                    }));
+                }
+            },
+        );
+        if let Some(final_checks) = checks.get(instructions.len()) {
+            if let Err(final_failures) =
+                result.run_checks(final_checks, accounts, &self.sysvars.rent)
+            {
+                failures.extend(final_failures);
+            }
+        }
+        (result, failures)
+    }
+
+    /// Process a transaction's instructions in order, as with
+    /// `process_instruction_chain`.
+    ///
+    /// Each instruction's account metas are derived from the message, with
+    /// signer and writable privileges taken from the message header. As in
+    /// the runtime, invoked programs and reserved accounts, such as sysvars,
+    /// are demoted to read-only.
+    /// Addresses loaded from lookup tables are resolved using the lookup table
+    /// accounts, which must be provided in `accounts`. Signatures are not
+    /// verified.
+    ///
+    /// Unlike a chain, a transaction is atomic: if any instruction fails, the
+    /// resulting accounts are the accounts provided, unchanged. No fee is
+    /// charged.
+    ///
+    /// Panics if the message fails sanitization, such as for a malformed
+    /// header or an account index out of bounds, if a lookup table account is
+    /// missing or invalid, or if an account's data exceeds the runtime's
+    /// limit. See `try_process_transaction` for a non-panicking version.
+    pub fn process_transaction(
+        &self,
+        transaction: &VersionedTransaction,
+        accounts: &[(Pubkey, AccountSharedData)],
+    ) -> InstructionResult {
+        self.try_process_transaction(transaction, accounts)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Process a transaction's instructions in order, as with
+    /// `process_transaction`.
+    ///
+    /// Returns `MolluskError::InvalidTransaction` if the message fails
+    /// sanitization or a lookup table account is missing or invalid, or
+    /// `MolluskError::AccountDataTooLarge` if an account's data exceeds the
+    /// runtime's limit.
+    pub fn try_process_transaction(
+        &self,
+        transaction: &VersionedTransaction,
+        accounts: &[(Pubkey, AccountSharedData)],
+    ) -> Result<InstructionResult, MolluskError> {
+        let instructions =
+            transaction_instructions(&transaction.message, accounts, &self.feature_set)?;
+        check_account_data_len(accounts)?;
+        let mut result = self.process_instruction_chain(&instructions, accounts);
+        // As on-chain, a failed transaction changes no accounts.
+        if result.program_result != ProgramResult::Success {
+            result.resulting_accounts = accounts.to_vec();
+        }
+        Ok(result)
+    }
+
+    /// Process an instruction using the provided sysvars in place of the
+    /// harness's own, for this call only.
+    ///
+    /// Useful for parameterizing a test over many `Clock` or `Rent` values
+    /// without mutating `self.sysvars`.
+    pub fn process_instruction_with_sysvars(
+        &self,
+        instruction: &Instruction,
+        accounts: &[(Pubkey, AccountSharedData)],
+        sysvars: &Sysvars,
+    ) -> InstructionResult {
+        self.process_instruction_with_config(
+            &instruction.program_id,
+            &instruction.data,
+            &instruction.accounts,
+            accounts,
+            &self.compute_budget,
+            &self.feature_set,
+            sysvars,
+        )
+    }
+
+    /// Process an instruction recorded in a fixture.
+    ///
+    /// The fixture's compute unit limit, feature set, and sysvars are used in
+    /// place of this instance's configuration. Programs are still loaded from
+    /// this instance's program cache, so the program under test must already
+    /// be added. As with `process_instruction`, panics if an account's data
+    /// exceeds the runtime's limit.
+    pub fn process_fixture(&self, fixture: &Fixture) -> InstructionResult {
+        let compute_budget = ComputeBudget {
+            compute_unit_limit: fixture.compute_unit_limit,
+            ..self.compute_budget
+        };
+        let mut feature_set = FeatureSet::default();
+        for feature_id in fixture.feature_set.iter() {
+            feature_set.activate(feature_id, 0);
+        }
+        self.process_instruction_with_config(
+            &fixture.instruction.program_id,
+            &fixture.instruction.data,
+            &fixture.instruction.accounts,
+            &fixture.accounts,
+            &compute_budget,
+            &feature_set,
+            &fixture.sysvars,
+        )
+    }
+
+    /// Process an instruction from its raw parts, without constructing an
+    /// `Instruction`. Intended for fuzzers, which can drive arbitrary
+    /// instruction data directly.
+    ///
+    /// Malformed instruction data never causes a panic. Any error is returned
+    /// in the result's `program_result`, just like `process_instruction`.
+    ///
+    /// The harness itself may still panic in three cases, none of which
+    /// depend on the instruction data:
+    ///
+    /// * An account's data exceeds the runtime's limit of
+    ///   `MAX_PERMITTED_DATA_LENGTH` bytes. Fuzzers generating accounts should
+    ///   stay within it, or use `try_process_instruction` to have it reported
+    ///   as a `MolluskError::AccountDataTooLarge`.
+    /// * The program cache's lock is poisoned, ie. a previous call panicked
+    ///   while holding it.
+    /// * The transaction context can't be deconstructed after execution, which
+    ///   only happens if an account is still borrowed.
+    pub fn process_instruction_raw(
+        &self,
+        program_id: &Pubkey,
+        data: &[u8],
+        account_metas: &[AccountMeta],
+        accounts: &[(Pubkey, AccountSharedData)],
+    ) -> InstructionResult {
+        self.process_instruction_with_config(
+            program_id,
+            data,
+            account_metas,
+            accounts,
+            &self.compute_budget,
+            &self.feature_set,
+            &self.sysvars,
+        )
+    }
+
+    /// Process an instruction, as with `process_instruction`, also returning
+    /// the transaction context it was executed in.
+    ///
+    /// This is an escape hatch for inspecting details of the execution not
+    /// surfaced by `InstructionResult`, such as the raw instruction trace or
+    /// the rent state of accounts. The first account in the context is the
+    /// program account, followed by the provided accounts, in order. Only the
+    /// first of any accounts referenced more than once is used.
+    pub fn process_instruction_raw_context(
+        &self,
+        instruction: &Instruction,
+        accounts: &[(Pubkey, AccountSharedData)],
+    ) -> (InstructionResult, TransactionContext) {
+        self.execute_instruction(
+            &instruction.program_id,
+            &instruction.data,
+            &instruction.accounts,
+            accounts.to_vec(),
+            &self.compute_budget,
+            &self.feature_set,
+            &self.sysvars,
+            TRANSACTION_LEVEL_STACK_HEIGHT,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn process_instruction_with_config(
+        &self,
+        program_id: &Pubkey,
+        data: &[u8],
+        account_metas: &[AccountMeta],
+        accounts: &[(Pubkey, AccountSharedData)],
+        compute_budget: &ComputeBudget,
+        feature_set: &FeatureSet,
+        sysvars: &Sysvars,
+    ) -> InstructionResult {
+        self.execute_instruction(
+            program_id,
+            data,
+            account_metas,
+            accounts.to_vec(),
+            compute_budget,
+            feature_set,
+            sysvars,
+            TRANSACTION_LEVEL_STACK_HEIGHT,
+        )
+        .0
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn execute_instruction(
+        &self,
+        program_id: &Pubkey,
+        data: &[u8],
+        account_metas: &[AccountMeta],
+        accounts: Vec<(Pubkey, AccountSharedData)>,
+        compute_budget: &ComputeBudget,
+        feature_set: &FeatureSet,
+        sysvars: &Sysvars,
+        stack_height: usize,
+    ) -> (InstructionResult, TransactionContext) {
+        // The runtime never holds an account larger than the limit, so a
+        // program is not expected to handle one.
+        check_account_data_len(&accounts).unwrap_or_else(|err| panic!("{}", err));
 
-        let transaction_accounts = [(self.program_id, self.program_account.clone())]
+        let mut compute_units_consumed = 0;
+        let mut timings = ExecuteTimings::default();
+        // Logs are collected in full, since the compute units, timings, and
+        // failures derived from them must not be lost to truncation. The logs
+        // in the result are truncated as the runtime would.
+        let log_collector = LogCollector::new_ref_with_limit(None);
+
+        // As in the runtime, an account referenced more than once is a single
+        // account, at the position it first appears. The accounts provided for
+        // any later references are unused.
+        let first_index = |pubkey: &Pubkey| {
+            account_metas
+                .iter()
+                .position(|meta| meta.pubkey == *pubkey)
+                .unwrap()
+        };
+        // Likewise, the signer and writable flags belong to the account, not
+        // the reference, and are set if set on any reference to it.
+        let is_signer = |pubkey: &Pubkey| {
+            account_metas
+                .iter()
+                .any(|meta| meta.pubkey == *pubkey && meta.is_signer)
+        };
+        let is_writable = |pubkey: &Pubkey| {
+            account_metas
+                .iter()
+                .any(|meta| meta.pubkey == *pubkey && meta.is_writable)
+        };
+        let instruction_accounts = account_metas
             .iter()
-            .chain(accounts)
-            .cloned()
+            .map(|meta| {
+                let index = first_index(&meta.pubkey);
+                InstructionAccount {
+                    index_in_callee: index as u16,
+                    index_in_caller: index as u16,
+                    index_in_transaction: (index + PROGRAM_ACCOUNTS_LEN) as u16,
+                    is_signer: is_signer(&meta.pubkey),
+                    is_writable: is_writable(&meta.pubkey),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // The program under test uses `self.program_account`, which may have
+        // been customized. Any other program is looked up in the cache, and
+        // an unknown program fails with `UnsupportedProgramId`.
+        let program_account = if *program_id == self.program_id {
+            self.program_account.clone()
+        } else {
+            self.program_cache
+                .program_account(program_id)
+                .unwrap_or_else(|| AccountSharedData::new(0, 0, &native_loader::id()))
+        };
+
+        // An empty `Instructions` sysvar account is populated with this
+        // instruction alone. Chains populate it with every instruction.
+        let transaction_accounts = [(*program_id, program_account)]
+            .into_iter()
+            .chain(accounts.into_iter().map(|(pubkey, account)| {
+                if pubkey == solana_sdk::sysvar::instructions::id() && account.data().is_empty() {
+                    let instruction = Instruction {
+                        program_id: *program_id,
+                        accounts: account_metas.to_vec(),
+                        data: data.to_vec(),
+                    };
+                    (
+                        pubkey,
+                        sysvar::instructions_sysvar_account(&[instruction], 0),
+                    )
+                } else {
+                    (pubkey, account)
+                }
+            }))
             .collect::<Vec<_>>();
 
         let mut transaction_context = TransactionContext::new(
             transaction_accounts,
-            Rent::default(),
-            self.compute_budget.max_instruction_stack_depth,
-            self.compute_budget.max_instruction_trace_length,
+            sysvars.rent.clone(),
+            compute_budget.max_instruction_stack_depth,
+            compute_budget.max_instruction_trace_length,
         );
 
+        // A pre-built cache stands in for this instance's own sysvars only,
+        // not those provided for a single call or by a fixture.
+        let sysvar_cache = match &self.sysvar_cache {
+            Some(sysvar_cache) if std::ptr::eq(sysvars, &self.sysvars) => sysvar_cache.clone(),
+            _ => {
+                let mut sysvar_cache = SysvarCache::from(sysvars);
+                #[allow(deprecated)]
+                sysvar_cache.fill_missing_entries(|pubkey, set_sysvar| {
+                    if *pubkey == solana_sdk::sysvar::recent_blockhashes::id() {
+                        set_sysvar(&bincode::serialize(&self.recent_blockhashes()).unwrap());
+                    }
+                });
+                sysvar_cache
+            }
+        };
         let invoke_result = {
-            let mut cache = self.program_cache.cache().write().unwrap();
-            InvokeContext::new(
+            // Each instruction executes against its own copy of the cache,
+            // sharing the compiled program entries, so that executions are
+            // isolated from one another and can run concurrently.
+            let mut cache = self.program_cache.cache().read().unwrap().clone();
+            let mut invoke_context = InvokeContext::new(
                 &mut transaction_context,
                 &mut cache,
                 EnvironmentConfig::new(
-                    Hash::default(),
+                    self.blockhash,
                     None,
                     None,
-                    Arc::new(self.feature_set.clone()),
+                    Arc::new(feature_set.clone()),
                     self.fee_structure.lamports_per_signature,
-                    &SysvarCache::from(&self.sysvars),
+                    &sysvar_cache,
                 ),
-                None,
-                self.compute_budget,
-            )
-            .process_instruction(
-                &instruction.data,
-                &instruction_accounts,
-                PROGRAM_INDICES,
-                &mut compute_units_consumed,
-                &mut timings,
-            )
+                Some(log_collector.clone()),
+                *compute_budget,
+            );
+            // Below the top level, the program is first pushed onto the
+            // stack as its own callers, with the same accounts and no data.
+            let mut frames = 0;
+            let mut invoke_result = (TRANSACTION_LEVEL_STACK_HEIGHT..stack_height)
+                .try_for_each(|_| -> Result<(), InstructionError> {
+                    invoke_context
+                        .transaction_context
+                        .get_next_instruction_context()?
+                        .configure(PROGRAM_INDICES, &instruction_accounts, &[]);
+                    invoke_context.push()?;
+                    frames += 1;
+                    Ok(())
+                })
+                .and_then(|()| {
+                    invoke_context.process_instruction(
+                        data,
+                        &instruction_accounts,
+                        PROGRAM_INDICES,
+                        &mut compute_units_consumed,
+                        &mut timings,
+                    )
+                });
+            for _ in 0..frames {
+                invoke_result = invoke_result.and(invoke_context.pop());
+            }
+            // The BPF Loader records its timings on the invoke context.
+            timings.details.accumulate(&invoke_context.timings);
+            invoke_result
         };
 
-        let resulting_accounts = transaction_context
-            .deconstruct_without_keys()
-            .unwrap()
-            .into_iter()
-            .skip(PROGRAM_ACCOUNTS_LEN)
-            .zip(instruction.accounts.iter())
-            .map(|(account, meta)| (meta.pubkey, account))
+        let all_logs = log_collector.take().into_messages();
+        let compute_units_per_program =
+            result::compute_units_per_program(&all_logs, |program_id| {
+                self.program_cache.builtin_compute_units(program_id)
+            });
+        for (program_id, units) in compute_units_per_program.iter() {
+            let program_timing = timings
+                .details
+                .per_program_timings
+                .entry(*program_id)
+                .or_default();
+            program_timing.accumulated_units += units;
+            program_timing.count += 1;
+        }
+        let allocation_failed = all_logs
+            .iter()
+            .any(|log| log.contains("memory allocation failed"));
+        // Builtins report an exhausted budget directly, but the BPF Loader
+        // reports any VM error as a failure to complete, so the log emitted
+        // for the VM's error distinguishes it.
+        let compute_budget_exceeded = match &invoke_result {
+            Err(InstructionError::ComputationalBudgetExceeded) => true,
+            Err(InstructionError::ProgramFailedToComplete) => all_logs
+                .iter()
+                .any(|log| log.contains("exceeded CUs meter")),
+            _ => false,
+        };
+        if compute_budget_exceeded {
+            log::warn!(
+                "Instruction exceeded its compute budget of {} units",
+                compute_budget.compute_unit_limit
+            );
+        }
+        let max_stack_depth = (0..transaction_context.get_instruction_trace_length())
+            .filter_map(|index| {
+                transaction_context
+                    .get_instruction_context_at_index_in_trace(index)
+                    .ok()
+            })
+            .map(|instruction_context| instruction_context.get_stack_height())
+            .max()
+            .unwrap_or(0);
+        let mut inner_instructions = result::inner_instructions(&transaction_context);
+        // The frames pushed to reach the requested stack height, and the
+        // instruction itself, are not inner instructions.
+        let frames = stack_height - TRANSACTION_LEVEL_STACK_HEIGHT;
+        inner_instructions.drain(..frames.min(inner_instructions.len()));
+        let return_data = transaction_context.get_return_data().1.to_vec();
+
+        let resulting_accounts = account_metas
+            .iter()
+            .enumerate()
+            .filter(|(index, meta)| first_index(&meta.pubkey) == *index)
+            .filter_map(|(index, meta)| {
+                let account = transaction_context
+                    .get_account_at_index((index + PROGRAM_ACCOUNTS_LEN) as u16)
+                    .ok()?
+                    .borrow()
+                    .clone();
+                Some((meta.pubkey, account))
+            })
             .collect::<Vec<_>>();
 
-        InstructionResult {
+        let logs = {
+            let mut log_collector = LogCollector::default();
+            all_logs.iter().for_each(|log| log_collector.log(log));
+            log_collector.into_messages()
+        };
+
+        let result = InstructionResult {
             compute_units_consumed,
             execution_time: timings.details.execute_us,
-            program_result: invoke_result.into(),
+            timings: timings.details,
+            logs,
+            compute_units_per_program,
+            heap_size: compute_budget.heap_size,
+            allocation_failed,
+            compute_budget_exceeded,
+            max_stack_depth,
+            inner_instructions,
+            program_result: invoke_result.clone().into(),
+            raw_result: invoke_result,
+            return_data,
+            prioritization_fee: 0,
             resulting_accounts,
-        }
+        };
+        (result, transaction_context)
+    }
+
+    /// Process a batch of independent instructions in parallel, each with its
+    /// own set of accounts. Requires the `parallel` feature.
+    ///
+    /// Every case is executed in isolation, exactly as `process_instruction`
+    /// would execute it, against the same configuration and program cache.
+    /// Results are returned in the order of the cases.
+    #[cfg(feature = "parallel")]
+    pub fn process_batch(
+        &self,
+        cases: &[(Instruction, Vec<(Pubkey, AccountSharedData)>)],
+    ) -> Vec<InstructionResult> {
+        use rayon::prelude::*;
+
+        cases
+            .par_iter()
+            .map(|(instruction, accounts)| self.process_instruction(instruction, accounts))
+            .collect()
+    }
+
+    /// Process an instruction repeatedly, summarizing the compute units
+    /// consumed and the wall-clock time taken.
+    ///
+    /// The program cache is reused across iterations, so programs are only
+    /// compiled once. Panics if `iterations` is zero.
+    pub fn bench_instruction(
+        &self,
+        instruction: &Instruction,
+        accounts: &[(Pubkey, AccountSharedData)],
+        iterations: usize,
+    ) -> BenchResult {
+        let runs = (0..iterations)
+            .map(|_| {
+                let start = Instant::now();
+                let result = self.process_instruction(instruction, accounts);
+                (result.compute_units_consumed, start.elapsed())
+            })
+            .collect::<Vec<_>>();
+        BenchResult::new(&runs)
     }
 
     /// The secondary Mollusk API method.
     ///
     /// Process an instruction using the minified Solana Virtual Machine (SVM)
     /// environment, then perform checks on the result. Panics if any checks
-    /// fail.
+    /// fail, or if any account referenced by the instruction was not provided
+    /// (see `allow_missing_accounts`).
     pub fn process_and_validate_instruction(
         &self,
         instruction: &Instruction,
         accounts: &[(Pubkey, AccountSharedData)],
         checks: &[Check],
     ) -> InstructionResult {
-        let result = self.process_instruction(instruction, accounts);
-        result.run_checks(checks);
+        let result = self
+            .try_process_instruction(instruction, accounts)
+            .unwrap_or_else(|err| panic!("{}", err));
+        if let Err(failures) = result.run_checks(checks, accounts, &self.sysvars.rent) {
+            result::panic_with_check_failures(&failures, &result);
+        }
         result
     }
+
+    /// Process an instruction using the minified Solana Virtual Machine (SVM)
+    /// environment, then perform checks on the result.
+    ///
+    /// Unlike `process_and_validate_instruction`, this method does not panic.
+    /// Every check is run, and all failures are returned. If any account
+    /// referenced by the instruction was not provided, the instruction is not
+    /// processed, and that is the only failure returned.
+    pub fn try_process_and_validate_instruction(
+        &self,
+        instruction: &Instruction,
+        accounts: &[(Pubkey, AccountSharedData)],
+        checks: &[Check],
+    ) -> Result<InstructionResult, Vec<CheckFailure>> {
+        let missing = self.missing_accounts(instruction, accounts);
+        if !missing.is_empty() {
+            let missing = missing.iter().map(Pubkey::to_string).collect::<Vec<_>>();
+            return Err(vec![CheckFailure {
+                check: "instruction accounts provided".to_string(),
+                expected: "an account for every key".to_string(),
+                actual: format!("missing {}", missing.join(", ")),
+            }]);
+        }
+        let result = self.process_instruction(instruction, accounts);
+        result.run_checks(checks, accounts, &self.sysvars.rent)?;
+        Ok(result)
+    }
+}
+
+// Errors for the first account with more data than the runtime permits.
+fn check_account_data_len(accounts: &[(Pubkey, AccountSharedData)]) -> Result<(), MolluskError> {
+    match accounts
+        .iter()
+        .find(|(_, account)| account.data().len() as u64 > MAX_PERMITTED_DATA_LENGTH)
+    {
+        Some((pubkey, account)) => Err(MolluskError::AccountDataTooLarge {
+            pubkey: *pubkey,
+            len: account.data().len(),
+        }),
+        None => Ok(()),
+    }
+}
+
+// Deserialize a compute budget instruction, erroring as the runtime does for
+// invalid data or a heap frame size it doesn't permit.
+fn parse_compute_budget_instruction(
+    data: &[u8],
+) -> Result<ComputeBudgetInstruction, InstructionError> {
+    let instruction = ComputeBudgetInstruction::try_from_slice(data)
+        .map_err(|_| InstructionError::InvalidInstructionData)?;
+    if let ComputeBudgetInstruction::RequestHeapFrame(bytes) = instruction {
+        if !(MIN_HEAP_FRAME_BYTES..=MAX_HEAP_FRAME_BYTES).contains(&bytes) || bytes % 1024 != 0 {
+            return Err(InstructionError::InvalidInstructionData);
+        }
+    }
+    Ok(instruction)
+}
+
+// The prioritization fee, in lamports, for a compute unit price in
+// micro-lamports, rounded up as the runtime does.
+fn prioritization_fee(compute_unit_price: u64, compute_units: u64) -> u64 {
+    const MICRO_LAMPORTS_PER_LAMPORT: u128 = 1_000_000;
+    let micro_lamports = u128::from(compute_unit_price) * u128::from(compute_units);
+    micro_lamports
+        .div_ceil(MICRO_LAMPORTS_PER_LAMPORT)
+        .try_into()
+        .unwrap_or(u64::MAX)
+}
+
+// Decompile a message into its instructions, resolving any addresses loaded
+// from lookup tables using the provided accounts.
+//
+// The message is sanitized first, and each account's privileges are taken
+// from the sanitized message, so that invoked programs and reserved account
+// keys are demoted to read-only, as they are by the runtime.
+fn transaction_instructions(
+    message: &VersionedMessage,
+    accounts: &[(Pubkey, AccountSharedData)],
+    feature_set: &FeatureSet,
+) -> Result<Vec<Instruction>, MolluskError> {
+    let SanitizedVersionedMessage { message } =
+        SanitizedVersionedMessage::try_from(message.clone())
+            .map_err(|err| MolluskError::InvalidTransaction(err.to_string()))?;
+
+    let mut reserved_account_keys = ReservedAccountKeys::default();
+    reserved_account_keys.update_active_set(feature_set);
+    let message = match message {
+        VersionedMessage::Legacy(message) => {
+            SanitizedMessage::Legacy(LegacyMessage::new(message, &reserved_account_keys.active))
+        }
+        VersionedMessage::V0(message) => {
+            let loaded_addresses = load_addresses(&message.address_table_lookups, accounts)?;
+            SanitizedMessage::V0(v0::LoadedMessage::new(
+                message,
+                loaded_addresses,
+                &reserved_account_keys.active,
+            ))
+        }
+    };
+
+    let account_keys = message.account_keys();
+    message
+        .program_instructions_iter()
+        .map(|(program_id, compiled)| {
+            let accounts = compiled
+                .accounts
+                .iter()
+                .map(|index| {
+                    let index = *index as usize;
+                    account_keys.get(index).map(|pubkey| AccountMeta {
+                        pubkey: *pubkey,
+                        is_signer: message.is_signer(index),
+                        is_writable: message.is_writable(index),
+                    })
+                })
+                .collect::<Option<Vec<_>>>()?;
+            Some(Instruction {
+                program_id: *program_id,
+                accounts,
+                data: compiled.data.clone(),
+            })
+        })
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| MolluskError::InvalidTransaction("Account index out of bounds".to_string()))
+}
+
+// Resolve the addresses a message loads from lookup tables, using the lookup
+// table accounts provided.
+fn load_addresses(
+    lookups: &[MessageAddressTableLookup],
+    accounts: &[(Pubkey, AccountSharedData)],
+) -> Result<LoadedAddresses, MolluskError> {
+    let mut loaded_addresses = LoadedAddresses::default();
+    for lookup in lookups {
+        let table_account = accounts
+            .iter()
+            .find(|(key, _)| *key == lookup.account_key)
+            .map(|(_, account)| account)
+            .ok_or_else(|| {
+                MolluskError::InvalidTransaction(format!(
+                    "Lookup table {} not provided",
+                    lookup.account_key
+                ))
+            })?;
+        let table = AddressLookupTable::deserialize(table_account.data()).map_err(|err| {
+            MolluskError::InvalidTransaction(format!(
+                "Invalid lookup table {}: {}",
+                lookup.account_key, err
+            ))
+        })?;
+        let resolve = |index: &u8| {
+            table
+                .addresses
+                .get(*index as usize)
+                .copied()
+                .ok_or_else(|| {
+                    MolluskError::InvalidTransaction(format!(
+                        "Lookup table index {} out of bounds",
+                        index
+                    ))
+                })
+        };
+        for index in lookup.writable_indexes.iter() {
+            loaded_addresses.writable.push(resolve(index)?);
+        }
+        for index in lookup.readonly_indexes.iter() {
+            loaded_addresses.readonly.push(resolve(index)?);
+        }
+    }
+    Ok(loaded_addresses)
 }
@@ -0,0 +1,41 @@
+//! Macros for writing Mollusk tests concisely. Requires the `macros` feature.
+
+/// Process and validate a table of instructions against a Mollusk instance.
+///
+/// Each case is a tuple of a name, an instruction, the accounts to provide,
+/// and the checks to perform, as passed to
+/// `Mollusk::try_process_and_validate_instruction`. Cases are independent:
+/// each is processed against its own accounts. Every case is run, then the
+/// macro panics if any failed, listing each failed check under the name of
+/// its case.
+///
+/// ```rust,ignore
+/// mollusk_test!(mollusk, [
+///     ("transfer", transfer_ix, [sender, recipient], [Check::success()]),
+///     ("overdraw", overdraw_ix, [sender, recipient], [Check::custom_err(1)]),
+/// ]);
+/// ```
+#[macro_export]
+macro_rules! mollusk_test {
+    (
+        $mollusk:expr,
+        [$(($name:expr, $instruction:expr, $accounts:expr, $checks:expr $(,)?)),* $(,)?] $(,)?
+    ) => {{
+        let mollusk: &$crate::Mollusk = &$mollusk;
+        let mut failed_cases: ::std::vec::Vec<::std::string::String> = ::std::vec::Vec::new();
+        $(
+            if let ::std::result::Result::Err(failures) = mollusk
+                .try_process_and_validate_instruction(&$instruction, &$accounts, &$checks)
+            {
+                let mut message = ::std::format!("Case {:?} failed:", $name);
+                for failure in failures.iter() {
+                    message.push_str(&::std::format!("\n  {}", failure));
+                }
+                failed_cases.push(message);
+            }
+        )*
+        if !failed_cases.is_empty() {
+            ::std::panic!("{}", failed_cases.join("\n"));
+        }
+    }};
+}
@@ -1,39 +1,129 @@
 //! Module for working with Solana programs.
 
 use {
+    crate::error::MolluskError,
     solana_bpf_loader_program::syscalls::create_program_runtime_environment_v1,
     solana_compute_budget::compute_budget::ComputeBudget,
     solana_program_runtime::{
-        invoke_context::BuiltinFunctionWithContext,
-        loaded_programs::{LoadProgramMetrics, ProgramCacheEntry, ProgramCacheForTxBatch},
+        invoke_context::{BuiltinFunctionWithContext, InvokeContext},
+        loaded_programs::{
+            LoadProgramMetrics, ProgramCacheEntry, ProgramCacheForTxBatch,
+            ProgramRuntimeEnvironment,
+        },
+        solana_rbpf::program::{BuiltinFunction, BuiltinProgram, FunctionRegistry},
     },
     solana_sdk::{
-        account::{Account, AccountSharedData},
+        account::{Account, AccountSharedData, WritableAccount},
         bpf_loader,
         bpf_loader_upgradeable::{self, UpgradeableLoaderState},
         feature_set::FeatureSet,
+        loader_v4::{self, LoaderV4State, LoaderV4Status},
         native_loader,
+        native_token::LAMPORTS_PER_SOL,
         pubkey::Pubkey,
         rent::Rent,
+        system_program,
+    },
+    std::{
+        collections::HashMap,
+        sync::{Arc, RwLock},
     },
-    std::sync::{Arc, RwLock},
 };
 
+/// Flags for the runtime environment a program is loaded into.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LoaderOptions {
+    /// Reject ELFs the loader considers broken, such as those with
+    /// unresolved symbols, as it does when a program is deployed. Enable this
+    /// to reproduce deployment-time verification failures.
+    pub reject_broken_elfs: bool,
+    /// Enable the VM's debugging features: symbol and section labels, and
+    /// instruction tracing.
+    pub debugging_features: bool,
+}
+
+/// Syscalls to replace, or add, in the runtime environment a program is
+/// loaded into, for injecting faults or stubbing out behavior.
+///
+/// A syscall is a function declared with
+/// `solana_rbpf::declare_builtin_function!`, and is registered by its VM
+/// interface, eg. `SyscallOverrides::default().set("sol_log_", MyLog::vm)`.
+///
+/// Overrides apply only to the programs added with them, not to programs
+/// they invoke via CPI.
+#[derive(Clone, Debug, Default)]
+pub struct SyscallOverrides {
+    syscalls: Vec<(&'static str, BuiltinFunction<InvokeContext<'static>>)>,
+}
+
+impl SyscallOverrides {
+    /// Override the syscall named `name`, such as `"sol_invoke_signed_rust"`,
+    /// replacing any previous override of the same name. A syscall not
+    /// otherwise available, such as a feature-gated one, is added.
+    pub fn set(
+        mut self,
+        name: &'static str,
+        syscall: BuiltinFunction<InvokeContext<'static>>,
+    ) -> Self {
+        self.syscalls.retain(|(existing, _)| *existing != name);
+        self.syscalls.push((name, syscall));
+        self
+    }
+
+    fn is_empty(&self) -> bool {
+        self.syscalls.is_empty()
+    }
+}
+
+// A non-builtin program's ELF, along with everything needed to recompile it.
+#[derive(Clone)]
+struct ProgramElf {
+    loader_key: Pubkey,
+    elf: Vec<u8>,
+    options: LoaderOptions,
+    syscalls: SyscallOverrides,
+    deployment_slot: u64,
+}
+
 pub struct ProgramCache {
+    // The cache's runtime environment is the one programs deployed during
+    // execution, eg. by BPF Loader Upgradeable, are verified and loaded into.
     cache: RwLock<ProgramCacheForTxBatch>,
+    // `ProgramCacheForTxBatch` does not support removing entries, so all
+    // entries are also tracked here, allowing the cache to be rebuilt.
+    entries: HashMap<Pubkey, Arc<ProgramCacheEntry>>,
+    // The ELF of every non-builtin program, so they can be recompiled when
+    // the runtime environment changes.
+    elfs: HashMap<Pubkey, ProgramElf>,
+}
+
+/// Cloning a program cache shares the compiled program entries, which are
+/// immutable, but not the cache itself. Programs added to or removed from a
+/// clone are not visible to the original, and vice versa.
+impl Clone for ProgramCache {
+    fn clone(&self) -> Self {
+        Self {
+            cache: RwLock::new(self.cache.read().unwrap().clone()),
+            entries: self.entries.clone(),
+            elfs: self.elfs.clone(),
+        }
+    }
 }
 
 impl Default for ProgramCache {
     fn default() -> Self {
-        let mut cache = ProgramCacheForTxBatch::default();
+        let mut program_cache = Self {
+            cache: RwLock::new(ProgramCacheForTxBatch::default()),
+            entries: HashMap::new(),
+            elfs: HashMap::new(),
+        };
+        program_cache.set_environment(&ComputeBudget::default(), &FeatureSet::all_enabled());
         BUILTINS.iter().for_each(|builtin| {
             let program_id = builtin.program_id;
             let entry = builtin.program_cache_entry();
-            cache.replenish(program_id, entry);
+            program_cache.replenish(program_id, entry);
         });
-        Self {
-            cache: RwLock::new(cache),
-        }
+        program_cache
     }
 }
 
@@ -42,7 +132,59 @@ impl ProgramCache {
         &self.cache
     }
 
+    fn set_environment(&mut self, compute_budget: &ComputeBudget, feature_set: &FeatureSet) {
+        let environment = create_environment(
+            compute_budget,
+            feature_set,
+            LoaderOptions::default(),
+            &SyscallOverrides::default(),
+        )
+        .unwrap_or_else(|err| panic!("Failed to create runtime environment: {}", err));
+        let cache = self.cache.get_mut().unwrap();
+        cache.environments.program_runtime_v1 = environment;
+    }
+
+    fn replenish(&mut self, program_id: Pubkey, entry: Arc<ProgramCacheEntry>) {
+        self.entries.insert(program_id, entry.clone());
+        self.cache.write().unwrap().replenish(program_id, entry);
+    }
+
+    /// Whether a program, builtin or otherwise, is in the cache.
+    pub fn contains(&self, program_id: &Pubkey) -> bool {
+        self.entries.contains_key(program_id)
+    }
+
+    /// The IDs of every program in the cache, including builtins, sorted.
+    pub fn program_ids(&self) -> Vec<Pubkey> {
+        let mut program_ids = self.entries.keys().copied().collect::<Vec<_>>();
+        program_ids.sort();
+        program_ids
+    }
+
+    /// Add a program to the cache, returning an error if the ELF fails to load
+    /// or verify.
+    pub fn try_add_program(
+        &mut self,
+        program_id: &Pubkey,
+        loader_key: &Pubkey,
+        elf: &[u8],
+        compute_budget: &ComputeBudget,
+        feature_set: &FeatureSet,
+    ) -> Result<(), MolluskError> {
+        self.try_add_program_with_environment(
+            program_id,
+            loader_key,
+            elf,
+            compute_budget,
+            feature_set,
+            LoaderOptions::default(),
+            SyscallOverrides::default(),
+        )
+    }
+
     /// Add a program to the cache.
+    ///
+    /// Panics if the ELF fails to load or verify. See `try_add_program`.
     pub fn add_program(
         &mut self,
         program_id: &Pubkey,
@@ -51,32 +193,367 @@ impl ProgramCache {
         compute_budget: &ComputeBudget,
         feature_set: &FeatureSet,
     ) {
-        let environment = Arc::new(
-            create_program_runtime_environment_v1(feature_set, compute_budget, false, false)
-                .unwrap(),
+        self.add_program_with_options(
+            program_id,
+            loader_key,
+            elf,
+            compute_budget,
+            feature_set,
+            LoaderOptions::default(),
         );
-        self.cache.write().unwrap().replenish(
-            *program_id,
-            Arc::new(
-                ProgramCacheEntry::new(
-                    loader_key,
-                    environment,
-                    0,
-                    0,
-                    elf,
-                    elf.len(),
-                    &mut LoadProgramMetrics::default(),
-                )
-                .unwrap(),
-            ),
+    }
+
+    /// Add a program to the cache, loading it into a runtime environment
+    /// with the provided flags, returning an error if the ELF fails to load
+    /// or verify. The flags are kept when the program is recompiled.
+    pub fn try_add_program_with_options(
+        &mut self,
+        program_id: &Pubkey,
+        loader_key: &Pubkey,
+        elf: &[u8],
+        compute_budget: &ComputeBudget,
+        feature_set: &FeatureSet,
+        options: LoaderOptions,
+    ) -> Result<(), MolluskError> {
+        self.try_add_program_with_environment(
+            program_id,
+            loader_key,
+            elf,
+            compute_budget,
+            feature_set,
+            options,
+            SyscallOverrides::default(),
+        )
+    }
+
+    /// Add a program to the cache, loading it into a runtime environment
+    /// with the provided flags. The flags are kept when the program is
+    /// recompiled.
+    ///
+    /// Panics if the ELF fails to load or verify. See
+    /// `try_add_program_with_options`.
+    pub fn add_program_with_options(
+        &mut self,
+        program_id: &Pubkey,
+        loader_key: &Pubkey,
+        elf: &[u8],
+        compute_budget: &ComputeBudget,
+        feature_set: &FeatureSet,
+        options: LoaderOptions,
+    ) {
+        self.add_program_with_environment(
+            program_id,
+            loader_key,
+            elf,
+            compute_budget,
+            feature_set,
+            options,
+            SyscallOverrides::default(),
         );
     }
 
+    /// Add a program to the cache, overriding some of the syscalls in the
+    /// runtime environment it's loaded into, returning an error if the ELF
+    /// fails to load or verify, or if an override fails to register. The
+    /// overrides are kept when the program is recompiled.
+    pub fn try_add_program_with_syscalls(
+        &mut self,
+        program_id: &Pubkey,
+        loader_key: &Pubkey,
+        elf: &[u8],
+        compute_budget: &ComputeBudget,
+        feature_set: &FeatureSet,
+        syscalls: SyscallOverrides,
+    ) -> Result<(), MolluskError> {
+        self.try_add_program_with_environment(
+            program_id,
+            loader_key,
+            elf,
+            compute_budget,
+            feature_set,
+            LoaderOptions::default(),
+            syscalls,
+        )
+    }
+
+    /// Add a program to the cache, overriding some of the syscalls in the
+    /// runtime environment it's loaded into. The overrides are kept when the
+    /// program is recompiled.
+    ///
+    /// Panics if the ELF fails to load or verify, or if an override fails to
+    /// register. See `try_add_program_with_syscalls`.
+    pub fn add_program_with_syscalls(
+        &mut self,
+        program_id: &Pubkey,
+        loader_key: &Pubkey,
+        elf: &[u8],
+        compute_budget: &ComputeBudget,
+        feature_set: &FeatureSet,
+        syscalls: SyscallOverrides,
+    ) {
+        self.add_program_with_environment(
+            program_id,
+            loader_key,
+            elf,
+            compute_budget,
+            feature_set,
+            LoaderOptions::default(),
+            syscalls,
+        );
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn add_program_with_environment(
+        &mut self,
+        program_id: &Pubkey,
+        loader_key: &Pubkey,
+        elf: &[u8],
+        compute_budget: &ComputeBudget,
+        feature_set: &FeatureSet,
+        options: LoaderOptions,
+        syscalls: SyscallOverrides,
+    ) {
+        self.try_add_program_with_environment(
+            program_id,
+            loader_key,
+            elf,
+            compute_budget,
+            feature_set,
+            options,
+            syscalls,
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn try_add_program_with_environment(
+        &mut self,
+        program_id: &Pubkey,
+        loader_key: &Pubkey,
+        elf: &[u8],
+        compute_budget: &ComputeBudget,
+        feature_set: &FeatureSet,
+        options: LoaderOptions,
+        syscalls: SyscallOverrides,
+    ) -> Result<(), MolluskError> {
+        let program_elf = ProgramElf {
+            loader_key: *loader_key,
+            elf: elf.to_vec(),
+            options,
+            syscalls,
+            deployment_slot: 0,
+        };
+        let entry = program_elf.try_load(program_id, compute_budget, feature_set)?;
+        self.elfs.insert(*program_id, program_elf);
+        self.replenish(*program_id, entry);
+        Ok(())
+    }
+
+    /// Replace the ELF of a program in the cache, as if it were redeployed at
+    /// `deployment_slot`. The program keeps its loader and loader options.
+    ///
+    /// The new program is visible immediately, rather than from the slot
+    /// after its deployment as it would be on a cluster.
+    ///
+    /// Panics if the program is not in the cache, or is a builtin.
+    pub fn upgrade_program(
+        &mut self,
+        program_id: &Pubkey,
+        elf: &[u8],
+        deployment_slot: u64,
+        compute_budget: &ComputeBudget,
+        feature_set: &FeatureSet,
+    ) {
+        let program_elf = self
+            .elfs
+            .get_mut(program_id)
+            .unwrap_or_else(|| panic!("Program {} cannot be upgraded", program_id));
+        program_elf.elf = elf.to_vec();
+        program_elf.deployment_slot = deployment_slot;
+        let entry = program_elf.load(program_id, compute_budget, feature_set);
+        self.replenish(*program_id, entry);
+    }
+
     /// Add a builtin program to the cache.
     pub fn add_builtin(&mut self, builtin: Builtin) {
         let program_id = builtin.program_id;
         let entry = builtin.program_cache_entry();
-        self.cache.write().unwrap().replenish(program_id, entry);
+        self.replenish(program_id, entry);
+    }
+
+    /// Add one of the builtin programs that are only available with the
+    /// `all-builtins` feature to the cache. Panics if `program_id` is not one
+    /// of them.
+    #[cfg(feature = "all-builtins")]
+    pub(crate) fn add_optional_builtin(&mut self, program_id: &Pubkey) {
+        let builtin = OPTIONAL_BUILTINS
+            .iter()
+            .find(|builtin| builtin.program_id == *program_id)
+            .unwrap_or_else(|| panic!("{} is not an optional builtin", program_id));
+        self.replenish(*program_id, builtin.program_cache_entry());
+    }
+
+    /// Get the fixed compute units charged by a builtin program in the cache
+    /// when invoked. Returns `None` if the program is not a builtin in the
+    /// cache.
+    pub(crate) fn builtin_compute_units(&self, program_id: &Pubkey) -> Option<u64> {
+        if self.entries.get(program_id)?.account_owner() != native_loader::id() {
+            return None;
+        }
+        BUILTINS
+            .iter()
+            .chain(OPTIONAL_BUILTINS)
+            .find(|builtin| builtin.program_id == *program_id)
+            .map(|builtin| builtin.compute_units)
+    }
+
+    /// Remove a program from the cache.
+    ///
+    /// Subsequent invocations of the program will fail just as they would for
+    /// a program that was never added.
+    pub fn remove_program(&mut self, program_id: &Pubkey) {
+        self.elfs.remove(program_id);
+        if self.entries.remove(program_id).is_some() {
+            let mut cache = ProgramCacheForTxBatch::default();
+            cache.environments = self.cache.get_mut().unwrap().environments.clone();
+            self.entries.iter().for_each(|(program_id, entry)| {
+                cache.replenish(*program_id, entry.clone());
+            });
+            *self.cache.get_mut().unwrap() = cache;
+        }
+    }
+
+    /// Create the account for a program in the cache, for use as the program
+    /// account of an instruction invoking it. Returns `None` if the program
+    /// is not in the cache.
+    pub(crate) fn program_account(&self, program_id: &Pubkey) -> Option<AccountSharedData> {
+        let loader_key = self.entries.get(program_id)?.account_owner();
+        if loader_key == native_loader::id() {
+            let name = BUILTINS
+                .iter()
+                .chain(OPTIONAL_BUILTINS)
+                .find(|builtin| builtin.program_id == *program_id)
+                .map_or("", |builtin| builtin.name);
+            return Some(builtin_program_account(program_id, name).1);
+        }
+        let elf = &self.elfs.get(program_id)?.elf;
+        let account = if loader_key == bpf_loader_upgradeable::id() {
+            program_account(program_id)
+        } else if loader_key == loader_v4::id() {
+            program_account_loader_v4(elf, Pubkey::default(), LoaderV4Status::Deployed)
+        } else {
+            let mut account = program_account_loader_2(elf);
+            account.set_owner(loader_key);
+            account
+        };
+        Some(account)
+    }
+
+    /// Recompile every non-builtin program in the cache against a new runtime
+    /// environment.
+    ///
+    /// A program's syscalls and VM configuration are derived from the
+    /// compute budget and feature set when it is loaded, so this must be
+    /// called after either changes for cached programs, and programs deployed
+    /// during execution, to observe the change.
+    pub fn recompile_programs(&mut self, compute_budget: &ComputeBudget, feature_set: &FeatureSet) {
+        self.set_environment(compute_budget, feature_set);
+        let entries = self
+            .elfs
+            .iter()
+            .map(|(program_id, program_elf)| {
+                let entry = program_elf.load(program_id, compute_budget, feature_set);
+                (*program_id, entry)
+            })
+            .collect::<Vec<_>>();
+        entries.into_iter().for_each(|(program_id, entry)| {
+            self.replenish(program_id, entry);
+        });
+    }
+}
+
+// Create the runtime environment a program is loaded into, erroring if the
+// environment's syscalls fail to register, such as when an override's name
+// collides with another syscall's hash.
+fn create_environment(
+    compute_budget: &ComputeBudget,
+    feature_set: &FeatureSet,
+    options: LoaderOptions,
+    syscalls: &SyscallOverrides,
+) -> Result<ProgramRuntimeEnvironment, String> {
+    let environment = create_program_runtime_environment_v1(
+        feature_set,
+        compute_budget,
+        options.reject_broken_elfs,
+        options.debugging_features,
+    )
+    .map_err(|err| err.to_string())?;
+    if syscalls.is_empty() {
+        return Ok(Arc::new(environment));
+    }
+    let mut functions = FunctionRegistry::default();
+    for (key, (name, function)) in environment.get_function_registry().iter() {
+        if !syscalls
+            .syscalls
+            .iter()
+            .any(|(overridden, _)| overridden.as_bytes() == name)
+        {
+            functions
+                .register_function(key, name, function)
+                .map_err(|err| err.to_string())?;
+        }
+    }
+    for (name, function) in syscalls.syscalls.iter() {
+        functions
+            .register_function_hashed(*name, *function)
+            .map_err(|err| format!("Failed to register syscall {}: {}", name, err))?;
+    }
+    Ok(Arc::new(BuiltinProgram::new_loader(
+        *environment.get_config(),
+        functions,
+    )))
+}
+
+impl ProgramElf {
+    // The program is effective from its deployment slot, so it's visible
+    // immediately.
+    fn try_load(
+        &self,
+        program_id: &Pubkey,
+        compute_budget: &ComputeBudget,
+        feature_set: &FeatureSet,
+    ) -> Result<Arc<ProgramCacheEntry>, MolluskError> {
+        let environment =
+            create_environment(compute_budget, feature_set, self.options, &self.syscalls).map_err(
+                |error| MolluskError::InvalidEnvironment {
+                    program_id: *program_id,
+                    error,
+                },
+            )?;
+        let entry = ProgramCacheEntry::new(
+            &self.loader_key,
+            environment,
+            self.deployment_slot,
+            self.deployment_slot,
+            &self.elf,
+            self.elf.len(),
+            &mut LoadProgramMetrics::default(),
+        )
+        .map_err(|err| MolluskError::InvalidElf {
+            program_id: *program_id,
+            error: err.to_string(),
+        })?;
+        Ok(Arc::new(entry))
+    }
+
+    fn load(
+        &self,
+        program_id: &Pubkey,
+        compute_budget: &ComputeBudget,
+        feature_set: &FeatureSet,
+    ) -> Arc<ProgramCacheEntry> {
+        self.try_load(program_id, compute_budget, feature_set)
+            .unwrap_or_else(|err| panic!("{}", err))
     }
 }
 
@@ -84,6 +561,8 @@ pub struct Builtin {
     program_id: Pubkey,
     name: &'static str,
     entrypoint: BuiltinFunctionWithContext,
+    // The fixed compute units the builtin charges when invoked directly.
+    compute_units: u64,
 }
 
 impl Builtin {
@@ -101,20 +580,72 @@ static BUILTINS: &[Builtin] = &[
         program_id: solana_system_program::id(),
         name: "system_program",
         entrypoint: solana_system_program::system_processor::Entrypoint::vm,
+        compute_units: solana_system_program::system_processor::DEFAULT_COMPUTE_UNITS,
     },
     Builtin {
         program_id: bpf_loader::id(),
         name: "solana_bpf_loader_program",
         entrypoint: solana_bpf_loader_program::Entrypoint::vm,
+        compute_units: solana_bpf_loader_program::DEFAULT_LOADER_COMPUTE_UNITS,
     },
     Builtin {
         program_id: bpf_loader_upgradeable::id(),
         name: "solana_bpf_loader_upgradeable_program",
         entrypoint: solana_bpf_loader_program::Entrypoint::vm,
+        compute_units: solana_bpf_loader_program::UPGRADEABLE_LOADER_COMPUTE_UNITS,
+    },
+    // Loader v4 programs are executed by the BPF Loader's entrypoint, which
+    // runs any program found in the cache. Loader v4 management instructions
+    // (deploy, retract, etc.) are not supported.
+    Builtin {
+        program_id: loader_v4::id(),
+        name: "loader_v4",
+        entrypoint: solana_bpf_loader_program::Entrypoint::vm,
+        compute_units: 0,
     },
     /* ... */
 ];
 
+// Builtins that are not loaded by default, since their crates are only
+// compiled with the `all-builtins` feature. See `Mollusk::add_all_builtins`.
+static OPTIONAL_BUILTINS: &[Builtin] = &[
+    #[cfg(feature = "all-builtins")]
+    Builtin {
+        program_id: solana_sdk::stake::program::id(),
+        name: "stake_program",
+        entrypoint: solana_stake_program::stake_instruction::Entrypoint::vm,
+        compute_units: solana_stake_program::stake_instruction::DEFAULT_COMPUTE_UNITS,
+    },
+    #[cfg(feature = "all-builtins")]
+    Builtin {
+        program_id: solana_sdk::vote::program::id(),
+        name: "vote_program",
+        entrypoint: solana_vote_program::vote_processor::Entrypoint::vm,
+        compute_units: solana_vote_program::vote_processor::DEFAULT_COMPUTE_UNITS,
+    },
+    #[cfg(feature = "all-builtins")]
+    Builtin {
+        program_id: solana_sdk::config::program::id(),
+        name: "config_program",
+        entrypoint: solana_config_program::config_processor::Entrypoint::vm,
+        compute_units: solana_config_program::config_processor::DEFAULT_COMPUTE_UNITS,
+    },
+    #[cfg(feature = "all-builtins")]
+    Builtin {
+        program_id: solana_sdk::address_lookup_table::program::id(),
+        name: "address_lookup_table_program",
+        entrypoint: solana_address_lookup_table_program::processor::Entrypoint::vm,
+        compute_units: solana_address_lookup_table_program::processor::DEFAULT_COMPUTE_UNITS,
+    },
+];
+
+/// The program IDs of the builtins available with the `all-builtins`
+/// feature: the Stake, Vote, Config, and Address Lookup Table programs.
+#[cfg(feature = "all-builtins")]
+pub(crate) fn optional_builtin_ids() -> impl Iterator<Item = Pubkey> {
+    OPTIONAL_BUILTINS.iter().map(|builtin| builtin.program_id)
+}
+
 fn builtin_program_account(program_id: &Pubkey, name: &str) -> (Pubkey, AccountSharedData) {
     let data = name.as_bytes().to_vec();
     let lamports = Rent::default().minimum_balance(data.len());
@@ -138,8 +669,195 @@ pub fn bpf_loader_upgradeable_program() -> (Pubkey, AccountSharedData) {
     builtin_program_account(&BUILTINS[1].program_id, BUILTINS[1].name)
 }
 
+/// Get the key and account for the Stake program.
+#[cfg(feature = "all-builtins")]
+pub fn stake_program() -> (Pubkey, AccountSharedData) {
+    builtin_program_account(&OPTIONAL_BUILTINS[0].program_id, OPTIONAL_BUILTINS[0].name)
+}
+
+/// Get the key and account for the Vote program.
+#[cfg(feature = "all-builtins")]
+pub fn vote_program() -> (Pubkey, AccountSharedData) {
+    builtin_program_account(&OPTIONAL_BUILTINS[1].program_id, OPTIONAL_BUILTINS[1].name)
+}
+
+/// Get the key and account for the Config program.
+#[cfg(feature = "all-builtins")]
+pub fn config_program() -> (Pubkey, AccountSharedData) {
+    builtin_program_account(&OPTIONAL_BUILTINS[2].program_id, OPTIONAL_BUILTINS[2].name)
+}
+
+/// Get the key and account for the Address Lookup Table program.
+#[cfg(feature = "all-builtins")]
+pub fn address_lookup_table_program() -> (Pubkey, AccountSharedData) {
+    builtin_program_account(&OPTIONAL_BUILTINS[3].program_id, OPTIONAL_BUILTINS[3].name)
+}
+
 /* ... */
 
+/// The SPL Token program.
+pub mod spl_token {
+    solana_sdk::declare_id!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+}
+
+const SPL_TOKEN_MINT_LEN: usize = 82;
+const SPL_TOKEN_ACCOUNT_LEN: usize = 165;
+
+// Serialize an SPL Token `COption<Pubkey>`.
+fn pack_coption_pubkey(data: &mut Vec<u8>, pubkey: Option<&Pubkey>) {
+    match pubkey {
+        Some(pubkey) => {
+            data.extend_from_slice(&[1, 0, 0, 0]);
+            data.extend_from_slice(pubkey.as_ref());
+        }
+        None => data.extend_from_slice(&[0; 36]),
+    }
+}
+
+/// Create an initialized SPL Token mint account, with no freeze authority.
+pub fn create_mint(mint_authority: &Pubkey, supply: u64, decimals: u8) -> AccountSharedData {
+    let mut data = Vec::with_capacity(SPL_TOKEN_MINT_LEN);
+    pack_coption_pubkey(&mut data, Some(mint_authority));
+    data.extend_from_slice(&supply.to_le_bytes());
+    data.push(decimals);
+    data.push(1); // is_initialized
+    pack_coption_pubkey(&mut data, None);
+    let lamports = Rent::default().minimum_balance(data.len());
+    AccountSharedData::from(Account {
+        lamports,
+        data,
+        owner: spl_token::id(),
+        executable: false,
+        rent_epoch: 0,
+    })
+}
+
+/// Create an initialized SPL Token account, with no delegate or close
+/// authority.
+pub fn create_token_account(mint: &Pubkey, owner: &Pubkey, amount: u64) -> AccountSharedData {
+    let mut data = Vec::with_capacity(SPL_TOKEN_ACCOUNT_LEN);
+    data.extend_from_slice(mint.as_ref());
+    data.extend_from_slice(owner.as_ref());
+    data.extend_from_slice(&amount.to_le_bytes());
+    pack_coption_pubkey(&mut data, None); // delegate
+    data.push(1); // state: Initialized
+    data.extend_from_slice(&[0; 12]); // is_native: None
+    data.extend_from_slice(&0u64.to_le_bytes()); // delegated_amount
+    pack_coption_pubkey(&mut data, None); // close_authority
+    let lamports = Rent::default().minimum_balance(data.len());
+    AccountSharedData::from(Account {
+        lamports,
+        data,
+        owner: spl_token::id(),
+        executable: false,
+        rent_epoch: 0,
+    })
+}
+
+/// The accounts for a typical SPL Token test, as created by
+/// `spl_token_scenario`.
+#[derive(Clone, Debug)]
+pub struct TokenScenario {
+    /// The mint, whose mint authority is `authority`.
+    pub mint: (Pubkey, AccountSharedData),
+    /// A token account owned by `authority`, holding the mint's full supply.
+    pub source: (Pubkey, AccountSharedData),
+    /// An empty token account owned by `authority`.
+    pub destination: (Pubkey, AccountSharedData),
+    /// A system account funded with one SOL, which owns both token accounts
+    /// and can mint new tokens.
+    pub authority: (Pubkey, AccountSharedData),
+}
+
+impl TokenScenario {
+    /// The key of the mint.
+    pub fn mint_key(&self) -> Pubkey {
+        self.mint.0
+    }
+
+    /// The key of the source token account.
+    pub fn source_key(&self) -> Pubkey {
+        self.source.0
+    }
+
+    /// The key of the destination token account.
+    pub fn destination_key(&self) -> Pubkey {
+        self.destination.0
+    }
+
+    /// The key of the authority.
+    pub fn authority_key(&self) -> Pubkey {
+        self.authority.0
+    }
+
+    /// All accounts in the scenario, in the order mint, source, destination,
+    /// authority.
+    pub fn accounts(&self) -> Vec<(Pubkey, AccountSharedData)> {
+        vec![
+            self.mint.clone(),
+            self.source.clone(),
+            self.destination.clone(),
+            self.authority.clone(),
+        ]
+    }
+}
+
+/// Create a mint with a supply of `amount`, a source token account holding
+/// all of it, an empty destination token account, and an authority which
+/// owns both token accounts and is the mint authority. Every key is unique.
+pub fn spl_token_scenario(decimals: u8, amount: u64) -> TokenScenario {
+    let mint = Pubkey::new_unique();
+    let source = Pubkey::new_unique();
+    let destination = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+    TokenScenario {
+        mint: (mint, create_mint(&authority, amount, decimals)),
+        source: (source, create_token_account(&mint, &authority, amount)),
+        destination: (destination, create_token_account(&mint, &authority, 0)),
+        authority: (
+            authority,
+            AccountSharedData::new(LAMPORTS_PER_SOL, 0, &system_program::id()),
+        ),
+    }
+}
+
+/// Create a rent-exempt, zero-initialized account owned by `owner`.
+///
+/// Rent is computed with `Rent::default()`. To use the rent configured on a
+/// `Mollusk` instance, see `Mollusk::rent_exempt_account`.
+pub fn rent_exempt_account(owner: &Pubkey, space: usize) -> AccountSharedData {
+    let lamports = Rent::default().minimum_balance(space);
+    AccountSharedData::new(lamports, space, owner)
+}
+
+/// Create a rent-exempt account owned by `owner`, holding `data`.
+///
+/// Rent is computed with `Rent::default()`. To use the rent configured on a
+/// `Mollusk` instance, see `Mollusk::rent_exempt_account_with_data`.
+pub fn rent_exempt_account_with_data(owner: &Pubkey, data: &[u8]) -> AccountSharedData {
+    let lamports = Rent::default().minimum_balance(data.len());
+    AccountSharedData::from(Account {
+        lamports,
+        data: data.to_vec(),
+        owner: *owner,
+        executable: false,
+        rent_epoch: 0,
+    })
+}
+
+/// Create a rent-exempt, zero-initialized account at the program-derived
+/// address for `seeds`, owned by the `owner` program.
+///
+/// Returns a tuple of the derived address, its bump seed, and the account.
+pub fn create_pda_account(
+    seeds: &[&[u8]],
+    owner: &Pubkey,
+    space: usize,
+) -> (Pubkey, u8, AccountSharedData) {
+    let (address, bump) = Pubkey::find_program_address(seeds, owner);
+    (address, bump, rent_exempt_account(owner, space))
+}
+
 /// Create a BPF Loader 2 program account.
 pub fn program_account_loader_2(elf: &[u8]) -> AccountSharedData {
     let lamports = Rent::default().minimum_balance(elf.len());
@@ -152,6 +870,65 @@ pub fn program_account_loader_2(elf: &[u8]) -> AccountSharedData {
     })
 }
 
+// The size of the `LoaderV4State` header preceding a loader v4 program's ELF:
+// slot (u64), authority address (Pubkey), and status (u64).
+const LOADER_V4_STATE_LEN: usize = 48;
+
+/// Lay out the data of a BPF Loader v4 program account: the `LoaderV4State`
+/// header, as the loader reads it in place, followed by the ELF.
+pub fn program_data_loader_v4(state: &LoaderV4State, elf: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(LOADER_V4_STATE_LEN + elf.len());
+    data.extend_from_slice(&state.slot.to_le_bytes());
+    data.extend_from_slice(state.authority_address.as_ref());
+    data.extend_from_slice(&(state.status as u64).to_le_bytes());
+    data.extend_from_slice(elf);
+    data
+}
+
+/// Read the `LoaderV4State` header of a BPF Loader v4 program account's
+/// data. Returns `None` if the data is too short, or the status is invalid.
+pub fn loader_v4_state(data: &[u8]) -> Option<LoaderV4State> {
+    let header = data.get(..LOADER_V4_STATE_LEN)?;
+    let status = match u64::from_le_bytes(header[40..48].try_into().unwrap()) {
+        0 => LoaderV4Status::Retracted,
+        1 => LoaderV4Status::Deployed,
+        2 => LoaderV4Status::Finalized,
+        _ => return None,
+    };
+    Some(LoaderV4State {
+        slot: u64::from_le_bytes(header[..8].try_into().unwrap()),
+        authority_address: Pubkey::try_from(&header[8..40]).unwrap(),
+        status,
+    })
+}
+
+/// Create a BPF Loader v4 program account.
+///
+/// The account's data is a `LoaderV4State` header, recording the program as
+/// deployed at slot 0 with the provided authority and status, followed by
+/// the ELF. The status is only recorded: Mollusk invokes programs from its
+/// program cache, so it does not prevent invoking a `Retracted` program.
+pub fn program_account_loader_v4(
+    elf: &[u8],
+    authority: Pubkey,
+    status: LoaderV4Status,
+) -> AccountSharedData {
+    let state = LoaderV4State {
+        slot: 0,
+        authority_address: authority,
+        status,
+    };
+    let data = program_data_loader_v4(&state, elf);
+    let lamports = Rent::default().minimum_balance(data.len());
+    AccountSharedData::from(Account {
+        lamports,
+        data,
+        owner: loader_v4::id(),
+        executable: true,
+        rent_epoch: 0,
+    })
+}
+
 /// Create a BPF Loader Upgradeable program account.
 pub fn program_account(program_id: &Pubkey) -> AccountSharedData {
     let programdata_address =
@@ -171,7 +948,21 @@ pub fn program_account(program_id: &Pubkey) -> AccountSharedData {
 }
 
 /// Create a BPF Loader Upgradeable program data account.
+///
+/// The program is marked as deployed at slot 0, with no upgrade authority.
 pub fn program_data_account(elf: &[u8]) -> AccountSharedData {
+    program_data_account_with_authority(elf, 0, None)
+}
+
+/// Create a BPF Loader Upgradeable program data account, deployed at `slot`
+/// with the provided upgrade authority.
+///
+/// An authority of `None` marks the program as immutable.
+pub fn program_data_account_with_authority(
+    elf: &[u8],
+    slot: u64,
+    authority: Option<Pubkey>,
+) -> AccountSharedData {
     let data = {
         let elf_offset = UpgradeableLoaderState::size_of_programdata_metadata();
         let data_len = elf_offset + elf.len();
@@ -179,8 +970,8 @@ pub fn program_data_account(elf: &[u8]) -> AccountSharedData {
         bincode::serialize_into(
             &mut data[0..elf_offset],
             &UpgradeableLoaderState::ProgramData {
-                slot: 0,
-                upgrade_authority_address: None,
+                slot,
+                upgrade_authority_address: authority,
             },
         )
         .unwrap();
@@ -204,3 +995,204 @@ pub fn program_data_account(elf: &[u8]) -> AccountSharedData {
 pub fn program_accounts(program_id: &Pubkey, elf: &[u8]) -> (AccountSharedData, AccountSharedData) {
     (program_account(program_id), program_data_account(elf))
 }
+
+#[cfg(test)]
+mod tests {
+    use {super::*, solana_sdk::account::ReadableAccount};
+
+    #[test]
+    fn test_create_mint() {
+        let mint_authority = Pubkey::new_unique();
+        let account = create_mint(&mint_authority, 1_000, 6);
+
+        let data = account.data();
+        assert_eq!(data.len(), SPL_TOKEN_MINT_LEN);
+        assert_eq!(account.owner(), &spl_token::id());
+        assert_eq!(&data[0..4], &[1, 0, 0, 0]);
+        assert_eq!(&data[4..36], mint_authority.as_ref());
+        assert_eq!(&data[36..44], &1_000u64.to_le_bytes());
+        assert_eq!(data[44], 6);
+        assert_eq!(data[45], 1);
+        assert_eq!(&data[46..82], &[0; 36]);
+    }
+
+    #[test]
+    fn test_create_token_account() {
+        let mint = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let account = create_token_account(&mint, &owner, 500);
+
+        let data = account.data();
+        assert_eq!(data.len(), SPL_TOKEN_ACCOUNT_LEN);
+        assert_eq!(account.owner(), &spl_token::id());
+        assert_eq!(&data[0..32], mint.as_ref());
+        assert_eq!(&data[32..64], owner.as_ref());
+        assert_eq!(&data[64..72], &500u64.to_le_bytes());
+        assert_eq!(data[108], 1);
+    }
+
+    #[test]
+    fn test_spl_token_scenario() {
+        let scenario = spl_token_scenario(9, 1_000);
+
+        let (mint, mint_account) = &scenario.mint;
+        assert_eq!(*mint, scenario.mint_key());
+        assert_eq!(
+            mint_account,
+            &create_mint(&scenario.authority_key(), 1_000, 9)
+        );
+
+        let (_, source_account) = &scenario.source;
+        assert_eq!(
+            source_account,
+            &create_token_account(mint, &scenario.authority_key(), 1_000)
+        );
+
+        let (_, destination_account) = &scenario.destination;
+        assert_eq!(
+            destination_account,
+            &create_token_account(mint, &scenario.authority_key(), 0)
+        );
+
+        let (_, authority_account) = &scenario.authority;
+        assert_eq!(authority_account.owner(), &system_program::id());
+        assert_eq!(authority_account.lamports(), LAMPORTS_PER_SOL);
+
+        let keys = scenario
+            .accounts()
+            .iter()
+            .map(|(key, _)| *key)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            vec![
+                scenario.mint_key(),
+                scenario.source_key(),
+                scenario.destination_key(),
+                scenario.authority_key(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rent_exempt_account() {
+        let owner = Pubkey::new_unique();
+        let rent = Rent::default();
+
+        let account = rent_exempt_account(&owner, 100);
+        assert_eq!(account.owner(), &owner);
+        assert_eq!(account.data(), &[0; 100]);
+        assert_eq!(account.lamports(), rent.minimum_balance(100));
+
+        let account = rent_exempt_account_with_data(&owner, &[1, 2, 3]);
+        assert_eq!(account.owner(), &owner);
+        assert_eq!(account.data(), &[1, 2, 3]);
+        assert_eq!(account.lamports(), rent.minimum_balance(3));
+    }
+
+    #[test]
+    fn test_create_pda_account() {
+        let owner = Pubkey::new_unique();
+        let (address, bump, account) = create_pda_account(&[b"vault", &[7]], &owner, 64);
+
+        assert_eq!(
+            Pubkey::create_program_address(&[b"vault", &[7], &[bump]], &owner).unwrap(),
+            address
+        );
+        assert_eq!(account.owner(), &owner);
+        assert_eq!(account.data(), &[0; 64]);
+        assert_eq!(account.lamports(), Rent::default().minimum_balance(64));
+        assert!(!account.executable());
+    }
+
+    #[test]
+    fn test_program_data_account_with_authority() {
+        let elf = &[1, 2, 3, 4];
+        let authority = Pubkey::new_unique();
+        let account = program_data_account_with_authority(elf, 42, Some(authority));
+
+        let elf_offset = UpgradeableLoaderState::size_of_programdata_metadata();
+        let data = account.data();
+        assert_eq!(
+            bincode::deserialize::<UpgradeableLoaderState>(&data[..elf_offset]).unwrap(),
+            UpgradeableLoaderState::ProgramData {
+                slot: 42,
+                upgrade_authority_address: Some(authority),
+            }
+        );
+        assert_eq!(&data[elf_offset..], elf);
+        assert_eq!(account.owner(), &bpf_loader_upgradeable::id());
+        assert!(!account.executable());
+
+        assert_eq!(
+            program_data_account(elf),
+            program_data_account_with_authority(elf, 0, None)
+        );
+    }
+
+    #[test]
+    fn test_contains_and_program_ids() {
+        let mut program_cache = ProgramCache::default();
+        let builtin_ids = BUILTINS
+            .iter()
+            .map(|builtin| builtin.program_id)
+            .collect::<Vec<_>>();
+        assert!(builtin_ids.iter().all(|id| program_cache.contains(id)));
+        assert_eq!(program_cache.program_ids().len(), builtin_ids.len());
+        assert!(!program_cache.contains(&Pubkey::new_unique()));
+
+        program_cache.remove_program(&bpf_loader::id());
+        assert!(!program_cache.contains(&bpf_loader::id()));
+        assert!(!program_cache.program_ids().contains(&bpf_loader::id()));
+        assert_eq!(program_cache.program_ids().len(), builtin_ids.len() - 1);
+    }
+
+    #[test]
+    fn test_program_account_loader_v4() {
+        let elf = &[1, 2, 3, 4];
+        let authority = Pubkey::new_unique();
+        let account = program_account_loader_v4(elf, authority, LoaderV4Status::Deployed);
+
+        let data = account.data();
+        assert_eq!(LOADER_V4_STATE_LEN, LoaderV4State::program_data_offset());
+        assert_eq!(data.len(), LOADER_V4_STATE_LEN + elf.len());
+        assert_eq!(account.owner(), &loader_v4::id());
+        assert!(account.executable());
+        assert_eq!(&data[..8], &0u64.to_le_bytes());
+        assert_eq!(&data[8..40], authority.as_ref());
+        assert_eq!(&data[40..48], &1u64.to_le_bytes());
+        assert_eq!(&data[LOADER_V4_STATE_LEN..], elf);
+
+        assert_eq!(
+            loader_v4_state(data),
+            Some(LoaderV4State {
+                slot: 0,
+                authority_address: authority,
+                status: LoaderV4Status::Deployed,
+            })
+        );
+    }
+
+    #[test]
+    fn test_loader_v4_state() {
+        let elf = &[1, 2, 3, 4];
+        for status in [
+            LoaderV4Status::Retracted,
+            LoaderV4Status::Deployed,
+            LoaderV4Status::Finalized,
+        ] {
+            let state = LoaderV4State {
+                slot: 42,
+                authority_address: Pubkey::new_unique(),
+                status,
+            };
+            let data = program_data_loader_v4(&state, elf);
+            assert_eq!(loader_v4_state(&data), Some(state));
+        }
+
+        assert_eq!(loader_v4_state(&[0; LOADER_V4_STATE_LEN - 1]), None);
+        let mut data = [0; LOADER_V4_STATE_LEN];
+        data[40] = 3;
+        assert_eq!(loader_v4_state(&data), None);
+    }
+}
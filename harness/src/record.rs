@@ -0,0 +1,123 @@
+//! Recorded executions, for one-off debugging.
+//!
+//! A recording captures the accounts provided to an instruction, the result
+//! of executing it, and the changes it made to each account, and can be
+//! written to a JSON file for offline inspection. Unlike a fixture, it is not
+//! meant to be replayed.
+//!
+//! Like the `file` module, these functions are intended for testing purposes
+//! and will panic if a recording cannot be written.
+
+use {
+    crate::result::{AccountDiff, InstructionResult},
+    base64::{prelude::BASE64_STANDARD, Engine},
+    serde_json::{json, Value},
+    solana_sdk::{
+        account::{AccountSharedData, ReadableAccount},
+        pubkey::Pubkey,
+    },
+    std::{fs::File, path::Path},
+};
+
+/// An instruction execution, with the full before and after state of every
+/// account it was provided.
+#[derive(Debug)]
+pub struct RecordedExecution {
+    /// The accounts provided to the instruction.
+    pub accounts: Vec<(Pubkey, AccountSharedData)>,
+    /// The result of executing the instruction.
+    pub result: InstructionResult,
+    /// The changes made to each account, in the order of the resulting
+    /// accounts. See `InstructionResult::account_diffs`.
+    pub diffs: Vec<AccountDiff>,
+}
+
+impl RecordedExecution {
+    /// Record the result of executing an instruction with the provided
+    /// accounts.
+    pub fn new(accounts: Vec<(Pubkey, AccountSharedData)>, result: InstructionResult) -> Self {
+        let diffs = result.account_diffs(&accounts);
+        Self {
+            accounts,
+            result,
+            diffs,
+        }
+    }
+
+    /// Render the recording as JSON.
+    ///
+    /// Accounts are rendered in the format produced by
+    /// `solana account --output json`, and the bytes of each changed data
+    /// range are included before and after, base64-encoded.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "computeUnitsConsumed": self.result.compute_units_consumed,
+            "programResult": format!("{:?}", self.result.program_result),
+            "returnData": BASE64_STANDARD.encode(&self.result.return_data),
+            "logs": self.result.logs,
+            "accounts": keyed_accounts_json(&self.accounts),
+            "resultingAccounts": keyed_accounts_json(&self.result.resulting_accounts),
+            "diffs": self.diffs.iter().map(|diff| self.diff_json(diff)).collect::<Vec<_>>(),
+        })
+    }
+
+    /// Write the recording to a file as pretty-printed JSON.
+    pub fn write_json<P: AsRef<Path>>(&self, path: P) {
+        let path = path.as_ref();
+        let file = File::create(path)
+            .unwrap_or_else(|err| panic!("Failed to create {}: {}", path.display(), err));
+        serde_json::to_writer_pretty(file, &self.to_json())
+            .unwrap_or_else(|err| panic!("Failed to write recording {}: {}", path.display(), err));
+    }
+
+    fn diff_json(&self, diff: &AccountDiff) -> Value {
+        // A diff is only produced for an account present in both the inputs
+        // and the results.
+        let original = get_data(&self.accounts, &diff.pubkey);
+        let resulting = get_data(&self.result.resulting_accounts, &diff.pubkey);
+        let data_changes = diff
+            .data_changes
+            .iter()
+            .map(|range| {
+                json!({
+                    "offset": range.start,
+                    "before": BASE64_STANDARD.encode(&original[range.clone()]),
+                    "after": BASE64_STANDARD.encode(&resulting[range.clone()]),
+                })
+            })
+            .collect::<Vec<_>>();
+        json!({
+            "pubkey": diff.pubkey.to_string(),
+            "lamports": diff.lamports,
+            "owner": diff.owner.map(|(before, after)| [before.to_string(), after.to_string()]),
+            "dataLen": diff.data_len,
+            "dataChanges": data_changes,
+        })
+    }
+}
+
+fn get_data<'a>(accounts: &'a [(Pubkey, AccountSharedData)], pubkey: &Pubkey) -> &'a [u8] {
+    accounts
+        .iter()
+        .find(|(key, _)| key == pubkey)
+        .map(|(_, account)| account.data())
+        .unwrap_or_default()
+}
+
+fn keyed_accounts_json(accounts: &[(Pubkey, AccountSharedData)]) -> Vec<Value> {
+    accounts
+        .iter()
+        .map(|(pubkey, account)| {
+            json!({
+                "pubkey": pubkey.to_string(),
+                "account": {
+                    "lamports": account.lamports(),
+                    "data": [BASE64_STANDARD.encode(account.data()), "base64"],
+                    "owner": account.owner().to_string(),
+                    "executable": account.executable(),
+                    "rentEpoch": account.rent_epoch(),
+                },
+            })
+        })
+        .collect()
+}
@@ -1,16 +1,186 @@
 //! Results of Mollusk program execution.
 
-use solana_sdk::{
-    account::{AccountSharedData, ReadableAccount},
-    instruction::InstructionError,
-    program_error::ProgramError,
-    pubkey::Pubkey,
+use {
+    base64::{prelude::BASE64_STANDARD, Engine},
+    serde::{Deserialize, Serialize},
+    solana_program_runtime::timings::ExecuteDetailsTimings,
+    solana_sdk::{
+        account::{AccountSharedData, ReadableAccount},
+        instruction::{AccountMeta, Instruction, InstructionError},
+        program_error::ProgramError,
+        pubkey::Pubkey,
+        rent::Rent,
+        transaction_context::TransactionContext,
+    },
+    std::{
+        collections::{BTreeMap, HashMap},
+        fmt,
+        ops::Range,
+        path::Path,
+    },
 };
 
+/// The offset Anchor adds to the discriminant of a program's custom error
+/// enum (`#[error_code]`) to produce its `ProgramError::Custom` code.
+///
+/// Codes below this offset are reserved for Anchor's own framework errors.
+pub const ANCHOR_ERROR_CODE_OFFSET: u32 = 6000;
+
+/// The environment variable which, when set to `1`, makes
+/// `Check::compute_units_baseline` write the compute units consumed as the
+/// new baseline, rather than checking against the stored one.
+pub const UPDATE_BASELINE_VAR: &str = "MOLLUSK_UPDATE_BASELINE";
+
+/// Compute the custom error code Anchor returns for a variant of a program's
+/// `#[error_code]` enum, given the variant's discriminant (ie. its index in
+/// the enum, unless explicitly set).
+pub fn anchor_error_code(discriminant: u32) -> u32 {
+    ANCHOR_ERROR_CODE_OFFSET + discriminant
+}
+
+/// Compute the discriminator Anchor prefixes to the data of an event emitted
+/// with `emit!`: the first 8 bytes of the SHA-256 hash of `event:<name>`,
+/// where `name` is the name of the event struct. Requires the `anchor`
+/// feature.
+#[cfg(feature = "anchor")]
+pub fn anchor_event_discriminator(name: &str) -> [u8; 8] {
+    let hash = solana_sdk::hash::hash(format!("event:{}", name).as_bytes());
+    hash.to_bytes()[..8].try_into().unwrap()
+}
+
+// The bare name of an event's struct, without module path or generics, for
+// labelling check failures and panics. The discriminator is taken from the
+// event's `Discriminator` implementation, not derived from this.
+#[cfg(feature = "anchor")]
+fn anchor_event_name<T>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    let name = name.split('<').next().unwrap_or(name);
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+// Decode the data logged with `sol_log_data`, ie. the base64 fields of each
+// `Program data: ` log line, in order.
+#[cfg(feature = "anchor")]
+fn logged_data(logs: &[String]) -> Vec<Vec<u8>> {
+    logs.iter()
+        .filter_map(|log| log.strip_prefix("Program data: "))
+        .flat_map(|fields| fields.split(' '))
+        .filter_map(|field| BASE64_STANDARD.decode(field).ok())
+        .collect()
+}
+
+// Parse the compute units consumed by each program invocation from the
+// `Program {id} consumed {units} of {limit} compute units` log messages
+// emitted by the BPF Loader. Builtins don't log their consumption, so each
+// completed builtin invocation (`Program {id} success` or `failed`) is
+// attributed the fixed cost given by `builtin_compute_units`.
+pub(crate) fn compute_units_per_program(
+    logs: &[String],
+    builtin_compute_units: impl Fn(&Pubkey) -> Option<u64>,
+) -> Vec<(Pubkey, u64)> {
+    logs.iter()
+        .filter_map(|log| {
+            let mut words = log.strip_prefix("Program ")?.split(' ');
+            let program_id = words.next()?.parse::<Pubkey>().ok()?;
+            let units = match words.next()? {
+                "consumed" => words.next()?.parse::<u64>().ok()?,
+                "success" | "failed:" => builtin_compute_units(&program_id)?,
+                _ => return None,
+            };
+            Some((program_id, units))
+        })
+        .collect()
+}
+
+// Whether a log line is emitted by the runtime to frame a program's
+// execution, rather than by the program itself: its invocation, compute units
+// consumed, success or failure, and return data.
+fn is_framing_log(log: &str) -> bool {
+    let Some(rest) = log.strip_prefix("Program ") else {
+        return false;
+    };
+    if rest.starts_with("return: ") {
+        return true;
+    }
+    let mut words = rest.split(' ');
+    words
+        .next()
+        .is_some_and(|word| word.parse::<Pubkey>().is_ok())
+        && matches!(
+            words.next(),
+            Some("invoke" | "consumed" | "success" | "failed:")
+        )
+}
+
+// Whether an account exists, as far as the runtime is concerned. An account
+// with no lamports and no data is indistinguishable from one never created.
+fn account_exists(account: &AccountSharedData) -> bool {
+    account.lamports() != 0 || !account.data().is_empty()
+}
+
+// Collect the instructions invoked via CPI from the transaction context's
+// instruction trace, in the order they were invoked.
+pub(crate) fn inner_instructions(
+    transaction_context: &TransactionContext,
+) -> Vec<InnerInstruction> {
+    (0..transaction_context.get_instruction_trace_length())
+        .filter_map(|index| {
+            let instruction_context = transaction_context
+                .get_instruction_context_at_index_in_trace(index)
+                .ok()?;
+            let stack_height = instruction_context.get_stack_height();
+            if stack_height <= 1 {
+                return None;
+            }
+            let program_id = *instruction_context
+                .get_last_program_key(transaction_context)
+                .ok()?;
+            let accounts = (0..instruction_context.get_number_of_instruction_accounts())
+                .map(|index| {
+                    let index_in_transaction = instruction_context
+                        .get_index_of_instruction_account_in_transaction(index)
+                        .ok()?;
+                    Some(AccountMeta {
+                        pubkey: *transaction_context
+                            .get_key_of_account_at_index(index_in_transaction)
+                            .ok()?,
+                        is_signer: instruction_context
+                            .is_instruction_account_signer(index)
+                            .ok()?,
+                        is_writable: instruction_context
+                            .is_instruction_account_writable(index)
+                            .ok()?,
+                    })
+                })
+                .collect::<Option<Vec<_>>>()?;
+            Some(InnerInstruction {
+                instruction: Instruction {
+                    program_id,
+                    accounts,
+                    data: instruction_context.get_instruction_data().to_vec(),
+                },
+                stack_height,
+            })
+        })
+        .collect()
+}
+
+/// An instruction invoked via CPI during execution.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InnerInstruction {
+    /// The invoked instruction, with the account privileges it was invoked
+    /// with.
+    pub instruction: Instruction,
+    /// The instruction stack height at which it was invoked. An instruction
+    /// invoked by a top-level instruction has a height of 2.
+    pub stack_height: usize,
+}
+
 /// The result code of the program's execution.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
 pub enum ProgramResult {
     /// The program executed successfully.
+    #[default]
     Success,
     /// The program returned an error.
     Failure(ProgramError),
@@ -18,6 +188,20 @@ pub enum ProgramResult {
     UnknownError(InstructionError),
 }
 
+impl ProgramResult {
+    /// The result of a successful execution.
+    pub fn success() -> Self {
+        ProgramResult::Success
+    }
+
+    /// The result of an execution failing with `error`, classified as either
+    /// a `Failure` or an `UnknownError` exactly as an actual execution's
+    /// result is.
+    pub fn error(error: InstructionError) -> Self {
+        Err(error).into()
+    }
+}
+
 impl From<Result<(), InstructionError>> for ProgramResult {
     fn from(result: Result<(), InstructionError>) -> Self {
         match result {
@@ -33,6 +217,18 @@ impl From<Result<(), InstructionError>> for ProgramResult {
     }
 }
 
+impl From<ProgramResult> for Result<(), InstructionError> {
+    fn from(result: ProgramResult) -> Self {
+        match result {
+            ProgramResult::Success => Ok(()),
+            ProgramResult::Failure(program_error) => {
+                Err(InstructionError::from(u64::from(program_error)))
+            }
+            ProgramResult::UnknownError(err) => Err(err),
+        }
+    }
+}
+
 /// The overall result of the instruction.
 #[derive(Debug, PartialEq, Eq)]
 pub struct InstructionResult {
@@ -40,17 +236,107 @@ pub struct InstructionResult {
     pub compute_units_consumed: u64,
     /// The time taken to execute the instruction.
     pub execution_time: u64,
+    /// A breakdown of the time taken to execute the instruction, in
+    /// microseconds, including serialization, VM creation, and execution.
+    ///
+    /// `per_program_timings` records the invocation count and compute units
+    /// of each program, as in `compute_units_per_program`.
+    pub timings: ExecuteDetailsTimings,
+    /// The log messages emitted during execution, in order.
+    pub logs: Vec<String>,
+    /// The compute units consumed by each program invocation, in the order
+    /// the invocations completed.
+    ///
+    /// Each entry includes the compute units consumed by any programs it
+    /// invoked via CPI, so for a single CPI the callee appears first, then the
+    /// caller with the combined total. Builtin programs, such as the System
+    /// program, don't report their consumption, so their entries are the
+    /// fixed cost each charges when invoked.
+    pub compute_units_per_program: Vec<(Pubkey, u64)>,
+    /// The size of the program heap, in bytes, configured for execution.
+    ///
+    /// The runtime doesn't expose how much of the heap a program actually
+    /// used, but `allocation_failed` reports whether it ran out.
+    pub heap_size: u32,
+    /// Whether a program failed to allocate heap memory, as reported by the
+    /// `memory allocation failed` log message emitted by the default SBF
+    /// allocator.
+    pub allocation_failed: bool,
+    /// Whether execution ran out of compute units, as opposed to failing for
+    /// some other reason.
+    pub compute_budget_exceeded: bool,
+    /// The deepest instruction stack height reached during execution. A
+    /// top-level instruction has a height of 1, and each nested CPI adds 1.
+    pub max_stack_depth: usize,
+    /// The instructions invoked via CPI during execution, in the order they
+    /// were invoked, including nested invocations.
+    pub inner_instructions: Vec<InnerInstruction>,
     /// The result code of the program's execution.
     pub program_result: ProgramResult,
+    /// The unconverted result of the instruction's execution, from which
+    /// `program_result` is derived.
+    pub raw_result: Result<(), InstructionError>,
+    /// The return data set by the program via `sol_set_return_data`, if any.
+    pub return_data: Vec<u8>,
+    /// The prioritization fee, in lamports, a transaction would pay for the
+    /// compute units consumed, at the price requested with a compute budget
+    /// `SetComputeUnitPrice` instruction. Only instruction chains and
+    /// transactions can request a price, so this is otherwise zero.
+    ///
+    /// On a cluster, the fee is charged for the requested compute unit limit,
+    /// so this is the fee for a limit set exactly to the units consumed. No
+    /// lamports are deducted.
+    pub prioritization_fee: u64,
     /// The resulting accounts after executing the instruction.
     ///
-    /// This includes all accounts provided to the processor, in the order
-    /// they were provided. Any accounts that were modified will maintain
-    /// their original position in this list, but with updated state.
+    /// This includes every account referenced by the instruction exactly
+    /// once, in the order of their first appearance in the instruction's
+    /// account metas, even if referenced more than once. Any accounts that
+    /// were modified will maintain their position in this list, but with
+    /// updated state, including the new length of any account the program
+    /// resized.
     pub resulting_accounts: Vec<(Pubkey, AccountSharedData)>,
 }
 
+impl Default for InstructionResult {
+    fn default() -> Self {
+        Self {
+            compute_units_consumed: 0,
+            execution_time: 0,
+            timings: ExecuteDetailsTimings::default(),
+            logs: Vec::new(),
+            compute_units_per_program: Vec::new(),
+            heap_size: 0,
+            allocation_failed: false,
+            compute_budget_exceeded: false,
+            max_stack_depth: 0,
+            inner_instructions: Vec::new(),
+            program_result: ProgramResult::Success,
+            raw_result: Ok(()),
+            return_data: Vec::new(),
+            prioritization_fee: 0,
+            resulting_accounts: Vec::new(),
+        }
+    }
+}
+
 impl InstructionResult {
+    /// Whether the instruction executed successfully.
+    pub fn is_ok(&self) -> bool {
+        self.raw_result.is_ok()
+    }
+
+    /// Whether the instruction failed.
+    pub fn is_err(&self) -> bool {
+        self.raw_result.is_err()
+    }
+
+    /// The exact error the instruction failed with, if any, for matching on
+    /// with plain Rust rather than a `Check`.
+    pub fn instruction_error(&self) -> Option<&InstructionError> {
+        self.raw_result.as_ref().err()
+    }
+
     /// Get an account from the resulting accounts by its pubkey.
     pub fn get_account(&self, pubkey: &Pubkey) -> Option<&AccountSharedData> {
         self.resulting_accounts
@@ -59,95 +345,988 @@ impl InstructionResult {
             .map(|(_, a)| a)
     }
 
-    /// Perform checks on the instruction result, panicking if any checks fail.
-    pub(crate) fn run_checks(&self, checks: &[Check]) {
+    /// Get a mutable reference to an account from the resulting accounts by
+    /// its pubkey, for example to adjust its state before passing the
+    /// resulting accounts to another instruction.
+    pub fn get_account_mut(&mut self, pubkey: &Pubkey) -> Option<&mut AccountSharedData> {
+        self.resulting_accounts
+            .iter_mut()
+            .find(|(k, _)| k == pubkey)
+            .map(|(_, a)| a)
+    }
+
+    /// Index the resulting accounts by pubkey, for constant-time lookups
+    /// where `get_account` would scan the list each time.
+    ///
+    /// The order of the accounts is not preserved; `resulting_accounts`
+    /// remains available for that.
+    pub fn resulting_accounts_map(&self) -> HashMap<Pubkey, &AccountSharedData> {
+        self.resulting_accounts
+            .iter()
+            .map(|(pubkey, account)| (*pubkey, account))
+            .collect()
+    }
+
+    /// Deserialize the events of type `T` an Anchor program emitted with
+    /// `emit!`, in the order they were emitted. Requires the `anchor`
+    /// feature.
+    ///
+    /// Events are read from the `Program data: ` log lines, and matched by
+    /// the discriminator of `T`, an event struct declared with `#[event]`.
+    /// Events emitted with `emit_cpi!` are not logged, so are not included.
+    /// Panics if a matching event fails to deserialize.
+    #[cfg(feature = "anchor")]
+    pub fn anchor_events<T: anchor_lang::Event>(&self) -> Vec<T> {
+        let name = anchor_event_name::<T>();
+        logged_data(&self.logs)
+            .iter()
+            .filter_map(|data| data.strip_prefix(T::DISCRIMINATOR.as_slice()))
+            .map(|data| {
+                T::try_from_slice(data)
+                    .unwrap_or_else(|err| panic!("Failed to deserialize {} event: {}", name, err))
+            })
+            .collect()
+    }
+
+    /// Fold the result of a subsequent instruction into this one, as
+    /// `Mollusk::process_instruction_chain` does.
+    ///
+    /// Compute units, execution time, timings, logs, inner instructions, and
+    /// prioritization fees accumulate. The program result, raw result, return
+    /// data, and heap size are taken from `other`. Each of `other`'s
+    /// resulting accounts replaces the account with the same pubkey, or is
+    /// appended if there is none.
+    pub fn merge(&mut self, other: InstructionResult) {
+        self.compute_units_consumed += other.compute_units_consumed;
+        self.execution_time += other.execution_time;
+        self.timings.accumulate(&other.timings);
+        self.logs.extend(other.logs);
+        self.compute_units_per_program
+            .extend(other.compute_units_per_program);
+        self.heap_size = other.heap_size;
+        self.allocation_failed |= other.allocation_failed;
+        self.compute_budget_exceeded |= other.compute_budget_exceeded;
+        self.max_stack_depth = self.max_stack_depth.max(other.max_stack_depth);
+        self.inner_instructions.extend(other.inner_instructions);
+        self.program_result = other.program_result;
+        self.raw_result = other.raw_result;
+        self.return_data = other.return_data;
+        self.prioritization_fee += other.prioritization_fee;
+        for (pubkey, account) in other.resulting_accounts {
+            let mut found = false;
+            for (key, existing) in self.resulting_accounts.iter_mut() {
+                if *key == pubkey {
+                    *existing = account.clone();
+                    found = true;
+                }
+            }
+            if !found {
+                self.resulting_accounts.push((pubkey, account));
+            }
+        }
+    }
+
+    /// Render a human-readable summary of the result: the compute units
+    /// consumed, program result, return data, logs, and each resulting
+    /// account's lamports, owner, and data length.
+    ///
+    /// This summary is included in the panic message when a check fails.
+    pub fn dump(&self) -> String {
+        let mut dump = format!(
+            "Compute units consumed: {}\nProgram result: {:?}\nReturn data: {:?}\nLogs:\n",
+            self.compute_units_consumed, self.program_result, self.return_data,
+        );
+        for log in self.logs.iter() {
+            dump.push_str(&format!("  {}\n", log));
+        }
+        dump.push_str("Resulting accounts:\n");
+        for (pubkey, account) in self.resulting_accounts.iter() {
+            dump.push_str(&format!(
+                "  {}: lamports {}, owner {}, data length {}\n",
+                pubkey,
+                account.lamports(),
+                account.owner(),
+                account.data().len(),
+            ));
+        }
+        dump
+    }
+
+    /// Compare the resulting accounts against the accounts provided to the
+    /// instruction, returning a diff for every account that changed.
+    ///
+    /// Accounts are matched by pubkey, and diffs are returned in the order of
+    /// the resulting accounts.
+    pub fn account_diffs(&self, inputs: &[(Pubkey, AccountSharedData)]) -> Vec<AccountDiff> {
+        self.resulting_accounts
+            .iter()
+            .filter_map(|(pubkey, resulting)| {
+                let input = get_account(inputs, pubkey)?;
+                AccountDiff::new(pubkey, input, resulting)
+            })
+            .collect()
+    }
+
+    /// Render the resulting accounts as a deterministic, human-readable
+    /// snapshot, suitable for golden-file testing.
+    ///
+    /// Accounts are sorted by pubkey, with their data base64-encoded. See
+    /// `Check::matches_snapshot`.
+    pub fn snapshot(&self) -> String {
+        render_snapshot(self.resulting_accounts.iter())
+    }
+
+    // Like `snapshot`, but omitting accounts identical to their input.
+    fn snapshot_changed(&self, inputs: &[(Pubkey, AccountSharedData)]) -> String {
+        render_snapshot(
+            self.resulting_accounts
+                .iter()
+                .filter(|(pubkey, account)| get_account(inputs, pubkey) != Some(account)),
+        )
+    }
+
+    /// Perform checks on the instruction result, returning all failed checks.
+    ///
+    /// `accounts` are the accounts provided to the instruction, used by checks
+    /// comparing the resulting accounts against their original state. `rent`
+    /// is the environment's `Rent` sysvar, used by rent-exemption checks.
+    pub(crate) fn run_checks(
+        &self,
+        checks: &[Check],
+        accounts: &[(Pubkey, AccountSharedData)],
+        rent: &Rent,
+    ) -> Result<(), Vec<CheckFailure>> {
+        let mut failures = Vec::new();
         for check in checks {
             match &check.check {
                 CheckType::ComputeUnitsConsumed(units) => {
                     let check_units = *units;
                     let actual_units = self.compute_units_consumed;
-                    assert_eq!(
-                        check_units, actual_units,
-                        "Checking compute units consumed: expected {}, got {}",
-                        check_units, actual_units
-                    );
+                    if check_units != actual_units {
+                        failures.push(CheckFailure::new(
+                            "compute units consumed",
+                            check_units.to_string(),
+                            actual_units.to_string(),
+                        ));
+                    }
+                }
+                CheckType::ComputeUnitsLessThan(units) => {
+                    let check_units = *units;
+                    let actual_units = self.compute_units_consumed;
+                    if actual_units >= check_units {
+                        failures.push(CheckFailure::new(
+                            "compute units consumed",
+                            format!("less than {}", check_units),
+                            actual_units.to_string(),
+                        ));
+                    }
+                }
+                CheckType::ComputeUnitsAtMost(units) => {
+                    let check_units = *units;
+                    let actual_units = self.compute_units_consumed;
+                    if actual_units > check_units {
+                        failures.push(CheckFailure::new(
+                            "compute units consumed",
+                            format!("at most {}", check_units),
+                            actual_units.to_string(),
+                        ));
+                    }
+                }
+                CheckType::ComputeUnitsBetween(min, max) => {
+                    let (check_min, check_max) = (*min, *max);
+                    let actual_units = self.compute_units_consumed;
+                    if actual_units < check_min || actual_units > check_max {
+                        failures.push(CheckFailure::new(
+                            "compute units consumed",
+                            format!("between {} and {}", check_min, check_max),
+                            actual_units.to_string(),
+                        ));
+                    }
+                }
+                CheckType::ComputeUnitsBaseline {
+                    path,
+                    tolerance_pct,
+                } => {
+                    let label = format!("compute units baseline {}", path.display());
+                    let actual_units = self.compute_units_consumed;
+                    let update = std::env::var(UPDATE_BASELINE_VAR).is_ok_and(|value| value == "1");
+                    let contents = match read_or_write_expected(
+                        path,
+                        &format!("{}\n", actual_units),
+                        update,
+                        &label,
+                    ) {
+                        Ok(Some(contents)) => contents,
+                        Ok(None) => continue,
+                        Err(failure) => {
+                            failures.push(failure);
+                            continue;
+                        }
+                    };
+                    let baseline: u64 = match contents.trim().parse() {
+                        Ok(baseline) => baseline,
+                        Err(err) => {
+                            failures.push(CheckFailure::new(
+                                &label,
+                                "a compute unit count".to_string(),
+                                format!(
+                                    "Invalid compute units baseline {}: {}",
+                                    path.display(),
+                                    err
+                                ),
+                            ));
+                            continue;
+                        }
+                    };
+                    let tolerance = baseline as f64 * tolerance_pct / 100.0;
+                    if actual_units.abs_diff(baseline) as f64 > tolerance {
+                        failures.push(CheckFailure::new(
+                            &label,
+                            format!("{} (within {}%)", baseline, tolerance_pct),
+                            actual_units.to_string(),
+                        ));
+                    }
+                }
+                CheckType::ComputeBudgetExceeded => {
+                    if !self.compute_budget_exceeded {
+                        failures.push(CheckFailure::new(
+                            "compute budget exceeded",
+                            "true".to_string(),
+                            "false".to_string(),
+                        ));
+                    }
                 }
                 CheckType::ExecutionTime(time) => {
                     let check_time = *time;
                     let actual_time = self.execution_time;
-                    assert_eq!(
-                        check_time, actual_time,
-                        "Checking execution time: expected {}, got {}",
-                        check_time, actual_time
-                    );
+                    if check_time != actual_time {
+                        failures.push(CheckFailure::new(
+                            "execution time",
+                            check_time.to_string(),
+                            actual_time.to_string(),
+                        ));
+                    }
+                }
+                CheckType::Log(line) => {
+                    let check_line = *line;
+                    if !self.logs.iter().any(|log| log == check_line) {
+                        failures.push(CheckFailure::new(
+                            "log",
+                            format!("{:?}", check_line),
+                            format!("{:?}", self.logs),
+                        ));
+                    }
+                }
+                CheckType::Logs {
+                    lines,
+                    include_framing,
+                } => {
+                    let actual_lines = self
+                        .logs
+                        .iter()
+                        .filter(|log| *include_framing || !is_framing_log(log))
+                        .map(String::as_str)
+                        .collect::<Vec<_>>();
+                    if actual_lines != *lines {
+                        failures.push(CheckFailure::new(
+                            "logs",
+                            format!("{:?}", lines),
+                            format!("{:?}", actual_lines),
+                        ));
+                    }
+                }
+                CheckType::LogContains(substring) => {
+                    let check_substring = *substring;
+                    if !self.logs.iter().any(|log| log.contains(check_substring)) {
+                        failures.push(CheckFailure::new(
+                            "log contains",
+                            format!("{:?}", check_substring),
+                            format!("{:?}", self.logs),
+                        ));
+                    }
+                }
+                CheckType::LogNotContaining(substring) => {
+                    let check_substring = *substring;
+                    let matching = self
+                        .logs
+                        .iter()
+                        .filter(|log| log.contains(check_substring))
+                        .collect::<Vec<_>>();
+                    if !matching.is_empty() {
+                        failures.push(CheckFailure::new(
+                            "log not containing",
+                            format!("no log containing {:?}", check_substring),
+                            format!("{:?}", matching),
+                        ));
+                    }
                 }
                 CheckType::ProgramResult(result) => {
                     let check_result = result;
                     let actual_result = &self.program_result;
-                    assert_eq!(
-                        check_result, actual_result,
-                        "Checking program result: expected {:?}, got {:?}",
-                        check_result, actual_result
-                    );
+                    if check_result != actual_result {
+                        failures.push(CheckFailure::new(
+                            "program result",
+                            format!("{:?}", check_result),
+                            format!("{:?}", actual_result),
+                        ));
+                    }
                 }
-                CheckType::ResultingAccount(account) => {
-                    let pubkey = account.pubkey;
-                    let resulting_account = self
+                CheckType::ReturnData(return_data) => {
+                    let check_return_data = *return_data;
+                    let actual_return_data = self.return_data.as_slice();
+                    if check_return_data != actual_return_data {
+                        failures.push(CheckFailure::new(
+                            "return data",
+                            format!("{:?}", check_return_data),
+                            format!("{:?}", actual_return_data),
+                        ));
+                    }
+                }
+                CheckType::ReturnDataLen(len) => {
+                    let check_len = *len;
+                    let actual_len = self.return_data.len();
+                    if check_len != actual_len {
+                        failures.push(CheckFailure::new(
+                            "return data length",
+                            check_len.to_string(),
+                            actual_len.to_string(),
+                        ));
+                    }
+                }
+                CheckType::CpiTo(program_id) => {
+                    if !self
+                        .inner_instructions
+                        .iter()
+                        .any(|inner| inner.instruction.program_id == *program_id)
+                    {
+                        failures.push(CheckFailure::new(
+                            &format!("cpi to {}", program_id),
+                            "invoked".to_string(),
+                            "not invoked".to_string(),
+                        ));
+                    }
+                }
+                CheckType::CpiCount(count) => {
+                    let check_count = *count;
+                    let actual_count = self.inner_instructions.len();
+                    if check_count != actual_count {
+                        failures.push(CheckFailure::new(
+                            "cpi count",
+                            check_count.to_string(),
+                            actual_count.to_string(),
+                        ));
+                    }
+                }
+                CheckType::BuiltinComputeUnits(program_id, units) => {
+                    let check_units = *units;
+                    let actual_units = self
+                        .compute_units_per_program
+                        .iter()
+                        .filter(|(key, _)| key == program_id)
+                        .map(|(_, units)| *units)
+                        .sum::<u64>();
+                    if check_units != actual_units {
+                        failures.push(CheckFailure::new(
+                            &format!("builtin compute units {}", program_id),
+                            check_units.to_string(),
+                            actual_units.to_string(),
+                        ));
+                    }
+                }
+                #[cfg(feature = "anchor")]
+                CheckType::AnchorEvent { name, data } => {
+                    let discriminator = &data[..8];
+                    let emitted = logged_data(&self.logs)
+                        .into_iter()
+                        .filter(|emitted| emitted.starts_with(discriminator))
+                        .collect::<Vec<_>>();
+                    if !emitted.contains(data) {
+                        failures.push(CheckFailure::new(
+                            &format!("anchor event {}", name),
+                            BASE64_STANDARD.encode(data),
+                            format!(
+                                "{:?}",
+                                emitted
+                                    .iter()
+                                    .map(|data| BASE64_STANDARD.encode(data))
+                                    .collect::<Vec<_>>()
+                            ),
+                        ));
+                    }
+                }
+                CheckType::LamportsDelta(pubkey, delta) => {
+                    let check_delta = *delta;
+                    let Some(input_account) = get_account(accounts, pubkey) else {
+                        failures.push(CheckFailure::new(
+                            &format!("account {} in input accounts", pubkey),
+                            "present".to_string(),
+                            "absent".to_string(),
+                        ));
+                        continue;
+                    };
+                    let Some(resulting_account) = self.get_account(pubkey) else {
+                        failures.push(CheckFailure::new(
+                            &format!("account {} in resulting accounts", pubkey),
+                            "present".to_string(),
+                            "absent".to_string(),
+                        ));
+                        continue;
+                    };
+                    let actual_delta =
+                        resulting_account.lamports() as i128 - input_account.lamports() as i128;
+                    if check_delta != actual_delta {
+                        failures.push(CheckFailure::new(
+                            &format!("account {} lamports delta", pubkey),
+                            check_delta.to_string(),
+                            actual_delta.to_string(),
+                        ));
+                    }
+                }
+                CheckType::TotalLamportsConserved => {
+                    // Only accounts referenced by the instruction are counted,
+                    // each once.
+                    let input_total = self
                         .resulting_accounts
                         .iter()
-                        .find(|(k, _)| k == &pubkey)
-                        .map(|(_, a)| a)
-                        .unwrap_or_else(|| {
-                            panic!("Account not found in resulting accounts: {}", pubkey)
-                        });
+                        .filter_map(|(pubkey, _)| get_account(accounts, pubkey))
+                        .map(|account| account.lamports() as u128)
+                        .sum::<u128>();
+                    let resulting_total = self
+                        .resulting_accounts
+                        .iter()
+                        .map(|(_, account)| account.lamports() as u128)
+                        .sum::<u128>();
+                    if input_total != resulting_total {
+                        failures.push(CheckFailure::new(
+                            "total lamports",
+                            input_total.to_string(),
+                            resulting_total.to_string(),
+                        ));
+                    }
+                }
+                CheckType::OnlyAccountsChanged(allowed) => {
+                    for (pubkey, resulting_account) in self.resulting_accounts.iter() {
+                        if allowed.contains(pubkey) {
+                            continue;
+                        }
+                        let Some(input_account) = get_account(accounts, pubkey) else {
+                            continue;
+                        };
+                        let mut changed = Vec::new();
+                        if input_account.lamports() != resulting_account.lamports() {
+                            changed.push("lamports");
+                        }
+                        if input_account.data() != resulting_account.data() {
+                            changed.push("data");
+                        }
+                        if input_account.owner() != resulting_account.owner() {
+                            changed.push("owner");
+                        }
+                        if !changed.is_empty() {
+                            failures.push(CheckFailure::new(
+                                &format!("account {} unchanged", pubkey),
+                                "unchanged".to_string(),
+                                format!("changed {}", changed.join(", ")),
+                            ));
+                        }
+                    }
+                }
+                CheckType::AccountEquals(pubkey, expected) => {
+                    let Some(resulting_account) = self.get_account(pubkey) else {
+                        failures.push(CheckFailure::new(
+                            &format!("account {} in resulting accounts", pubkey),
+                            "present".to_string(),
+                            "absent".to_string(),
+                        ));
+                        continue;
+                    };
+                    if expected.lamports() != resulting_account.lamports() {
+                        failures.push(CheckFailure::new(
+                            &format!("account {} lamports", pubkey),
+                            expected.lamports().to_string(),
+                            resulting_account.lamports().to_string(),
+                        ));
+                    }
+                    if expected.owner() != resulting_account.owner() {
+                        failures.push(CheckFailure::new(
+                            &format!("account {} owner", pubkey),
+                            expected.owner().to_string(),
+                            resulting_account.owner().to_string(),
+                        ));
+                    }
+                    if expected.executable() != resulting_account.executable() {
+                        failures.push(CheckFailure::new(
+                            &format!("account {} executable", pubkey),
+                            expected.executable().to_string(),
+                            resulting_account.executable().to_string(),
+                        ));
+                    }
+                    if expected.data() != resulting_account.data() {
+                        failures.push(CheckFailure::new(
+                            &format!("account {} data", pubkey),
+                            format!("{:?}", expected.data()),
+                            format!("{:?}", resulting_account.data()),
+                        ));
+                    }
+                }
+                CheckType::RentExempt {
+                    pubkey,
+                    allow_closed,
+                } => {
+                    let Some(resulting_account) = self.get_account(pubkey) else {
+                        failures.push(CheckFailure::new(
+                            &format!("account {} in resulting accounts", pubkey),
+                            "present".to_string(),
+                            "absent".to_string(),
+                        ));
+                        continue;
+                    };
+                    let lamports = resulting_account.lamports();
+                    if lamports == 0 && *allow_closed {
+                        continue;
+                    }
+                    let minimum_balance = rent.minimum_balance(resulting_account.data().len());
+                    if lamports < minimum_balance {
+                        failures.push(CheckFailure::new(
+                            &format!("account {} rent exempt", pubkey),
+                            format!("at least {} lamports", minimum_balance),
+                            lamports.to_string(),
+                        ));
+                    }
+                }
+                CheckType::AccountExists { pubkey, exists } => {
+                    let resulting_account = self.get_account(pubkey);
+                    if *exists == resulting_account.is_some_and(account_exists) {
+                        continue;
+                    }
+                    let (expected, actual) = match resulting_account {
+                        Some(account) if *exists => (
+                            "exists",
+                            format!(
+                                "absent ({} lamports, {} bytes of data)",
+                                account.lamports(),
+                                account.data().len()
+                            ),
+                        ),
+                        Some(_) => ("absent", "exists".to_string()),
+                        None => ("exists", "absent (not in resulting accounts)".to_string()),
+                    };
+                    failures.push(CheckFailure::new(
+                        &format!("account {} existence", pubkey),
+                        expected.to_string(),
+                        actual,
+                    ));
+                }
+                CheckType::AccountDataZeroed { pubkey, zeroed } => {
+                    let Some(resulting_account) = self.get_account(pubkey) else {
+                        failures.push(CheckFailure::new(
+                            &format!("account {} in resulting accounts", pubkey),
+                            "present".to_string(),
+                            "absent".to_string(),
+                        ));
+                        continue;
+                    };
+                    let first_non_zero = resulting_account.data().iter().position(|b| *b != 0);
+                    match (*zeroed, first_non_zero) {
+                        (true, Some(offset)) => failures.push(CheckFailure::new(
+                            &format!("account {} data all zero", pubkey),
+                            "all zero".to_string(),
+                            format!("non-zero byte at offset {}", offset),
+                        )),
+                        (false, None) => failures.push(CheckFailure::new(
+                            &format!("account {} data not all zero", pubkey),
+                            "a non-zero byte".to_string(),
+                            "all zero".to_string(),
+                        )),
+                        _ => {}
+                    }
+                }
+                CheckType::Snapshot { path, changed_only } => {
+                    let actual = if *changed_only {
+                        self.snapshot_changed(accounts)
+                    } else {
+                        self.snapshot()
+                    };
+                    let label = format!("snapshot {}", path.display());
+                    let expected = match read_or_write_expected(path, &actual, false, &label) {
+                        Ok(Some(expected)) => expected,
+                        Ok(None) => continue,
+                        Err(failure) => {
+                            failures.push(failure);
+                            continue;
+                        }
+                    };
+                    if expected != actual {
+                        failures.push(CheckFailure::new(&label, expected, actual));
+                    }
+                }
+                CheckType::ResultingAccount(account) => {
+                    let pubkey = account.pubkey;
+                    let Some(resulting_account) = self.get_account(&pubkey) else {
+                        failures.push(CheckFailure::new(
+                            &format!("account {} in resulting accounts", pubkey),
+                            "present".to_string(),
+                            "absent".to_string(),
+                        ));
+                        continue;
+                    };
                     if let Some(check_data) = account.check_data {
                         let actual_data = resulting_account.data();
-                        assert_eq!(
-                            check_data, actual_data,
-                            "Checking account data: expected {:?}, got {:?}",
-                            check_data, actual_data
-                        );
+                        if check_data != actual_data {
+                            failures.push(CheckFailure::new(
+                                "account data",
+                                format!("{:?}", check_data),
+                                format!("{:?}", actual_data),
+                            ));
+                        }
+                    }
+                    if let Some((offset, check_slice)) = account.check_data_slice {
+                        let actual_data = resulting_account.data();
+                        match actual_data.get(offset..offset.saturating_add(check_slice.len())) {
+                            Some(actual_slice) => {
+                                if check_slice != actual_slice {
+                                    failures.push(CheckFailure::new(
+                                        &format!("account data at offset {}", offset),
+                                        format!("{:?}", check_slice),
+                                        format!("{:?}", actual_slice),
+                                    ));
+                                }
+                            }
+                            None => {
+                                failures.push(CheckFailure::new(
+                                    &format!("account data at offset {}", offset),
+                                    format!("{} bytes", check_slice.len()),
+                                    format!("out of bounds for data length {}", actual_data.len()),
+                                ));
+                            }
+                        }
+                    }
+                    if let Some(check_executable) = account.check_executable {
+                        let actual_executable = resulting_account.executable();
+                        if check_executable != actual_executable {
+                            failures.push(CheckFailure::new(
+                                "account executable",
+                                check_executable.to_string(),
+                                actual_executable.to_string(),
+                            ));
+                        }
                     }
                     if let Some(check_lamports) = account.check_lamports {
                         let actual_lamports = resulting_account.lamports();
-                        assert_eq!(
-                            check_lamports, actual_lamports,
-                            "Checking account lamports: expected {}, got {}",
-                            check_lamports, actual_lamports
-                        );
+                        if check_lamports != actual_lamports {
+                            failures.push(CheckFailure::new(
+                                "account lamports",
+                                check_lamports.to_string(),
+                                actual_lamports.to_string(),
+                            ));
+                        }
                     }
                     if let Some(check_owner) = account.check_owner {
                         let actual_owner = resulting_account.owner();
-                        assert_eq!(
-                            check_owner, actual_owner,
-                            "Checking account owner: expected {}, got {}",
-                            check_owner, actual_owner
-                        );
+                        if check_owner != actual_owner {
+                            failures.push(CheckFailure::new(
+                                "account owner",
+                                check_owner.to_string(),
+                                actual_owner.to_string(),
+                            ));
+                        }
+                    }
+                    if let Some(check_space) = account.check_space {
+                        let actual_space = resulting_account.data().len();
+                        if check_space != actual_space {
+                            failures.push(CheckFailure::new(
+                                "account space",
+                                check_space.to_string(),
+                                actual_space.to_string(),
+                            ));
+                        }
                     }
                     if let Some(check_state) = &account.check_state {
                         match check_state {
                             AccountStateCheck::Closed => {
-                                assert_eq!(
-                                    &AccountSharedData::default(),
-                                    resulting_account,
-                                    "Checking account closed: expected true, got false"
-                                );
+                                if &AccountSharedData::default() != resulting_account {
+                                    failures.push(CheckFailure::new(
+                                        "account closed",
+                                        "true".to_string(),
+                                        "false".to_string(),
+                                    ));
+                                }
+                            }
+                            AccountStateCheck::ClosedAnyOwner => {
+                                if resulting_account.lamports() != 0
+                                    || resulting_account.data().iter().any(|byte| *byte != 0)
+                                {
+                                    failures.push(CheckFailure::new(
+                                        "account closed",
+                                        "true".to_string(),
+                                        "false".to_string(),
+                                    ));
+                                }
                             }
                         }
                     }
                 }
             }
         }
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(failures)
+        }
+    }
+}
+
+/// The changes made to an account by an instruction.
+#[derive(Debug, PartialEq, Eq)]
+pub struct AccountDiff {
+    /// The account's address.
+    pub pubkey: Pubkey,
+    /// The change in the account's lamports.
+    pub lamports_delta: i128,
+    /// The account's lamports before and after, if they changed.
+    pub lamports: Option<(u64, u64)>,
+    /// The account's owner before and after, if it changed.
+    pub owner: Option<(Pubkey, Pubkey)>,
+    /// The account's data length before and after, if it changed.
+    pub data_len: Option<(usize, usize)>,
+    /// The byte ranges of the account's data that changed, within the length
+    /// common to both the original and resulting data.
+    pub data_changes: Vec<Range<usize>>,
+    original_data: Vec<u8>,
+    resulting_data: Vec<u8>,
+}
+
+impl AccountDiff {
+    fn new(
+        pubkey: &Pubkey,
+        original: &AccountSharedData,
+        resulting: &AccountSharedData,
+    ) -> Option<Self> {
+        let lamports = (original.lamports() != resulting.lamports())
+            .then_some((original.lamports(), resulting.lamports()));
+        let owner = (original.owner() != resulting.owner())
+            .then_some((*original.owner(), *resulting.owner()));
+        let data_len = (original.data().len() != resulting.data().len())
+            .then_some((original.data().len(), resulting.data().len()));
+
+        let mut data_changes: Vec<Range<usize>> = Vec::new();
+        for (i, (a, b)) in original.data().iter().zip(resulting.data()).enumerate() {
+            if a == b {
+                continue;
+            }
+            match data_changes.last_mut() {
+                Some(range) if range.end == i => range.end = i + 1,
+                _ => data_changes.push(i..i + 1),
+            }
+        }
+
+        if lamports.is_none() && owner.is_none() && data_len.is_none() && data_changes.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            pubkey: *pubkey,
+            lamports_delta: resulting.lamports() as i128 - original.lamports() as i128,
+            lamports,
+            owner,
+            data_len,
+            data_changes,
+            original_data: original.data().to_vec(),
+            resulting_data: resulting.data().to_vec(),
+        })
+    }
+}
+
+impl fmt::Display for AccountDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Account {}:", self.pubkey)?;
+        if let Some((before, after)) = self.lamports {
+            writeln!(
+                f,
+                "  lamports: {} -> {} ({:+})",
+                before, after, self.lamports_delta
+            )?;
+        }
+        if let Some((before, after)) = self.owner {
+            writeln!(f, "  owner: {} -> {}", before, after)?;
+        }
+        if let Some((before, after)) = self.data_len {
+            writeln!(f, "  data length: {} -> {}", before, after)?;
+        }
+        for range in self.data_changes.iter() {
+            writeln!(f, "  data [{}..{}]:", range.start, range.end)?;
+            writeln!(f, "  - {:?}", &self.original_data[range.clone()])?;
+            writeln!(f, "  + {:?}", &self.resulting_data[range.clone()])?;
+        }
+        Ok(())
+    }
+}
+
+fn render_snapshot<'a>(accounts: impl Iterator<Item = &'a (Pubkey, AccountSharedData)>) -> String {
+    let accounts = accounts
+        .map(|(pubkey, account)| (pubkey, account))
+        .collect::<BTreeMap<_, _>>();
+    let mut snapshot = String::new();
+    for (pubkey, account) in accounts {
+        snapshot.push_str(&format!(
+            "{}:\n  lamports: {}\n  owner: {}\n  executable: {}\n  data: {}\n",
+            pubkey,
+            account.lamports(),
+            account.owner(),
+            account.executable(),
+            BASE64_STANDARD.encode(account.data()),
+        ));
+    }
+    snapshot
+}
+
+// Read the expected value of a file-backed check from `path`. If the file
+// doesn't exist yet, or `update` is set, the actual value is written to it
+// instead and `None` is returned. I/O errors are reported as a failure of the
+// check labeled `check`.
+fn read_or_write_expected(
+    path: &Path,
+    actual: &str,
+    update: bool,
+    check: &str,
+) -> Result<Option<String>, CheckFailure> {
+    if update || !path.exists() {
+        let write = || -> std::io::Result<()> {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(path, actual)
+        };
+        return write().map(|()| None).map_err(|err| {
+            CheckFailure::new(
+                check,
+                "a writable file".to_string(),
+                format!("Failed to write {}: {}", path.display(), err),
+            )
+        });
+    }
+    std::fs::read_to_string(path).map(Some).map_err(|err| {
+        CheckFailure::new(
+            check,
+            "a readable file".to_string(),
+            format!("Failed to read {}: {}", path.display(), err),
+        )
+    })
+}
+
+fn get_account<'a>(
+    accounts: &'a [(Pubkey, AccountSharedData)],
+    pubkey: &Pubkey,
+) -> Option<&'a AccountSharedData> {
+    accounts.iter().find(|(k, _)| k == pubkey).map(|(_, a)| a)
+}
+
+/// A check that failed, along with the expected and actual values.
+#[derive(Debug, PartialEq, Eq)]
+pub struct CheckFailure {
+    /// A description of what was checked.
+    pub check: String,
+    /// The expected value.
+    pub expected: String,
+    /// The actual value.
+    pub actual: String,
+}
+
+impl CheckFailure {
+    fn new(check: &str, expected: String, actual: String) -> Self {
+        Self {
+            check: check.to_string(),
+            expected,
+            actual,
+        }
     }
 }
 
+impl fmt::Display for CheckFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Checking {}: expected {}, got {}",
+            self.check, self.expected, self.actual
+        )
+    }
+}
+
+impl std::error::Error for CheckFailure {}
+
+/// Panic with a message listing every failed check, followed by a summary
+/// of the result the checks were run against.
+pub(crate) fn panic_with_check_failures(
+    failures: &[CheckFailure],
+    result: &InstructionResult,
+) -> ! {
+    let messages = failures.iter().map(ToString::to_string).collect::<Vec<_>>();
+    panic!("{}\n\n{}", messages.join("\n"), result.dump())
+}
+
 enum CheckType<'a> {
     /// Check the number of compute units consumed by the instruction.
     ComputeUnitsConsumed(u64),
+    /// Check the number of compute units consumed is less than a bound.
+    ComputeUnitsLessThan(u64),
+    /// Check the number of compute units consumed is at most a bound.
+    ComputeUnitsAtMost(u64),
+    /// Check the number of compute units consumed is within an inclusive
+    /// range.
+    ComputeUnitsBetween(u64, u64),
+    /// Check the number of compute units consumed is within a percentage
+    /// tolerance of a baseline stored in a file.
+    ComputeUnitsBaseline { path: &'a Path, tolerance_pct: f64 },
+    /// Check that execution ran out of compute units.
+    ComputeBudgetExceeded,
     /// Check the time taken to execute the instruction.
     ExecutionTime(u64),
+    /// Check that a log line matching exactly was emitted.
+    Log(&'a str),
+    /// Check the exact sequence of log lines emitted, optionally including
+    /// the runtime's framing of each invocation.
+    Logs {
+        lines: &'a [&'a str],
+        include_framing: bool,
+    },
+    /// Check that a log line containing a substring was emitted.
+    LogContains(&'a str),
+    /// Check that no log line containing a substring was emitted.
+    LogNotContaining(&'a str),
     /// Check the result code of the program's execution.
     ProgramResult(ProgramResult),
+    /// Check the return data set by the program.
+    ReturnData(&'a [u8]),
+    /// Check the length of the return data set by the program.
+    ReturnDataLen(usize),
+    /// Check that a program was invoked via CPI.
+    CpiTo(Pubkey),
+    /// Check the number of instructions invoked via CPI.
+    CpiCount(usize),
+    /// Check the total compute units charged by a builtin program.
+    BuiltinComputeUnits(Pubkey, u64),
+    /// Check that an Anchor program emitted an event, given the event's name
+    /// and its data, including the discriminator.
+    #[cfg(feature = "anchor")]
+    AnchorEvent { name: &'static str, data: Vec<u8> },
+    /// Check the change in an account's lamports.
+    LamportsDelta(Pubkey, i128),
+    /// Check the total lamports across all accounts is unchanged.
+    TotalLamportsConserved,
+    /// Check that no accounts outside an allow-list were modified.
+    OnlyAccountsChanged(&'a [Pubkey]),
+    /// Check that a resulting account is identical to an expected account.
+    AccountEquals(Pubkey, AccountSharedData),
+    /// Check that a resulting account is rent exempt, optionally treating a
+    /// closed account as exempt.
+    RentExempt { pubkey: Pubkey, allow_closed: bool },
+    /// Check that a resulting account exists, or is absent.
+    AccountExists { pubkey: Pubkey, exists: bool },
+    /// Check that a resulting account's data is, or is not, entirely zero.
+    AccountDataZeroed { pubkey: Pubkey, zeroed: bool },
+    /// Check the resulting accounts against a snapshot file, optionally
+    /// omitting accounts unchanged by the instruction.
+    Snapshot { path: &'a Path, changed_only: bool },
     /// Check a resulting account after executing the instruction.
     ResultingAccount(AccountCheck<'a>),
 }
@@ -166,11 +1345,94 @@ impl<'a> Check<'a> {
         Check::new(CheckType::ComputeUnitsConsumed(units))
     }
 
+    /// Check the number of compute units consumed by the instruction is
+    /// strictly less than the provided bound.
+    pub fn compute_units_less_than(units: u64) -> Self {
+        Check::new(CheckType::ComputeUnitsLessThan(units))
+    }
+
+    /// Check the number of compute units consumed by the instruction is less
+    /// than or equal to the provided bound.
+    pub fn compute_units_at_most(units: u64) -> Self {
+        Check::new(CheckType::ComputeUnitsAtMost(units))
+    }
+
+    /// Check the number of compute units consumed by the instruction is
+    /// between `min` and `max`, inclusive.
+    pub fn compute_units_between(min: u64, max: u64) -> Self {
+        Check::new(CheckType::ComputeUnitsBetween(min, max))
+    }
+
+    /// Check the number of compute units consumed by the instruction against
+    /// a baseline stored at `path`, allowing it to differ by up to
+    /// `tolerance_pct` percent, ie. `5.0` for 5%.
+    ///
+    /// If the file does not exist, or the `MOLLUSK_UPDATE_BASELINE`
+    /// environment variable is set to `1`, the compute units consumed are
+    /// written to it as the new baseline and the check passes.
+    pub fn compute_units_baseline(path: &'a Path, tolerance_pct: f64) -> Self {
+        Check::new(CheckType::ComputeUnitsBaseline {
+            path,
+            tolerance_pct,
+        })
+    }
+
+    /// Check that execution ran out of compute units, rather than failing
+    /// for some other reason. See `InstructionResult::compute_budget_exceeded`.
+    pub fn compute_budget_exceeded() -> Self {
+        Check::new(CheckType::ComputeBudgetExceeded)
+    }
+
     /// Check the time taken to execute the instruction.
     pub fn time(time: u64) -> Self {
         Check::new(CheckType::ExecutionTime(time))
     }
 
+    /// Check that a log line matching exactly was emitted.
+    pub fn log(line: &'a str) -> Self {
+        Check::new(CheckType::Log(line))
+    }
+
+    /// Check that exactly `lines` were logged, in order, and nothing else.
+    ///
+    /// The lines the runtime emits around each program invocation, eg.
+    /// `Program <id> invoke [1]`, `Program <id> consumed ...`,
+    /// `Program <id> success`, `Program <id> failed: ...`, and
+    /// `Program return: ...`, are ignored. Lines logged by programs are
+    /// compared as emitted, including any `Program log: ` prefix. To include
+    /// the framing, see `logs_with_framing`.
+    pub fn logs(lines: &'a [&'a str]) -> Self {
+        Check::new(CheckType::Logs {
+            lines,
+            include_framing: false,
+        })
+    }
+
+    /// Like `logs`, but comparing every log line, including the runtime's
+    /// framing of each program invocation.
+    pub fn logs_with_framing(lines: &'a [&'a str]) -> Self {
+        Check::new(CheckType::Logs {
+            lines,
+            include_framing: true,
+        })
+    }
+
+    /// Check that a log line containing the provided substring was emitted.
+    pub fn log_contains(substring: &'a str) -> Self {
+        Check::new(CheckType::LogContains(substring))
+    }
+
+    /// Check that no log line containing the provided substring was emitted,
+    /// for example to verify an error-logging branch wasn't taken.
+    pub fn log_not_containing(substring: &'a str) -> Self {
+        Check::new(CheckType::LogNotContaining(substring))
+    }
+
+    /// Assert that the program's result matches exactly.
+    pub(crate) fn program_result(result: ProgramResult) -> Self {
+        Check::new(CheckType::ProgramResult(result))
+    }
+
     /// Assert that the program executed successfully.
     pub fn success() -> Self {
         Check::new(CheckType::ProgramResult(ProgramResult::Success))
@@ -181,26 +1443,249 @@ impl<'a> Check<'a> {
         Check::new(CheckType::ProgramResult(ProgramResult::Failure(error)))
     }
 
+    /// Assert that the program returned a custom error code, ie.
+    /// `ProgramError::Custom(code)`.
+    ///
+    /// Programs using `thiserror`-style error enums typically map each
+    /// variant to a `u32` code this way.
+    pub fn custom_err(code: u32) -> Self {
+        Check::err(ProgramError::Custom(code))
+    }
+
+    /// Assert that an Anchor program returned one of its custom errors.
+    ///
+    /// Anchor's `#[error_code]` enums implement `Into<u32>`, producing the
+    /// discriminant offset by `ANCHOR_ERROR_CODE_OFFSET`, which is the code
+    /// the program returns. To check against a raw discriminant instead, use
+    /// `Check::custom_err(anchor_error_code(discriminant))`.
+    pub fn anchor_err<E: Into<u32>>(error: E) -> Self {
+        Check::custom_err(error.into())
+    }
+
+    /// Check that an Anchor program emitted `event` with `emit!`, as read by
+    /// `InstructionResult::anchor_events`. Requires the `anchor` feature.
+    ///
+    /// Events are compared by their serialized data, so every field must
+    /// match.
+    #[cfg(feature = "anchor")]
+    pub fn emitted_event<T: anchor_lang::Event>(event: &T) -> Self {
+        Check::new(CheckType::AnchorEvent {
+            name: anchor_event_name::<T>(),
+            data: event.data(),
+        })
+    }
+
     /// Assert that the instruction returned an error.
     pub fn instruction_err(error: InstructionError) -> Self {
         Check::new(CheckType::ProgramResult(ProgramResult::UnknownError(error)))
     }
 
+    /// Check that the program invoked `program_id` via CPI at least once,
+    /// at any depth.
+    pub fn cpi_to(program_id: &Pubkey) -> Self {
+        Check::new(CheckType::CpiTo(*program_id))
+    }
+
+    /// Check the total number of instructions invoked via CPI, including
+    /// nested invocations.
+    pub fn cpi_count(count: usize) -> Self {
+        Check::new(CheckType::CpiCount(count))
+    }
+
+    /// Check the total compute units charged by the builtin `program_id`
+    /// across all of its invocations, directly or via CPI.
+    ///
+    /// Builtins charge a fixed cost per invocation, so this verifies, for
+    /// example, that a System program transfer CPI added exactly the
+    /// System program's cost.
+    pub fn builtin_cu(program_id: &Pubkey, expected: u64) -> Self {
+        Check::new(CheckType::BuiltinComputeUnits(*program_id, expected))
+    }
+
+    /// Check the return data set by the program.
+    pub fn return_data(return_data: &'a [u8]) -> Self {
+        Check::new(CheckType::ReturnData(return_data))
+    }
+
+    /// Check the length of the return data set by the program.
+    pub fn return_data_len(len: usize) -> Self {
+        Check::new(CheckType::ReturnDataLen(len))
+    }
+
+    /// Check a slice of a resulting account's data, starting at `offset`.
+    ///
+    /// Shorthand for `Check::account(pubkey).data_slice(offset, data).build()`.
+    /// The check fails if the slice extends past the end of the account's
+    /// data.
+    pub fn account_data_slice(pubkey: &Pubkey, offset: usize, data: &'a [u8]) -> Self {
+        Check::account(pubkey).data_slice(offset, data).build()
+    }
+
+    /// Check the change in an account's lamports, ie. its lamports in the
+    /// resulting accounts minus its lamports in the input accounts.
+    pub fn account_lamports_delta(pubkey: &Pubkey, expected_delta: i128) -> Self {
+        Check::new(CheckType::LamportsDelta(*pubkey, expected_delta))
+    }
+
+    /// Check that the sum of all account lamports is unchanged by the
+    /// instruction, ie. no lamports were minted or burned.
+    pub fn total_lamports_conserved() -> Self {
+        Check::new(CheckType::TotalLamportsConserved)
+    }
+
+    /// Check that the instruction modified no accounts other than those
+    /// provided.
+    ///
+    /// Every resulting account not in `pubkeys` is compared against its input
+    /// state, and the check fails if its lamports, data, or owner changed.
+    pub fn only_accounts_changed(pubkeys: &'a [Pubkey]) -> Self {
+        Check::new(CheckType::OnlyAccountsChanged(pubkeys))
+    }
+
+    /// Check that a resulting account matches `expected` exactly: its
+    /// lamports, owner, executable flag, and data.
+    ///
+    /// Each mismatched field is reported as a separate failure.
+    pub fn account_equals(pubkey: &Pubkey, expected: AccountSharedData) -> Self {
+        Check::new(CheckType::AccountEquals(*pubkey, expected))
+    }
+
+    /// Check that a resulting account holds at least the rent-exempt minimum
+    /// balance for its data length, according to the environment's `Rent`
+    /// sysvar.
+    ///
+    /// An account with zero lamports has been closed, and will be reclaimed
+    /// by the runtime rather than charged rent, so it passes the check. To
+    /// treat a closed account as a failure, use
+    /// `account_rent_exempt_strict`.
+    pub fn account_rent_exempt(pubkey: &Pubkey) -> Self {
+        Check::new(CheckType::RentExempt {
+            pubkey: *pubkey,
+            allow_closed: true,
+        })
+    }
+
+    /// Like `account_rent_exempt`, but failing if the account was closed.
+    pub fn account_rent_exempt_strict(pubkey: &Pubkey) -> Self {
+        Check::new(CheckType::RentExempt {
+            pubkey: *pubkey,
+            allow_closed: false,
+        })
+    }
+
+    /// Check that an account exists after the instruction: it is in the
+    /// resulting accounts with a non-zero balance or non-empty data.
+    ///
+    /// The runtime represents an account that doesn't exist as one with zero
+    /// lamports and no data, and deletes any account left that way, so such
+    /// an account fails the check.
+    pub fn account_exists(pubkey: &Pubkey) -> Self {
+        Check::new(CheckType::AccountExists {
+            pubkey: *pubkey,
+            exists: true,
+        })
+    }
+
+    /// Check that an account does not exist after the instruction: it is
+    /// either not in the resulting accounts, or has zero lamports and no
+    /// data. The inverse of `account_exists`.
+    pub fn account_absent(pubkey: &Pubkey) -> Self {
+        Check::new(CheckType::AccountExists {
+            pubkey: *pubkey,
+            exists: false,
+        })
+    }
+
+    /// Check that every byte of a resulting account's data is zero, such as
+    /// after the account was wiped on close. Empty data passes the check.
+    ///
+    /// On failure, the offset of the first non-zero byte is reported.
+    pub fn account_data_all_zero(pubkey: &Pubkey) -> Self {
+        Check::new(CheckType::AccountDataZeroed {
+            pubkey: *pubkey,
+            zeroed: true,
+        })
+    }
+
+    /// Check that at least one byte of a resulting account's data is
+    /// non-zero, such as after the account was initialized, without pinning
+    /// its exact contents. Empty data fails the check.
+    pub fn account_data_not_all_zero(pubkey: &Pubkey) -> Self {
+        Check::new(CheckType::AccountDataZeroed {
+            pubkey: *pubkey,
+            zeroed: false,
+        })
+    }
+
+    /// Check the accounts changed by the instruction against a snapshot
+    /// stored at `path`, as rendered by `InstructionResult::snapshot`.
+    ///
+    /// Accounts identical to their input, such as program accounts, are
+    /// omitted. If the file does not exist, the snapshot is written to it and
+    /// the check passes. To update a snapshot, delete the file.
+    pub fn matches_snapshot(path: &'a Path) -> Self {
+        Check::new(CheckType::Snapshot {
+            path,
+            changed_only: true,
+        })
+    }
+
+    /// Like `matches_snapshot`, but including every resulting account, even
+    /// those unchanged by the instruction.
+    pub fn matches_full_snapshot(path: &'a Path) -> Self {
+        Check::new(CheckType::Snapshot {
+            path,
+            changed_only: false,
+        })
+    }
+
     /// Check a resulting account after executing the instruction.
+    ///
+    /// Returns a builder for checking one or more properties of the account.
     pub fn account(pubkey: &Pubkey) -> AccountCheckBuilder {
         AccountCheckBuilder::new(pubkey)
     }
+
+    /// Check that a resulting account was closed: its lamports drained, its
+    /// data cleared, and its ownership returned to the System program.
+    /// Shorthand for `Check::account(pubkey).closed().build()`.
+    pub fn account_closed(pubkey: &Pubkey) -> Self {
+        AccountCheckBuilder::new(pubkey).closed().build()
+    }
+
+    /// Like `account_closed`, but for close patterns which don't reassign
+    /// the account: only its lamports and data are checked.
+    pub fn account_closed_any_owner(pubkey: &Pubkey) -> Self {
+        AccountCheckBuilder::new(pubkey).closed_any_owner().build()
+    }
+
+    /// Check the owner of a resulting account, including an owner assigned
+    /// by a program invoked via CPI. Shorthand for
+    /// `Check::account(pubkey).owner(owner).build()`.
+    pub fn account_owner(pubkey: &Pubkey, owner: &'a Pubkey) -> Self {
+        AccountCheckBuilder::new(pubkey).owner(owner).build()
+    }
+
+    /// Check the data length of a resulting account, without comparing its
+    /// data. Shorthand for `Check::account(pubkey).space(len).build()`.
+    pub fn account_space(pubkey: &Pubkey, len: usize) -> Self {
+        AccountCheckBuilder::new(pubkey).space(len).build()
+    }
 }
 
 enum AccountStateCheck {
     Closed,
+    ClosedAnyOwner,
 }
 
 struct AccountCheck<'a> {
     pubkey: Pubkey,
     check_data: Option<&'a [u8]>,
+    check_data_slice: Option<(usize, &'a [u8])>,
+    check_executable: Option<bool>,
     check_lamports: Option<u64>,
     check_owner: Option<&'a Pubkey>,
+    check_space: Option<usize>,
     check_state: Option<AccountStateCheck>,
 }
 
@@ -209,8 +1694,11 @@ impl AccountCheck<'_> {
         Self {
             pubkey: *pubkey,
             check_data: None,
+            check_data_slice: None,
+            check_executable: None,
             check_lamports: None,
             check_owner: None,
+            check_space: None,
             check_state: None,
         }
     }
@@ -227,26 +1715,62 @@ impl<'a> AccountCheckBuilder<'a> {
         }
     }
 
+    /// Check that the account is closed, ie. equal to a default account.
     pub fn closed(mut self) -> Self {
         self.check.check_state = Some(AccountStateCheck::Closed);
         self
     }
 
+    /// Check that the account is closed, without checking its owner, ie. it
+    /// has no lamports and its data, if any, is zeroed.
+    pub fn closed_any_owner(mut self) -> Self {
+        self.check.check_state = Some(AccountStateCheck::ClosedAnyOwner);
+        self
+    }
+
+    /// Check the account's data.
     pub fn data(mut self, data: &'a [u8]) -> Self {
         self.check.check_data = Some(data);
         self
     }
 
+    /// Check a slice of the account's data, starting at `offset`.
+    ///
+    /// The check fails if the slice extends past the end of the account's
+    /// data.
+    pub fn data_slice(mut self, offset: usize, data: &'a [u8]) -> Self {
+        self.check.check_data_slice = Some((offset, data));
+        self
+    }
+
+    /// Check whether the account is executable.
+    pub fn executable(mut self, executable: bool) -> Self {
+        self.check.check_executable = Some(executable);
+        self
+    }
+
+    /// Check the account's lamports.
     pub fn lamports(mut self, lamports: u64) -> Self {
         self.check.check_lamports = Some(lamports);
         self
     }
 
+    /// Check the account's owner.
     pub fn owner(mut self, owner: &'a Pubkey) -> Self {
         self.check.check_owner = Some(owner);
         self
     }
 
+    /// Check the length of the account's data.
+    pub fn space(mut self, space: usize) -> Self {
+        self.check.check_space = Some(space);
+        self
+    }
+
+    /// Build the account check.
+    ///
+    /// The check fails if the account is not present in the resulting
+    /// accounts.
     pub fn build(self) -> Check<'a> {
         Check::new(CheckType::ResultingAccount(self.check))
     }
@@ -0,0 +1,194 @@
+//! Fetching account state from a live RPC endpoint.
+//!
+//! Requires the `rpc` feature. Useful for reproducing issues observed on a
+//! live cluster, by executing instructions against real account state.
+//!
+//! Like the `file` module, these functions are intended for testing purposes
+//! and will panic if a request fails or the response is malformed.
+
+use {
+    crate::file::JsonAccount,
+    serde::Deserialize,
+    serde_json::json,
+    solana_sdk::{account::AccountSharedData, pubkey::Pubkey},
+};
+
+/// The maximum number of accounts the `getMultipleAccounts` method accepts
+/// per request.
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+#[derive(Deserialize)]
+struct RpcResponse<T> {
+    result: Option<RpcResult<T>>,
+    error: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct RpcResult<T> {
+    value: T,
+}
+
+// Send a JSON-RPC request, returning the raw response body.
+fn send(url: &str, method: &str, params: serde_json::Value) -> String {
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": method,
+        "params": params,
+    });
+    reqwest::blocking::Client::new()
+        .post(url)
+        .header("Content-Type", "application/json")
+        .body(request.to_string())
+        .send()
+        .and_then(|response| response.error_for_status())
+        .and_then(|response| response.text())
+        .unwrap_or_else(|err| panic!("RPC request {} to {} failed: {}", method, url, err))
+}
+
+// Parse a JSON-RPC response body, returning its `result.value`.
+fn parse_response<T: for<'de> Deserialize<'de>>(body: &str, method: &str, url: &str) -> T {
+    let response: RpcResponse<T> = serde_json::from_str(body)
+        .unwrap_or_else(|err| panic!("Invalid {} response from {}: {}", method, url, err));
+    match (response.result, response.error) {
+        (Some(result), _) => result.value,
+        (None, Some(error)) => panic!("RPC request {} to {} failed: {}", method, url, error),
+        (None, None) => panic!("Invalid {} response from {}: no result", method, url),
+    }
+}
+
+fn parse_account_info(body: &str, url: &str) -> Option<AccountSharedData> {
+    let account: Option<JsonAccount> = parse_response(body, "getAccountInfo", url);
+    account.map(|account| account.into_account(url))
+}
+
+fn parse_multiple_accounts(body: &str, url: &str) -> Vec<Option<AccountSharedData>> {
+    let accounts: Vec<Option<JsonAccount>> = parse_response(body, "getMultipleAccounts", url);
+    accounts
+        .into_iter()
+        .map(|account| account.map(|account| account.into_account(url)))
+        .collect()
+}
+
+/// Fetch an account with the `getAccountInfo` RPC method.
+///
+/// Panics if the account does not exist.
+pub fn get_account(url: &str, pubkey: &Pubkey) -> AccountSharedData {
+    let body = send(
+        url,
+        "getAccountInfo",
+        json!([pubkey.to_string(), { "encoding": "base64" }]),
+    );
+    parse_account_info(&body, url)
+        .unwrap_or_else(|| panic!("Account {} not found at {}", pubkey, url))
+}
+
+/// Fetch a list of accounts with the `getMultipleAccounts` RPC method,
+/// batching requests as necessary.
+///
+/// Accounts that do not exist are returned as `None`, in the position of
+/// their key.
+pub fn get_multiple_accounts(url: &str, pubkeys: &[Pubkey]) -> Vec<Option<AccountSharedData>> {
+    pubkeys
+        .chunks(MAX_MULTIPLE_ACCOUNTS)
+        .flat_map(|chunk| {
+            let keys = chunk.iter().map(Pubkey::to_string).collect::<Vec<_>>();
+            let body = send(
+                url,
+                "getMultipleAccounts",
+                json!([keys, { "encoding": "base64" }]),
+            );
+            parse_multiple_accounts(&body, url)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_sdk::account::{Account, ReadableAccount},
+    };
+
+    const URL: &str = "http://localhost:8899";
+
+    fn account_json(lamports: u64, data: &str, owner: &Pubkey) -> serde_json::Value {
+        json!({
+            "lamports": lamports,
+            "data": [data, "base64"],
+            "owner": owner.to_string(),
+            "executable": false,
+            "rentEpoch": 18446744073709551615u64,
+            "space": 3,
+        })
+    }
+
+    fn response_json(value: serde_json::Value) -> String {
+        json!({
+            "jsonrpc": "2.0",
+            "result": {
+                "context": { "apiVersion": "2.0.9", "slot": 300_000_000u64 },
+                "value": value,
+            },
+            "id": 1,
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn test_parse_account_info() {
+        let owner = Pubkey::new_unique();
+
+        let body = response_json(account_json(1_000, "AQID", &owner));
+        let account = parse_account_info(&body, URL).unwrap();
+        assert_eq!(
+            account,
+            AccountSharedData::from(Account {
+                lamports: 1_000,
+                data: vec![1, 2, 3],
+                owner,
+                executable: false,
+                rent_epoch: u64::MAX,
+            })
+        );
+
+        let body = response_json(serde_json::Value::Null);
+        assert_eq!(parse_account_info(&body, URL), None);
+    }
+
+    #[test]
+    fn test_parse_multiple_accounts() {
+        let owner = Pubkey::new_unique();
+
+        let body = response_json(json!([
+            account_json(1_000, "AQID", &owner),
+            null,
+            account_json(2_000, "", &owner),
+        ]));
+        let accounts = parse_multiple_accounts(&body, URL);
+        assert_eq!(accounts.len(), 3);
+        assert_eq!(accounts[0].as_ref().unwrap().data(), &[1, 2, 3]);
+        assert_eq!(accounts[1], None);
+        assert_eq!(accounts[2].as_ref().unwrap().lamports(), 2_000);
+        assert!(accounts[2].as_ref().unwrap().data().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "RPC request getAccountInfo to http://localhost:8899 failed")]
+    fn test_parse_error_response() {
+        let body = json!({
+            "jsonrpc": "2.0",
+            "error": { "code": -32602, "message": "Invalid param: WrongSize" },
+            "id": 1,
+        })
+        .to_string();
+        parse_account_info(&body, URL);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid base64 data")]
+    fn test_parse_invalid_base64() {
+        let body = response_json(account_json(1_000, "not base64!", &Pubkey::new_unique()));
+        parse_account_info(&body, URL);
+    }
+}
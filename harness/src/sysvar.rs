@@ -1,23 +1,33 @@
 //! Module for working with Solana sysvars.
 
 use {
+    serde::{Deserialize, Serialize},
     solana_program_runtime::sysvar_cache::SysvarCache,
     solana_sdk::{
-        clock::{Clock, Slot},
+        account::{
+            create_account_shared_data_with_fields, Account, AccountSharedData, ReadableAccount,
+            WritableAccount,
+        },
+        clock::{Clock, Epoch, Slot, UnixTimestamp, DEFAULT_MS_PER_SLOT},
         epoch_rewards::EpochRewards,
         epoch_schedule::EpochSchedule,
         hash::Hash,
+        instruction::{BorrowedAccountMeta, BorrowedInstruction, Instruction},
+        pubkey::Pubkey,
         rent::Rent,
-        slot_hashes::SlotHashes,
-        stake_history::StakeHistory,
-        sysvar::{last_restart_slot::LastRestartSlot, SysvarId},
+        slot_hashes::{SlotHashes, MAX_ENTRIES},
+        stake_history::{StakeHistory, StakeHistoryEntry},
+        sysvar::{
+            self, instructions::construct_instructions_data, last_restart_slot::LastRestartSlot,
+            Sysvar, SysvarId,
+        },
     },
 };
 
 // Agave's sysvar cache is difficult to work with, so Mollusk offers a wrapper
 // around it for modifying its contents.
 /// Mollusk sysvars.
-#[derive(Default)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct Sysvars {
     pub clock: Clock,
     pub epoch_rewards: EpochRewards,
@@ -28,29 +38,192 @@ pub struct Sysvars {
     pub stake_history: StakeHistory,
 }
 
+/// The time elapsed since genesis at a slot, in seconds, assuming
+/// `DEFAULT_MS_PER_SLOT`.
+fn timestamp_at(slot: Slot) -> UnixTimestamp {
+    (slot.saturating_mul(DEFAULT_MS_PER_SLOT) / 1_000) as UnixTimestamp
+}
+
 impl Sysvars {
+    /// Create sysvars for an environment starting at a slot other than
+    /// genesis.
+    ///
+    /// The `Clock`'s epoch and leader schedule epoch are derived from the
+    /// default `EpochSchedule`, as in `warp_to_slot`. Its Unix timestamp is
+    /// the time elapsed since genesis (timestamp zero), assuming
+    /// `DEFAULT_MS_PER_SLOT`, and its epoch start timestamp is derived the
+    /// same way from the first slot of the epoch.
+    pub fn new_at_slot(slot: Slot) -> Self {
+        let mut sysvars = Self::default();
+        sysvars.warp_to_slot(slot);
+        let epoch_start_slot = sysvars
+            .epoch_schedule
+            .get_first_slot_in_epoch(sysvars.clock.epoch);
+        sysvars.clock.epoch_start_timestamp = timestamp_at(epoch_start_slot);
+        sysvars.clock.unix_timestamp = timestamp_at(slot);
+        sysvars
+    }
+
     /// Warp the test environment to a slot by updating sysvars.
+    ///
+    /// The `Clock`'s epoch and leader schedule epoch are derived from the
+    /// `EpochSchedule` sysvar. Its timestamps are left unchanged within the
+    /// current epoch. When the warp crosses into another epoch, the Unix
+    /// timestamp is moved by the time elapsed between the current slot and
+    /// the new slot, assuming `DEFAULT_MS_PER_SLOT`, and the epoch start
+    /// timestamp is derived the same way from the first slot of the new
+    /// epoch.
     pub fn warp_to_slot(&mut self, slot: Slot) {
         // First update `Clock`.
         let epoch = self.epoch_schedule.get_epoch(slot);
         let leader_schedule_epoch = self.epoch_schedule.get_leader_schedule_epoch(slot);
+        let (epoch_start_timestamp, unix_timestamp) = if epoch == self.clock.epoch {
+            (self.clock.epoch_start_timestamp, self.clock.unix_timestamp)
+        } else {
+            let epoch_start_slot = self.epoch_schedule.get_first_slot_in_epoch(epoch);
+            let unix_timestamp = self
+                .clock
+                .unix_timestamp
+                .saturating_add(timestamp_at(slot))
+                .saturating_sub(timestamp_at(self.clock.slot));
+            let epoch_start_timestamp = unix_timestamp
+                .saturating_sub(timestamp_at(slot))
+                .saturating_add(timestamp_at(epoch_start_slot));
+            (epoch_start_timestamp, unix_timestamp)
+        };
         self.clock = Clock {
             slot,
+            epoch_start_timestamp,
             epoch,
             leader_schedule_epoch,
-            ..Default::default()
+            unix_timestamp,
         };
 
-        // Then update `SlotHashes`.
-        let mut i = 0;
+        // Then update `SlotHashes`, adding a default hash for every slot after
+        // the most recent entry, up to and including the new slot. The sysvar
+        // only retains the most recent `MAX_ENTRIES` slots, so older entries
+        // are dropped and there's no need to generate more than that.
+        let mut i = slot.saturating_sub(MAX_ENTRIES as u64 - 1);
         if let Some(most_recent_slot_hash) = self.slot_hashes.first() {
-            i = most_recent_slot_hash.0;
+            i = i.max(most_recent_slot_hash.0.saturating_add(1));
         }
-        let mut new_slot_hashes = vec![];
         for slot in i..slot + 1 {
-            new_slot_hashes.push((slot, Hash::default()));
+            self.slot_hashes.add(slot, Hash::default());
         }
-        self.slot_hashes = SlotHashes::new(&new_slot_hashes);
+    }
+
+    /// Warp the test environment to the first slot of an epoch by updating
+    /// sysvars.
+    ///
+    /// The slot is derived from the `EpochSchedule` sysvar. The `Clock`'s
+    /// Unix timestamp is advanced by the time elapsed between the current slot
+    /// and the new slot, assuming `DEFAULT_MS_PER_SLOT`, and the epoch start
+    /// timestamp is set to the new Unix timestamp.
+    pub fn warp_to_epoch(&mut self, epoch: Epoch) {
+        let slot = self.epoch_schedule.get_first_slot_in_epoch(epoch);
+        let elapsed_slots = slot.saturating_sub(self.clock.slot);
+        let unix_timestamp = self
+            .clock
+            .unix_timestamp
+            .saturating_add(timestamp_at(elapsed_slots));
+
+        self.warp_to_slot(slot);
+        self.clock.epoch_start_timestamp = unix_timestamp;
+        self.clock.unix_timestamp = unix_timestamp;
+    }
+
+    /// Set the `Clock`'s Unix timestamp.
+    pub fn set_unix_timestamp(&mut self, unix_timestamp: UnixTimestamp) {
+        self.clock.unix_timestamp = unix_timestamp;
+    }
+
+    /// Set the `Rent` sysvar.
+    pub fn set_rent(&mut self, rent: Rent) {
+        self.rent = rent;
+    }
+
+    /// Set the `EpochSchedule` sysvar.
+    ///
+    /// The `Clock`'s epoch and leader schedule epoch are re-derived from the
+    /// new schedule at the current slot, so the two stay consistent.
+    pub fn set_epoch_schedule(&mut self, epoch_schedule: EpochSchedule) {
+        let slot = self.clock.slot;
+        self.clock.epoch = epoch_schedule.get_epoch(slot);
+        self.clock.leader_schedule_epoch = epoch_schedule.get_leader_schedule_epoch(slot);
+        self.epoch_schedule = epoch_schedule;
+    }
+
+    /// Set the `EpochRewards` sysvar.
+    pub fn set_epoch_rewards(&mut self, epoch_rewards: EpochRewards) {
+        self.epoch_rewards = epoch_rewards;
+    }
+
+    /// Set the slot of the `LastRestartSlot` sysvar.
+    pub fn set_last_restart_slot(&mut self, last_restart_slot: Slot) {
+        self.last_restart_slot = LastRestartSlot { last_restart_slot };
+    }
+
+    /// Add an entry to the `SlotHashes` sysvar, replacing any existing entry
+    /// for the same slot.
+    pub fn push_slot_hash(&mut self, slot: Slot, hash: Hash) {
+        self.slot_hashes.add(slot, hash);
+    }
+
+    /// Add an entry to the `StakeHistory` sysvar, replacing any existing
+    /// entry for the same epoch.
+    pub fn add_stake_history_entry(&mut self, epoch: Epoch, entry: StakeHistoryEntry) {
+        self.stake_history.add(epoch, entry);
+    }
+
+    /// Serialize the sysvars to bincode, the format used by fixtures, for
+    /// pinning a known sysvar environment. See `from_bytes`.
+    ///
+    /// Every sysvar held by `Sysvars` is included. The `Fees` sysvar is
+    /// omitted, since it is deprecated and not modeled here; the lamports per
+    /// signature are configured on the Mollusk fee structure instead.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        bincode::serialize(self).unwrap()
+    }
+
+    /// Deserialize sysvars serialized with `to_bytes`. Panics if the bytes
+    /// are not valid serialized sysvars.
+    ///
+    /// As with `to_bytes`, the deprecated `Fees` sysvar is not included.
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        bincode::deserialize(bytes).unwrap_or_else(|err| panic!("Invalid sysvars: {}", err))
+    }
+
+    /// Create the account for a sysvar, holding its current state, for
+    /// programs that take the sysvar as an instruction account rather than
+    /// reading it with a syscall.
+    ///
+    /// The account is owned by the sysvar program, sized as on a cluster, and
+    /// rent exempt. Returns `None` if `sysvar_id` is not one of these sysvars.
+    pub fn sysvar_account(&self, sysvar_id: &Pubkey) -> Option<AccountSharedData> {
+        let account = if *sysvar_id == Clock::id() {
+            self.create_sysvar_account(&self.clock)
+        } else if *sysvar_id == EpochRewards::id() {
+            self.create_sysvar_account(&self.epoch_rewards)
+        } else if *sysvar_id == EpochSchedule::id() {
+            self.create_sysvar_account(&self.epoch_schedule)
+        } else if *sysvar_id == LastRestartSlot::id() {
+            self.create_sysvar_account(&self.last_restart_slot)
+        } else if *sysvar_id == Rent::id() {
+            self.create_sysvar_account(&self.rent)
+        } else if *sysvar_id == SlotHashes::id() {
+            self.create_sysvar_account(&self.slot_hashes)
+        } else if *sysvar_id == StakeHistory::id() {
+            self.create_sysvar_account(&self.stake_history)
+        } else {
+            return None;
+        };
+        Some(account)
+    }
+
+    fn create_sysvar_account<S: Sysvar>(&self, sysvar: &S) -> AccountSharedData {
+        let mut account = create_account_shared_data_with_fields(sysvar, (0, 0));
+        account.set_lamports(self.rent.minimum_balance(account.data().len()));
+        account
     }
 }
 
@@ -84,9 +257,48 @@ impl From<&Sysvars> for SysvarCache {
     }
 }
 
+/// Create an `Instructions` sysvar account holding `instructions`, with
+/// `current_index` as the index of the executing instruction.
+///
+/// Programs read this account with `load_instruction_at_checked` and
+/// `load_current_index_checked` to introspect sibling instructions.
+pub fn instructions_sysvar_account(
+    instructions: &[Instruction],
+    current_index: u16,
+) -> AccountSharedData {
+    let borrowed_instructions = instructions
+        .iter()
+        .map(|instruction| BorrowedInstruction {
+            program_id: &instruction.program_id,
+            accounts: instruction
+                .accounts
+                .iter()
+                .map(|meta| BorrowedAccountMeta {
+                    pubkey: &meta.pubkey,
+                    is_signer: meta.is_signer,
+                    is_writable: meta.is_writable,
+                })
+                .collect(),
+            data: &instruction.data,
+        })
+        .collect::<Vec<_>>();
+    let mut data = construct_instructions_data(&borrowed_instructions);
+    // The current index is stored in the last two bytes.
+    let len = data.len();
+    data[len - 2..].copy_from_slice(&current_index.to_le_bytes());
+    let lamports = Rent::default().minimum_balance(data.len());
+    AccountSharedData::from(Account {
+        lamports,
+        data,
+        owner: sysvar::id(),
+        executable: false,
+        rent_epoch: 0,
+    })
+}
+
 #[cfg(test)]
 mod tests {
-    use {super::*, solana_sdk::stake_history::StakeHistoryEntry, std::ops::Deref};
+    use {super::*, std::ops::Deref};
 
     #[test]
     fn test_warp_to_slot() {
@@ -101,6 +313,222 @@ mod tests {
 
         sysvars.warp_to_slot(800_000);
         assert_eq!(sysvars.clock.slot, 800_000);
+        assert_eq!(
+            sysvars.clock.epoch,
+            sysvars.epoch_schedule.get_epoch(800_000)
+        );
+        assert_eq!(sysvars.slot_hashes.len(), MAX_ENTRIES);
+        assert_eq!(sysvars.slot_hashes.first().unwrap().0, 800_000);
+        assert_eq!(
+            sysvars.slot_hashes.last().unwrap().0,
+            800_000 - (MAX_ENTRIES as u64 - 1)
+        );
+    }
+
+    #[test]
+    fn test_new_at_slot() {
+        let sysvars = Sysvars::new_at_slot(0);
+        assert_eq!(sysvars.clock, Sysvars::default().clock);
+
+        let slot = 1_000_000;
+        let sysvars = Sysvars::new_at_slot(slot);
+        let epoch = sysvars.epoch_schedule.get_epoch(slot);
+        assert_eq!(sysvars.clock.slot, slot);
+        assert_eq!(sysvars.clock.epoch, epoch);
+        assert_eq!(
+            sysvars.clock.leader_schedule_epoch,
+            sysvars.epoch_schedule.get_leader_schedule_epoch(slot)
+        );
+        assert_eq!(
+            sysvars.clock.unix_timestamp,
+            (slot * DEFAULT_MS_PER_SLOT / 1_000) as UnixTimestamp
+        );
+        let epoch_start_slot = sysvars.epoch_schedule.get_first_slot_in_epoch(epoch);
+        assert_eq!(
+            sysvars.clock.epoch_start_timestamp,
+            (epoch_start_slot * DEFAULT_MS_PER_SLOT / 1_000) as UnixTimestamp
+        );
+        assert_eq!(sysvars.slot_hashes.first().unwrap().0, slot);
+    }
+
+    #[test]
+    fn test_sysvar_account() {
+        let mut sysvars = Sysvars::default();
+        sysvars.warp_to_slot(200);
+        sysvars.set_unix_timestamp(1_700_000_000);
+
+        let account = sysvars.sysvar_account(&Clock::id()).unwrap();
+        assert_eq!(account.owner(), &sysvar::id());
+        assert_eq!(
+            account.lamports(),
+            sysvars.rent.minimum_balance(account.data().len())
+        );
+        let clock: Clock = bincode::deserialize(account.data()).unwrap();
+        assert_eq!(clock, sysvars.clock);
+
+        let account = sysvars.sysvar_account(&SlotHashes::id()).unwrap();
+        assert_eq!(account.data().len(), SlotHashes::size_of());
+        let slot_hashes: SlotHashes = bincode::deserialize(account.data()).unwrap();
+        assert_eq!(slot_hashes, sysvars.slot_hashes);
+
+        let account = sysvars.sysvar_account(&Rent::id()).unwrap();
+        let rent: Rent = bincode::deserialize(account.data()).unwrap();
+        assert_eq!(rent, sysvars.rent);
+
+        assert!(sysvars.sysvar_account(&Pubkey::new_unique()).is_none());
+    }
+
+    #[test]
+    fn test_slot_hashes_and_stake_history() {
+        let mut sysvars = Sysvars::default();
+
+        let hash = Hash::new_unique();
+        sysvars.push_slot_hash(5, hash);
+        assert_eq!(sysvars.slot_hashes.get(&5), Some(&hash));
+
+        // Warping keeps existing entries and adds one for the new slot.
+        sysvars.warp_to_slot(10);
+        assert_eq!(sysvars.slot_hashes.get(&5), Some(&hash));
+        assert_eq!(sysvars.slot_hashes.get(&10), Some(&Hash::default()));
+        assert_eq!(sysvars.slot_hashes.first().unwrap().0, 10);
+
+        let entry = StakeHistoryEntry {
+            effective: 100,
+            activating: 10,
+            deactivating: 1,
+        };
+        sysvars.add_stake_history_entry(3, entry.clone());
+        assert_eq!(sysvars.stake_history.get(3), Some(&entry));
+    }
+
+    #[test]
+    fn test_warp_to_epoch() {
+        let mut sysvars = Sysvars {
+            epoch_schedule: EpochSchedule::without_warmup(),
+            ..Default::default()
+        };
+        let slots_per_epoch = sysvars.epoch_schedule.slots_per_epoch;
+
+        sysvars.warp_to_epoch(2);
+        assert_eq!(sysvars.clock.slot, 2 * slots_per_epoch);
+        assert_eq!(sysvars.clock.epoch, 2);
+
+        let expected_timestamp = (2 * slots_per_epoch * DEFAULT_MS_PER_SLOT / 1_000) as i64;
+        assert_eq!(sysvars.clock.unix_timestamp, expected_timestamp);
+        assert_eq!(sysvars.clock.epoch_start_timestamp, expected_timestamp);
+        assert_eq!(sysvars.slot_hashes.first().unwrap().0, 2 * slots_per_epoch);
+
+        // Warping to a slot keeps the timestamps.
+        sysvars.warp_to_slot(2 * slots_per_epoch + 10);
+        assert_eq!(sysvars.clock.epoch, 2);
+        assert_eq!(sysvars.clock.unix_timestamp, expected_timestamp);
+        assert_eq!(sysvars.clock.epoch_start_timestamp, expected_timestamp);
+
+        // Unless it crosses into another epoch.
+        sysvars.warp_to_slot(3 * slots_per_epoch + 10);
+        assert_eq!(sysvars.clock.epoch, 3);
+        assert_eq!(
+            sysvars.clock.unix_timestamp,
+            ((3 * slots_per_epoch + 10) * DEFAULT_MS_PER_SLOT / 1_000) as i64
+        );
+        assert_eq!(
+            sysvars.clock.epoch_start_timestamp,
+            (3 * slots_per_epoch * DEFAULT_MS_PER_SLOT / 1_000) as i64
+        );
+    }
+
+    #[test]
+    fn test_setters() {
+        let mut sysvars = Sysvars::default();
+        sysvars.warp_to_slot(1_000);
+
+        let rent = Rent {
+            lamports_per_byte_year: 7,
+            ..Default::default()
+        };
+        sysvars.set_rent(rent.clone());
+
+        let epoch_schedule = EpochSchedule::custom(100, 100, false);
+        sysvars.set_epoch_schedule(epoch_schedule.clone());
+        assert_eq!(sysvars.clock.epoch, 10);
+        assert_eq!(
+            sysvars.clock.leader_schedule_epoch,
+            epoch_schedule.get_leader_schedule_epoch(1_000)
+        );
+
+        let epoch_rewards = EpochRewards {
+            total_rewards: 4,
+            active: true,
+            ..Default::default()
+        };
+        sysvars.set_epoch_rewards(epoch_rewards.clone());
+
+        sysvars.set_last_restart_slot(900);
+
+        let sysvar_cache: SysvarCache = (&sysvars).into();
+        assert_eq!(sysvar_cache.get_rent().unwrap().deref(), &rent);
+        assert_eq!(
+            sysvar_cache.get_epoch_schedule().unwrap().deref(),
+            &epoch_schedule
+        );
+        assert_eq!(
+            sysvar_cache.get_epoch_rewards().unwrap().deref(),
+            &epoch_rewards
+        );
+        assert_eq!(
+            sysvar_cache
+                .get_last_restart_slot()
+                .unwrap()
+                .last_restart_slot,
+            900
+        );
+        assert_eq!(sysvar_cache.get_clock().unwrap().epoch, 10);
+    }
+
+    #[test]
+    fn test_bytes_round_trip() {
+        let mut sysvars = Sysvars::new_at_slot(1_000);
+        sysvars.set_rent(Rent {
+            lamports_per_byte_year: 7,
+            exemption_threshold: 3.5,
+            burn_percent: 10,
+        });
+        sysvars.set_epoch_schedule(EpochSchedule::custom(100, 100, false));
+        sysvars.set_epoch_rewards(EpochRewards {
+            total_rewards: 4,
+            distributed_rewards: 2,
+            active: true,
+            ..Default::default()
+        });
+        sysvars.set_last_restart_slot(900);
+        sysvars.push_slot_hash(999, Hash::new_unique());
+        sysvars.add_stake_history_entry(
+            9,
+            StakeHistoryEntry {
+                effective: 100,
+                activating: 10,
+                deactivating: 1,
+            },
+        );
+
+        let bytes = sysvars.to_bytes();
+        let decoded = Sysvars::from_bytes(&bytes);
+        assert_eq!(decoded.clock, sysvars.clock);
+        assert_eq!(decoded.epoch_rewards, sysvars.epoch_rewards);
+        assert_eq!(decoded.epoch_schedule, sysvars.epoch_schedule);
+        assert_eq!(decoded.last_restart_slot, sysvars.last_restart_slot);
+        assert_eq!(decoded.rent, sysvars.rent);
+        assert_eq!(decoded.slot_hashes, sysvars.slot_hashes);
+        assert_eq!(decoded.stake_history, sysvars.stake_history);
+
+        // Serialization is stable across round trips.
+        assert_eq!(decoded.to_bytes(), bytes);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid sysvars")]
+    fn test_from_bytes_invalid() {
+        Sysvars::from_bytes(&[1, 2, 3]);
     }
 
     #[test]
@@ -0,0 +1,49 @@
+use {
+    mollusk_svm::file::{load_account_json, load_accounts_json},
+    solana_sdk::{
+        account::{Account, AccountSharedData},
+        native_loader,
+        pubkey::Pubkey,
+    },
+    std::str::FromStr,
+};
+
+fn stake_account() -> (Pubkey, AccountSharedData) {
+    (
+        Pubkey::from_str("Stake11111111111111111111111111111111111111").unwrap(),
+        AccountSharedData::from(Account {
+            lamports: 1_000_000,
+            data: vec![1, 2, 3, 4, 5],
+            owner: Pubkey::from_str("Vote111111111111111111111111111111111111111").unwrap(),
+            executable: false,
+            rent_epoch: u64::MAX,
+        }),
+    )
+}
+
+#[test]
+fn test_load_account_json() {
+    let account = load_account_json("tests/fixtures/account.json");
+    assert_eq!(account, stake_account());
+}
+
+#[test]
+fn test_load_accounts_json() {
+    let accounts = load_accounts_json("tests/fixtures/accounts.json");
+    assert_eq!(
+        accounts,
+        vec![
+            stake_account(),
+            (
+                Pubkey::from_str("Vote111111111111111111111111111111111111111").unwrap(),
+                AccountSharedData::from(Account {
+                    lamports: 1,
+                    data: vec![],
+                    owner: native_loader::id(),
+                    executable: true,
+                    rent_epoch: 0,
+                }),
+            ),
+        ]
+    );
+}
@@ -0,0 +1,53 @@
+use {
+    mollusk_svm::Mollusk,
+    solana_sdk::{account::AccountSharedData, pubkey::Pubkey, system_instruction, system_program},
+    solana_system_program::system_processor::DEFAULT_COMPUTE_UNITS,
+};
+
+#[test]
+fn test_bench_instruction() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+
+    let instruction = system_instruction::transfer(&sender, &recipient, 42_000);
+    let accounts = [
+        (
+            sender,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (
+            recipient,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+    ];
+
+    let mollusk = Mollusk::default();
+    let result = mollusk.bench_instruction(&instruction, &accounts, 10);
+
+    assert_eq!(result.iterations, 10);
+    assert_eq!(result.compute_units_min, DEFAULT_COMPUTE_UNITS);
+    assert_eq!(result.compute_units_max, DEFAULT_COMPUTE_UNITS);
+    assert_eq!(result.compute_units_mean, DEFAULT_COMPUTE_UNITS);
+    assert!(result.time_min <= result.time_mean);
+    assert!(result.time_mean <= result.time_max);
+
+    let path = std::env::temp_dir().join(format!("mollusk_bench_{}.md", sender));
+
+    // The first run has no baseline.
+    result.write_markdown(&path);
+    let content = std::fs::read_to_string(&path).unwrap();
+    assert!(content.contains(&format!(
+        "| CUs (mean) | {} | - new - |",
+        DEFAULT_COMPUTE_UNITS
+    )));
+
+    // Unchanged compute units don't produce a new table.
+    mollusk
+        .bench_instruction(&instruction, &accounts, 10)
+        .write_markdown(&path);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), content);
+
+    std::fs::remove_file(&path).unwrap();
+}
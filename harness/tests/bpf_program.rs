@@ -1,18 +1,37 @@
 use {
     mollusk_svm::{
-        program::{program_account, system_program},
-        result::Check,
+        error::MolluskError,
+        file::{self, FileError},
+        program::{
+            program_account, program_account_loader_v4, program_accounts, system_program,
+            LoaderOptions, SyscallOverrides,
+        },
+        result::{Check, CheckFailure, InnerInstruction, ProgramResult},
+        sysvar::Sysvars,
         Mollusk,
     },
+    solana_compute_budget::compute_budget::ComputeBudget,
+    solana_program_runtime::{
+        invoke_context::InvokeContext,
+        solana_rbpf::{declare_builtin_function, memory_region::MemoryMapping},
+        sysvar_cache::SysvarCache,
+    },
     solana_sdk::{
-        account::AccountSharedData,
+        account::{AccountSharedData, WritableAccount},
+        bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+        compute_budget::ComputeBudgetInstruction,
+        entrypoint::MAX_PERMITTED_DATA_INCREASE,
+        feature_set::{self, FeatureSet},
         incinerator,
         instruction::{AccountMeta, Instruction, InstructionError},
+        loader_v4::{self, LoaderV4Status},
         program_error::ProgramError,
         pubkey::Pubkey,
-        system_instruction::SystemError,
+        rent::Rent,
+        system_instruction::{SystemError, MAX_PERMITTED_DATA_LENGTH},
         system_program,
     },
+    solana_system_program::system_processor::DEFAULT_COMPUTE_UNITS,
 };
 
 #[test]
@@ -79,13 +98,131 @@ fn test_write_data() {
             Check::compute_units(358),
             Check::account(&key)
                 .data(data)
+                .data_slice(1, &data[1..3])
+                .executable(false)
                 .lamports(lamports)
                 .owner(&program_id)
+                .space(space)
                 .build(),
         ],
     );
 }
 
+#[test]
+fn test_account_data_all_zero() {
+    std::env::set_var("SBF_OUT_DIR", "../target/deploy");
+
+    let program_id = Pubkey::new_unique();
+
+    let mollusk = Mollusk::new(&program_id, "test_program_primary");
+
+    let data = &[1, 2, 3, 4, 5];
+    let key = Pubkey::new_unique();
+    let account = mollusk.rent_exempt_account(&program_id, data.len());
+
+    let write = |data: &[u8]| {
+        let mut instruction_data = vec![1];
+        instruction_data.extend_from_slice(data);
+        Instruction::new_with_bytes(
+            program_id,
+            &instruction_data,
+            vec![AccountMeta::new(key, true)],
+        )
+    };
+
+    // A failed write leaves the data zeroed.
+    mollusk.process_and_validate_instruction(
+        &write(&[1; 7]),
+        &[(key, account.clone())],
+        &[
+            Check::err(ProgramError::AccountDataTooSmall),
+            Check::account_data_all_zero(&key),
+        ],
+    );
+
+    mollusk.process_and_validate_instruction(
+        &write(data),
+        &[(key, account.clone())],
+        &[Check::success(), Check::account_data_not_all_zero(&key)],
+    );
+
+    // The first non-zero byte is reported.
+    let failures = mollusk
+        .try_process_and_validate_instruction(
+            &write(data),
+            &[(key, account.clone())],
+            &[
+                Check::account_data_all_zero(&key),
+                Check::account_data_not_all_zero(&key),
+            ],
+        )
+        .unwrap_err();
+    assert_eq!(
+        failures,
+        vec![CheckFailure {
+            check: format!("account {} data all zero", key),
+            expected: "all zero".to_string(),
+            actual: "non-zero byte at offset 0".to_string(),
+        }]
+    );
+}
+
+#[test]
+fn test_write_readonly_account() {
+    std::env::set_var("SBF_OUT_DIR", "../target/deploy");
+
+    let program_id = Pubkey::new_unique();
+
+    let mollusk = Mollusk::new(&program_id, "test_program_primary");
+
+    let data = &[1, 2, 3, 4, 5];
+    let space = data.len();
+    let lamports = mollusk.sysvars.rent.minimum_balance(space);
+
+    let key = Pubkey::new_unique();
+    let account = AccountSharedData::new(lamports, space, &program_id);
+
+    let write = |accounts: Vec<AccountMeta>| {
+        let mut instruction_data = vec![1];
+        instruction_data.extend_from_slice(data);
+        Instruction::new_with_bytes(program_id, &instruction_data, accounts)
+    };
+
+    // An account marked read-only can't be written, even by its owner.
+    mollusk.process_and_validate_instruction(
+        &write(vec![AccountMeta::new_readonly(key, true)]),
+        &[(key, account.clone())],
+        &[
+            Check::instruction_err(InstructionError::ReadonlyDataModified),
+            Check::account(&key).data(&[0; 5]).build(),
+        ],
+    );
+
+    // A writable account owned by another program can't be written either.
+    mollusk.process_and_validate_instruction(
+        &write(vec![AccountMeta::new(key, true)]),
+        &[(
+            key,
+            AccountSharedData::new(lamports, space, &Pubkey::new_unique()),
+        )],
+        &[
+            Check::instruction_err(InstructionError::ExternalAccountDataModified),
+            Check::account(&key).data(&[0; 5]).build(),
+        ],
+    );
+
+    // As in the runtime, an account referenced more than once is writable if
+    // any reference marks it writable.
+    mollusk.process_and_validate_instruction(
+        &write(vec![
+            AccountMeta::new_readonly(key, true),
+            AccountMeta::new(key, true),
+        ]),
+        &[(key, account.clone()), (key, account)],
+        &[Check::success(), Check::account(&key).data(data).build()],
+    );
+}
+
 #[test]
 fn test_transfer() {
     std::env::set_var("SBF_OUT_DIR", "../target/deploy");
@@ -176,6 +313,52 @@ fn test_transfer() {
     );
 }
 
+#[test]
+fn test_builtin_cu() {
+    std::env::set_var("SBF_OUT_DIR", "../target/deploy");
+
+    let program_id = Pubkey::new_unique();
+
+    let mollusk = Mollusk::new(&program_id, "test_program_primary");
+
+    let payer = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let instruction = {
+        let mut instruction_data = vec![2];
+        instruction_data.extend_from_slice(&2_000_000_u64.to_le_bytes());
+        Instruction::new_with_bytes(
+            program_id,
+            &instruction_data,
+            vec![
+                AccountMeta::new(payer, true),
+                AccountMeta::new(recipient, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        )
+    };
+
+    // The System program's fixed cost is charged whether the transfer it's
+    // invoked for succeeds or fails.
+    for payer_account in [
+        AccountSharedData::new(100_000_000, 0, &system_program::id()),
+        AccountSharedData::default(),
+    ] {
+        mollusk.process_and_validate_instruction(
+            &instruction,
+            &[
+                (payer, payer_account),
+                (recipient, AccountSharedData::default()),
+                system_program(),
+            ],
+            &[Check::builtin_cu(
+                &system_program::id(),
+                DEFAULT_COMPUTE_UNITS,
+            )],
+        );
+    }
+}
+
 #[test]
 fn test_close_account() {
     std::env::set_var("SBF_OUT_DIR", "../target/deploy");
@@ -269,6 +452,8 @@ fn test_cpi() {
 
     // Fail CPI target program account not provided.
     {
+        let mut mollusk = mollusk.clone();
+        mollusk.allow_missing_accounts = true;
         mollusk.process_and_validate_instruction(
             &instruction,
             &[(key, account.clone())],
@@ -295,6 +480,7 @@ fn test_cpi() {
                 // "Program is not cached".
                 Check::err(ProgramError::InvalidAccountData),
                 Check::compute_units(1840),
+                Check::log("Program is not cached"),
             ],
         );
     }
@@ -347,7 +533,7 @@ fn test_cpi() {
     }
 
     // Success.
-    mollusk.process_and_validate_instruction(
+    let result = mollusk.process_and_validate_instruction(
         &instruction,
         &[
             (key, account.clone()),
@@ -366,4 +552,1549 @@ fn test_cpi() {
                 .build(),
         ],
     );
+
+    // The callee completes first, and the caller's total includes it.
+    let [(callee, callee_units), (caller, caller_units)] =
+        result.compute_units_per_program.as_slice()
+    else {
+        panic!(
+            "Expected two program invocations, got {:?}",
+            result.compute_units_per_program
+        );
+    };
+    assert_eq!(callee, &cpi_target_program_id);
+    assert_eq!(caller, &program_id);
+    assert_eq!(*caller_units, 2279);
+    assert!(callee_units < caller_units);
+
+    let caller_timing = result.timings.per_program_timings.get(&program_id).unwrap();
+    assert_eq!(caller_timing.count, 1);
+    assert_eq!(caller_timing.accumulated_units, 2279);
+    assert_eq!(result.execution_time, result.timings.execute_us);
+    assert_eq!(result.max_stack_depth, 2);
+
+    // The CPI is recorded as an inner instruction.
+    assert_eq!(
+        result.inner_instructions,
+        vec![InnerInstruction {
+            instruction: Instruction::new_with_bytes(
+                cpi_target_program_id,
+                data,
+                vec![AccountMeta::new(key, true)],
+            ),
+            stack_height: 2,
+        }]
+    );
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &[
+            (key, account.clone()),
+            (
+                cpi_target_program_id,
+                program_account(&cpi_target_program_id),
+            ),
+        ],
+        &[Check::cpi_to(&cpi_target_program_id), Check::cpi_count(1)],
+    );
+    let failures = mollusk
+        .try_process_and_validate_instruction(
+            &instruction,
+            &[
+                (key, account.clone()),
+                (
+                    cpi_target_program_id,
+                    program_account(&cpi_target_program_id),
+                ),
+            ],
+            &[Check::cpi_to(&program_id), Check::cpi_count(2)],
+        )
+        .unwrap_err();
+    assert_eq!(
+        failures,
+        vec![
+            CheckFailure {
+                check: format!("cpi to {}", program_id),
+                expected: "invoked".to_string(),
+                actual: "not invoked".to_string(),
+            },
+            CheckFailure {
+                check: "cpi count".to_string(),
+                expected: "2".to_string(),
+                actual: "1".to_string(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_compute_budget_exceeded() {
+    std::env::set_var("SBF_OUT_DIR", "../target/deploy");
+
+    let program_id = Pubkey::new_unique();
+
+    let mollusk = Mollusk::new(&program_id, "test_program_primary");
+
+    let data = &[1, 2, 3, 4, 5];
+    let key = Pubkey::new_unique();
+    let account = mollusk.rent_exempt_account(&program_id, data.len());
+
+    let instruction = {
+        let mut instruction_data = vec![1];
+        instruction_data.extend_from_slice(data);
+        Instruction::new_with_bytes(
+            program_id,
+            &instruction_data,
+            vec![AccountMeta::new(key, true)],
+        )
+    };
+
+    // The BPF Loader reports the exhausted budget as a failure to complete.
+    {
+        let mut mollusk = mollusk.clone();
+        mollusk.set_compute_unit_limit(100);
+
+        let result = mollusk.process_and_validate_instruction(
+            &instruction,
+            &[(key, account.clone())],
+            &[Check::compute_budget_exceeded()],
+        );
+        assert_eq!(
+            result.instruction_error(),
+            Some(&InstructionError::ProgramFailedToComplete)
+        );
+    }
+
+    // Still detected once the runtime's logs are truncated.
+    let mut data = vec![14];
+    data.extend_from_slice(&u32::MAX.to_le_bytes());
+    let instruction = Instruction::new_with_bytes(program_id, &data, vec![]);
+
+    let result = mollusk.process_and_validate_instruction(
+        &instruction,
+        &[],
+        &[Check::compute_budget_exceeded()],
+    );
+    assert_eq!(result.logs.last().unwrap(), "Log truncated");
+
+    // As on-chain, a requested limit is capped at the maximum.
+    let result = mollusk.process_instruction_chain(
+        &[
+            ComputeBudgetInstruction::set_compute_unit_limit(1_500_000),
+            instruction,
+        ],
+        &[],
+    );
+    assert!(result.compute_budget_exceeded);
+    assert_eq!(result.compute_units_consumed, 1_400_000);
+}
+
+#[test]
+fn test_compute_units_per_program_truncated_logs() {
+    std::env::set_var("SBF_OUT_DIR", "../target/deploy");
+
+    let program_id = Pubkey::new_unique();
+
+    let mollusk = Mollusk::new(&program_id, "test_program_primary");
+
+    // Over 10KB of logs, so the runtime's logs are truncated before the
+    // program's consumption is reported.
+    let mut data = vec![14];
+    data.extend_from_slice(&200u32.to_le_bytes());
+    let instruction = Instruction::new_with_bytes(program_id, &data, vec![]);
+
+    let result = mollusk.process_and_validate_instruction(&instruction, &[], &[Check::success()]);
+    assert_eq!(result.logs.last().unwrap(), "Log truncated");
+    assert_eq!(
+        result.compute_units_per_program,
+        vec![(program_id, result.compute_units_consumed)]
+    );
+
+    let timing = result.timings.per_program_timings.get(&program_id).unwrap();
+    assert_eq!(timing.count, 1);
+    assert_eq!(timing.accumulated_units, result.compute_units_consumed);
+}
+
+#[test]
+fn test_realloc() {
+    std::env::set_var("SBF_OUT_DIR", "../target/deploy");
+
+    let program_id = Pubkey::new_unique();
+
+    let mollusk = Mollusk::new(&program_id, "test_program_primary");
+
+    let key = Pubkey::new_unique();
+    let data = [7u8; 8];
+    let mut account = AccountSharedData::new(1_000_000_000, data.len(), &program_id);
+    account.set_data_from_slice(&data);
+
+    let realloc = |new_len: usize| {
+        let mut instruction_data = vec![10];
+        instruction_data.extend_from_slice(&(new_len as u32).to_le_bytes());
+        Instruction::new_with_bytes(
+            program_id,
+            &instruction_data,
+            vec![AccountMeta::new(key, false)],
+        )
+    };
+
+    // Grow by the maximum permitted in a single instruction. The extension
+    // is zeroed.
+    let new_len = data.len() + MAX_PERMITTED_DATA_INCREASE;
+    let mut expected_data = data.to_vec();
+    expected_data.resize(new_len, 0);
+    mollusk.process_and_validate_instruction(
+        &realloc(new_len),
+        &[(key, account.clone())],
+        &[
+            Check::success(),
+            Check::account(&key)
+                .data(&expected_data)
+                .space(new_len)
+                .build(),
+        ],
+    );
+
+    // Growing any further fails, leaving the account untouched.
+    mollusk.process_and_validate_instruction(
+        &realloc(new_len + 1),
+        &[(key, account.clone())],
+        &[
+            Check::err(ProgramError::InvalidRealloc),
+            Check::account(&key).data(&data).build(),
+        ],
+    );
+
+    // Shrinking is unrestricted.
+    mollusk.process_and_validate_instruction(
+        &realloc(2),
+        &[(key, account.clone())],
+        &[
+            Check::success(),
+            Check::account(&key).data(&data[..2]).build(),
+        ],
+    );
+
+    // The loader enforces the limit itself, for a program that sets the
+    // length without `AccountInfo::realloc`.
+    let set_len = |new_len: usize| {
+        let mut instruction_data = vec![15];
+        instruction_data.extend_from_slice(&(new_len as u32).to_le_bytes());
+        Instruction::new_with_bytes(
+            program_id,
+            &instruction_data,
+            vec![AccountMeta::new(key, false)],
+        )
+    };
+    mollusk.process_and_validate_instruction(
+        &set_len(new_len),
+        &[(key, account.clone())],
+        &[
+            Check::success(),
+            Check::account(&key).space(new_len).build(),
+        ],
+    );
+    mollusk.process_and_validate_instruction(
+        &set_len(new_len + 1),
+        &[(key, account)],
+        &[
+            Check::instruction_err(InstructionError::InvalidRealloc),
+            Check::account(&key).data(&data).build(),
+        ],
+    );
+}
+
+#[test]
+fn test_realloc_beyond_max_data_length() {
+    std::env::set_var("SBF_OUT_DIR", "../target/deploy");
+
+    let program_id = Pubkey::new_unique();
+
+    let mollusk = Mollusk::new(&program_id, "test_program_primary");
+
+    // An account just short of the limit, which a single realloc could
+    // otherwise grow past it.
+    let key = Pubkey::new_unique();
+    let len = MAX_PERMITTED_DATA_LENGTH as usize - 4;
+    let account = AccountSharedData::new(1_000_000_000, len, &program_id);
+
+    let realloc = |new_len: usize| {
+        let mut instruction_data = vec![10];
+        instruction_data.extend_from_slice(&(new_len as u32).to_le_bytes());
+        Instruction::new_with_bytes(
+            program_id,
+            &instruction_data,
+            vec![AccountMeta::new(key, false)],
+        )
+    };
+
+    // Growing to the limit succeeds.
+    mollusk.process_and_validate_instruction(
+        &realloc(MAX_PERMITTED_DATA_LENGTH as usize),
+        &[(key, account.clone())],
+        &[
+            Check::success(),
+            Check::account(&key)
+                .space(MAX_PERMITTED_DATA_LENGTH as usize)
+                .build(),
+        ],
+    );
+
+    // Growing past it fails with the runtime's error, leaving the account
+    // untouched.
+    mollusk.process_and_validate_instruction(
+        &realloc(MAX_PERMITTED_DATA_LENGTH as usize + 4),
+        &[(key, account)],
+        &[
+            Check::err(ProgramError::InvalidRealloc),
+            Check::account(&key).space(len).build(),
+        ],
+    );
+}
+
+#[test]
+fn test_cpi_create_account() {
+    std::env::set_var("SBF_OUT_DIR", "../target/deploy");
+
+    let program_id = Pubkey::new_unique();
+
+    let mollusk = Mollusk::new(&program_id, "test_program_primary");
+
+    let payer = Pubkey::new_unique();
+    let new_account = Pubkey::new_unique();
+
+    let space = 16;
+    let lamports = mollusk.sysvars.rent.minimum_balance(space);
+
+    let instruction = {
+        let mut instruction_data = vec![11];
+        instruction_data.extend_from_slice(&(space as u32).to_le_bytes());
+        Instruction::new_with_bytes(
+            program_id,
+            &instruction_data,
+            vec![
+                AccountMeta::new(payer, true),
+                AccountMeta::new(new_account, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        )
+    };
+
+    // The System program assigns the new account to the caller, and the
+    // owner change is reflected in the resulting accounts.
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &[
+            (
+                payer,
+                AccountSharedData::new(1_000_000_000, 0, &system_program::id()),
+            ),
+            (new_account, AccountSharedData::default()),
+            system_program(),
+        ],
+        &[
+            Check::success(),
+            Check::cpi_to(&system_program::id()),
+            Check::account_owner(&new_account, &program_id),
+            Check::account(&new_account)
+                .lamports(lamports)
+                .space(space)
+                .build(),
+            Check::account_lamports_delta(&payer, -(lamports as i128)),
+        ],
+    );
+}
+
+#[test]
+fn test_return_data() {
+    std::env::set_var("SBF_OUT_DIR", "../target/deploy");
+
+    let program_id = Pubkey::new_unique();
+
+    let mollusk = Mollusk::new(&program_id, "test_program_primary");
+
+    let return_data = &[5, 4, 3, 2, 1];
+
+    let instruction = {
+        let mut instruction_data = vec![5];
+        instruction_data.extend_from_slice(return_data);
+        Instruction::new_with_bytes(program_id, &instruction_data, vec![])
+    };
+
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &[],
+        &[
+            Check::success(),
+            Check::return_data(return_data),
+            Check::return_data_len(return_data.len()),
+        ],
+    );
+}
+
+#[test]
+fn test_heap_usage() {
+    std::env::set_var("SBF_OUT_DIR", "../target/deploy");
+
+    let program_id = Pubkey::new_unique();
+
+    let mut mollusk = Mollusk::new(&program_id, "test_program_primary");
+
+    let allocate = |len: u32| {
+        let mut instruction_data = vec![8];
+        instruction_data.extend_from_slice(&len.to_le_bytes());
+        Instruction::new_with_bytes(program_id, &instruction_data, vec![])
+    };
+
+    let result = mollusk.process_instruction(&allocate(1024), &[]);
+    assert_eq!(result.program_result, ProgramResult::Success);
+    assert!(!result.allocation_failed);
+    assert_eq!(result.heap_size, mollusk.compute_budget.heap_size);
+    assert_eq!(result.max_stack_depth, 1);
+
+    // The default heap is 32 KiB.
+    let result = mollusk.process_instruction(&allocate(64 * 1024), &[]);
+    assert_ne!(result.program_result, ProgramResult::Success);
+    assert!(result.allocation_failed);
+
+    mollusk.set_heap_size(128 * 1024);
+    let result = mollusk.process_instruction(&allocate(64 * 1024), &[]);
+    assert_eq!(result.program_result, ProgramResult::Success);
+    assert!(!result.allocation_failed);
+    assert_eq!(result.heap_size, 128 * 1024);
+}
+
+#[test]
+fn test_builder() {
+    std::env::set_var("SBF_OUT_DIR", "../target/deploy");
+
+    let program_id = Pubkey::new_unique();
+
+    let rent = Rent {
+        lamports_per_byte_year: 1,
+        ..Default::default()
+    };
+
+    let mollusk = Mollusk::builder()
+        .program(&program_id, "test_program_primary")
+        .compute_budget(ComputeBudget {
+            compute_unit_limit: 10_000,
+            ..Default::default()
+        })
+        .feature_set(FeatureSet::all_enabled())
+        .rent(rent.clone())
+        .clock_slot(1_000)
+        .build();
+
+    assert_eq!(mollusk.program_id, program_id);
+    assert_eq!(mollusk.compute_budget.compute_unit_limit, 10_000);
+    assert_eq!(mollusk.sysvars.rent, rent);
+    assert_eq!(mollusk.sysvars.clock.slot, 1_000);
+
+    let instruction = Instruction::new_with_bytes(program_id, &[0], vec![]);
+    mollusk.process_and_validate_instruction(&instruction, &[], &[Check::success()]);
+}
+
+#[test]
+fn test_remove_program() {
+    std::env::set_var("SBF_OUT_DIR", "../target/deploy");
+
+    let program_id = Pubkey::new_unique();
+    let cpi_target_program_id = Pubkey::new_unique();
+
+    let mut mollusk = Mollusk::new(&program_id, "test_program_primary");
+    mollusk.add_program(&cpi_target_program_id, "test_program_cpi_target");
+
+    let data = &[1, 2, 3, 4, 5];
+    let space = data.len();
+    let lamports = mollusk.sysvars.rent.minimum_balance(space);
+
+    let key = Pubkey::new_unique();
+    let account = AccountSharedData::new(lamports, space, &cpi_target_program_id);
+
+    let instruction = {
+        let mut instruction_data = vec![4];
+        instruction_data.extend_from_slice(cpi_target_program_id.as_ref());
+        instruction_data.extend_from_slice(data);
+        Instruction::new_with_bytes(
+            program_id,
+            &instruction_data,
+            vec![
+                AccountMeta::new(key, true),
+                AccountMeta::new_readonly(cpi_target_program_id, false),
+            ],
+        )
+    };
+    let accounts = [
+        (key, account.clone()),
+        (
+            cpi_target_program_id,
+            program_account(&cpi_target_program_id),
+        ),
+    ];
+
+    mollusk.process_and_validate_instruction(&instruction, &accounts, &[Check::success()]);
+
+    // Once removed, the CPI target behaves as if it was never added.
+    mollusk.remove_program(&cpi_target_program_id);
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &accounts,
+        &[
+            Check::err(ProgramError::InvalidAccountData),
+            Check::log("Program is not cached"),
+        ],
+    );
+
+    // Removing the program under test falls back to the System program.
+    mollusk.remove_program(&program_id);
+    assert_eq!(mollusk.program_id, system_program::id());
+}
+
+#[test]
+fn test_clone() {
+    std::env::set_var("SBF_OUT_DIR", "../target/deploy");
+
+    let program_id = Pubkey::new_unique();
+    let cpi_target_program_id = Pubkey::new_unique();
+
+    let mollusk = Mollusk::new(&program_id, "test_program_primary");
+
+    let data = &[1, 2, 3, 4, 5];
+    let space = data.len();
+    let lamports = mollusk.sysvars.rent.minimum_balance(space);
+
+    let key = Pubkey::new_unique();
+    let account = AccountSharedData::new(lamports, space, &cpi_target_program_id);
+
+    let instruction = {
+        let mut instruction_data = vec![4];
+        instruction_data.extend_from_slice(cpi_target_program_id.as_ref());
+        instruction_data.extend_from_slice(data);
+        Instruction::new_with_bytes(
+            program_id,
+            &instruction_data,
+            vec![
+                AccountMeta::new(key, true),
+                AccountMeta::new_readonly(cpi_target_program_id, false),
+            ],
+        )
+    };
+    let accounts = [
+        (key, account),
+        (
+            cpi_target_program_id,
+            program_account(&cpi_target_program_id),
+        ),
+    ];
+
+    // A program added to a clone is not visible to the original.
+    let mut clone = mollusk.clone();
+    clone.add_program(&cpi_target_program_id, "test_program_cpi_target");
+    clone.process_and_validate_instruction(&instruction, &accounts, &[Check::success()]);
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &accounts,
+        &[
+            Check::err(ProgramError::InvalidAccountData),
+            Check::log("Program is not cached"),
+        ],
+    );
+
+    // Clones can execute instructions on other threads.
+    let handles = (0..4u8)
+        .map(|i| {
+            let mollusk = clone.clone();
+            let instruction = Instruction::new_with_bytes(program_id, &[5, i], vec![]);
+            std::thread::spawn(move || {
+                mollusk.process_and_validate_instruction(
+                    &instruction,
+                    &[],
+                    &[Check::success(), Check::return_data(&[i])],
+                );
+            })
+        })
+        .collect::<Vec<_>>();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
+#[test]
+fn test_clone_with_program() {
+    std::env::set_var("SBF_OUT_DIR", "../target/deploy");
+
+    let program_id = Pubkey::new_unique();
+    let cpi_target_program_id = Pubkey::new_unique();
+
+    let mut mollusk = Mollusk::new(&program_id, "test_program_primary");
+    mollusk.add_program(&cpi_target_program_id, "test_program_cpi_target");
+    mollusk.set_compute_unit_limit(50_000);
+    mollusk.sysvars.warp_to_slot(1_000);
+
+    // The configuration is copied, but not the other programs.
+    let other_program_id = Pubkey::new_unique();
+    let other = mollusk.clone_with_program(
+        &other_program_id,
+        &file::load_program_elf("test_program_primary"),
+    );
+    assert_eq!(other.program_id, other_program_id);
+    assert_eq!(other.compute_budget.compute_unit_limit, 50_000);
+    assert_eq!(other.sysvars.clock, mollusk.sysvars.clock);
+    assert!(other.program_cache.contains(&other_program_id));
+    assert!(!other.program_cache.contains(&program_id));
+    assert!(!other.program_cache.contains(&cpi_target_program_id));
+
+    // The same build consumes the same compute units under either harness.
+    let result = mollusk.process_instruction(
+        &Instruction::new_with_bytes(program_id, &[5, 1, 2, 3], vec![]),
+        &[],
+    );
+    other.process_and_validate_instruction(
+        &Instruction::new_with_bytes(other_program_id, &[5, 1, 2, 3], vec![]),
+        &[],
+        &[
+            Check::success(),
+            Check::return_data(&[1, 2, 3]),
+            Check::compute_units(result.compute_units_consumed),
+        ],
+    );
+}
+
+#[test]
+fn test_upgrade_program() {
+    std::env::set_var("SBF_OUT_DIR", "../target/deploy");
+
+    let program_id = Pubkey::new_unique();
+
+    let mut mollusk = Mollusk::new(&program_id, "test_program_primary");
+
+    let instruction = Instruction::new_with_bytes(program_id, &[5, 1, 2, 3], vec![]);
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &[],
+        &[Check::success(), Check::return_data(&[1, 2, 3])],
+    );
+
+    // Upgrade to the CPI target program, which expects a signer account.
+    mollusk.warp_to_slot(100);
+    mollusk.upgrade_program(
+        &program_id,
+        &file::load_program_elf("test_program_cpi_target"),
+    );
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &[],
+        &[Check::err(ProgramError::NotEnoughAccountKeys)],
+    );
+
+    let key = Pubkey::new_unique();
+    mollusk.process_and_validate_instruction(
+        &Instruction::new_with_bytes(program_id, &[5, 1, 2], vec![AccountMeta::new(key, true)]),
+        &[(key, AccountSharedData::new(1_000_000, 3, &program_id))],
+        &[
+            Check::success(),
+            Check::account(&key).data(&[5, 1, 2]).build(),
+        ],
+    );
+}
+
+declare_builtin_function!(
+    /// A `sol_set_return_data` stub which always fails.
+    SyscallSetReturnDataFailure,
+    fn rust(
+        _invoke_context: &mut InvokeContext,
+        _addr: u64,
+        _len: u64,
+        _arg3: u64,
+        _arg4: u64,
+        _arg5: u64,
+        _memory_mapping: &mut MemoryMapping,
+    ) -> Result<u64, Box<dyn std::error::Error>> {
+        Err(Box::new(InstructionError::Custom(42)))
+    }
+);
+
+#[test]
+fn test_syscall_overrides() {
+    std::env::set_var("SBF_OUT_DIR", "../target/deploy");
+
+    let program_id = Pubkey::new_unique();
+
+    let mut mollusk = Mollusk::default();
+    mollusk.program_id = program_id;
+    mollusk.program_account = program_account(&program_id);
+    mollusk.add_program_with_syscalls(
+        &program_id,
+        "test_program_primary",
+        SyscallOverrides::default().set("sol_set_return_data", SyscallSetReturnDataFailure::vm),
+    );
+
+    let instruction = Instruction::new_with_bytes(program_id, &[5, 1, 2, 3], vec![]);
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &[],
+        &[Check::custom_err(42), Check::return_data(&[])],
+    );
+
+    // Overrides are kept when the program is recompiled.
+    mollusk.deactivate_feature(&feature_set::bpf_account_data_direct_mapping::id());
+    mollusk.process_and_validate_instruction(&instruction, &[], &[Check::custom_err(42)]);
+
+    // An override whose name hashes to the same key as another syscall fails
+    // to register. `sol_log_hqz4seb` collides with `sol_log_`.
+    let elf = file::load_program_elf("test_program_primary");
+    assert!(matches!(
+        mollusk.program_cache.try_add_program_with_syscalls(
+            &program_id,
+            &bpf_loader_upgradeable::id(),
+            &elf,
+            &mollusk.compute_budget,
+            &mollusk.feature_set,
+            SyscallOverrides::default().set("sol_log_hqz4seb", SyscallSetReturnDataFailure::vm),
+        ),
+        Err(MolluskError::InvalidEnvironment { error, .. }) if error.contains("sol_log_hqz4seb")
+    ));
+}
+
+#[test]
+fn test_feature_gating() {
+    std::env::set_var("SBF_OUT_DIR", "../target/deploy");
+
+    let program_id = Pubkey::new_unique();
+    let feature_id = feature_set::bpf_account_data_direct_mapping::id();
+
+    let mut mollusk = Mollusk::new(&program_id, "test_program_primary");
+    assert!(mollusk.feature_set.is_active(&feature_id));
+
+    // Write an account's existing data back to it, through a read-only meta.
+    let data = &[1, 2, 3, 4, 5];
+    let key = Pubkey::new_unique();
+    let mut account = AccountSharedData::new(1_000_000, data.len(), &program_id);
+    account.set_data_from_slice(data);
+
+    let instruction = {
+        let mut instruction_data = vec![1];
+        instruction_data.extend_from_slice(data);
+        Instruction::new_with_bytes(
+            program_id,
+            &instruction_data,
+            vec![AccountMeta::new_readonly(key, true)],
+        )
+    };
+    let accounts = [(key, account)];
+
+    // With direct mapping, the account's data is mapped read-only, so the
+    // write itself faults.
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &accounts,
+        &[Check::instruction_err(
+            InstructionError::ReadonlyDataModified,
+        )],
+    );
+
+    // Without it, the program writes to a copy of the data, which is only
+    // rejected if it changed. The program is recompiled against the new
+    // feature set.
+    mollusk.deactivate_feature(&feature_id);
+    assert!(!mollusk.feature_set.is_active(&feature_id));
+    mollusk.process_and_validate_instruction(&instruction, &accounts, &[Check::success()]);
+
+    mollusk.activate_feature(&feature_id);
+    assert!(mollusk.feature_set.is_active(&feature_id));
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &accounts,
+        &[Check::instruction_err(
+            InstructionError::ReadonlyDataModified,
+        )],
+    );
+}
+
+fn clock_return_data(slot: u64, unix_timestamp: i64) -> Vec<u8> {
+    let mut data = slot.to_le_bytes().to_vec();
+    data.extend_from_slice(&unix_timestamp.to_le_bytes());
+    data
+}
+
+#[test]
+fn test_sysvar_overrides() {
+    std::env::set_var("SBF_OUT_DIR", "../target/deploy");
+
+    let program_id = Pubkey::new_unique();
+
+    let mollusk = Mollusk::new(&program_id, "test_program_primary");
+
+    let instruction = Instruction::new_with_bytes(program_id, &[6], vec![]);
+
+    // Warping past the first epoch advances the timestamp at 400ms per slot.
+    for (slot, unix_timestamp) in [(1, 0), (100, 40), (10_000, 4_000)] {
+        let mut sysvars = Sysvars::default();
+        sysvars.warp_to_slot(slot);
+
+        let result = mollusk.process_instruction_with_sysvars(&instruction, &[], &sysvars);
+        assert_eq!(result.return_data, clock_return_data(slot, unix_timestamp));
+    }
+
+    // The harness's own sysvars are untouched.
+    assert_eq!(mollusk.sysvars.clock.slot, 0);
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &[],
+        &[Check::return_data(&clock_return_data(0, 0))],
+    );
+}
+
+#[test]
+fn test_set_sysvar_cache() {
+    std::env::set_var("SBF_OUT_DIR", "../target/deploy");
+
+    let program_id = Pubkey::new_unique();
+
+    let mut mollusk = Mollusk::new(&program_id, "test_program_primary");
+
+    let instruction = Instruction::new_with_bytes(program_id, &[6], vec![]);
+
+    let mut sysvars = Sysvars::default();
+    sysvars.warp_to_slot(42);
+    mollusk.set_sysvar_cache(SysvarCache::from(&sysvars));
+
+    // The cache is used in place of the harness's own sysvars.
+    assert_eq!(mollusk.sysvars.clock.slot, 0);
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &[],
+        &[Check::return_data(&clock_return_data(42, 16))],
+    );
+
+    // Changes to the harness's sysvars aren't visible while it's set.
+    mollusk.warp_to_slot(7);
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &[],
+        &[Check::return_data(&clock_return_data(42, 16))],
+    );
+
+    // Sysvars provided for a single call are used as usual.
+    let result = mollusk.process_instruction_with_sysvars(&instruction, &[], &Sysvars::default());
+    assert_eq!(result.return_data, clock_return_data(0, 0));
+
+    // A sysvar missing from the cache is unavailable.
+    mollusk.set_sysvar_cache(SysvarCache::default());
+    assert!(mollusk.process_instruction(&instruction, &[]).is_err());
+
+    mollusk.clear_sysvar_cache();
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &[],
+        &[Check::return_data(&clock_return_data(7, 0))],
+    );
+}
+
+#[test]
+fn test_clock_timestamp() {
+    std::env::set_var("SBF_OUT_DIR", "../target/deploy");
+
+    let program_id = Pubkey::new_unique();
+
+    let mut mollusk = Mollusk::new(&program_id, "test_program_primary");
+    mollusk.warp_to_slot(10);
+
+    let instruction = Instruction::new_with_bytes(program_id, &[6], vec![]);
+
+    mollusk.set_clock_timestamp(1_700_000_000);
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &[],
+        &[Check::return_data(&clock_return_data(10, 1_700_000_000))],
+    );
+
+    mollusk.advance_timestamp(3_600);
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &[],
+        &[Check::return_data(&clock_return_data(10, 1_700_003_600))],
+    );
+
+    mollusk.reset_clock();
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &[],
+        &[Check::return_data(&clock_return_data(0, 0))],
+    );
+
+    mollusk.warp_to_slot(20);
+    mollusk.set_clock_timestamp(1_700_000_000);
+    mollusk.reset_sysvars();
+    assert_eq!(mollusk.sysvars.clock, Sysvars::default().clock);
+    assert_eq!(mollusk.sysvars.slot_hashes, Sysvars::default().slot_hashes);
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &[],
+        &[Check::return_data(&clock_return_data(0, 0))],
+    );
+}
+
+#[test]
+fn test_new_at_slot() {
+    std::env::set_var("SBF_OUT_DIR", "../target/deploy");
+
+    let program_id = Pubkey::new_unique();
+    let slot = 1_000;
+
+    let mollusk = Mollusk::new_at_slot(&program_id, "test_program_primary", slot);
+    assert_eq!(mollusk.sysvars.clock, Sysvars::new_at_slot(slot).clock);
+
+    // 400ms per slot.
+    mollusk.process_and_validate_instruction(
+        &Instruction::new_with_bytes(program_id, &[6], vec![]),
+        &[],
+        &[
+            Check::success(),
+            Check::return_data(&clock_return_data(slot, 400)),
+        ],
+    );
+}
+
+#[test]
+fn test_custom_rent() {
+    std::env::set_var("SBF_OUT_DIR", "../target/deploy");
+
+    let program_id = Pubkey::new_unique();
+    let key = Pubkey::new_unique();
+
+    let mut mollusk = Mollusk::new(&program_id, "test_program_primary");
+    mollusk.sysvars.rent.lamports_per_byte_year *= 2;
+
+    let instruction = Instruction::new_with_bytes(
+        program_id,
+        &[7],
+        vec![AccountMeta::new_readonly(key, false)],
+    );
+
+    // Exempt under the default rent, but not the harness's rent.
+    let lamports = Rent::default().minimum_balance(100);
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &[(key, AccountSharedData::new(lamports, 100, &program_id))],
+        &[Check::err(ProgramError::AccountNotRentExempt)],
+    );
+
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &[(key, mollusk.rent_exempt_account(&program_id, 100))],
+        &[Check::success()],
+    );
+}
+
+#[test]
+fn test_custom_rent_executable() {
+    std::env::set_var("SBF_OUT_DIR", "../target/deploy");
+
+    let payer = Pubkey::new_unique();
+    let program_id = Pubkey::new_unique();
+    let buffer = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+    let (programdata, _) =
+        Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id());
+
+    // Marking an account executable is subject to the runtime's rent, not
+    // the Rent sysvar, so deploying a program funded under a lower rent
+    // only succeeds if the two agree.
+    let mut mollusk = Mollusk::default();
+    mollusk.sysvars.rent.lamports_per_byte_year /= 2;
+
+    let elf = file::load_program_elf("test_program_primary");
+    let program_len = UpgradeableLoaderState::size_of_program();
+    let lamports = mollusk.sysvars.rent.minimum_balance(program_len);
+    assert!(!Rent::default().is_exempt(lamports, program_len));
+
+    let instruction = bpf_loader_upgradeable::deploy_with_max_program_len(
+        &payer,
+        &program_id,
+        &buffer,
+        &authority,
+        lamports,
+        elf.len(),
+    )
+    .unwrap()
+    .remove(1);
+
+    let buffer_account = {
+        let mut data = bincode::serialize(&UpgradeableLoaderState::Buffer {
+            authority_address: Some(authority),
+        })
+        .unwrap();
+        data.extend_from_slice(&elf);
+        mollusk.rent_exempt_account_with_data(&bpf_loader_upgradeable::id(), &data)
+    };
+
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &[
+            (
+                payer,
+                AccountSharedData::new(10_000_000_000, 0, &system_program::id()),
+            ),
+            (programdata, AccountSharedData::default()),
+            (
+                program_id,
+                AccountSharedData::new(lamports, program_len, &bpf_loader_upgradeable::id()),
+            ),
+            (buffer, buffer_account),
+            mollusk.sysvar_account(&solana_sdk::sysvar::rent::id()),
+            mollusk.sysvar_account(&solana_sdk::sysvar::clock::id()),
+            system_program(),
+            (authority, AccountSharedData::default()),
+        ],
+        &[
+            Check::success(),
+            Check::account(&program_id).executable(true).build(),
+        ],
+    );
+}
+
+#[test]
+fn test_other_top_level_programs() {
+    std::env::set_var("SBF_OUT_DIR", "../target/deploy");
+
+    let program_id = Pubkey::new_unique();
+    let other_program_id = Pubkey::new_unique();
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let mut mollusk = Mollusk::new(&program_id, "test_program_primary");
+    mollusk.add_program(&other_program_id, "test_program_primary");
+
+    let base_lamports = 100_000_000u64;
+    let accounts = [
+        (
+            sender,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (
+            recipient,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+    ];
+
+    // A builtin.
+    mollusk.process_and_validate_instruction(
+        &solana_sdk::system_instruction::transfer(&sender, &recipient, 42_000),
+        &accounts,
+        &[
+            Check::success(),
+            Check::account(&recipient)
+                .lamports(base_lamports + 42_000)
+                .build(),
+        ],
+    );
+
+    // Another BPF program.
+    mollusk.process_and_validate_instruction(
+        &Instruction::new_with_bytes(other_program_id, &[5, 1, 2, 3], vec![]),
+        &[],
+        &[Check::success(), Check::return_data(&[1, 2, 3])],
+    );
+
+    // An unknown program.
+    mollusk.process_and_validate_instruction(
+        &Instruction::new_with_bytes(Pubkey::new_unique(), &[0], vec![]),
+        &[],
+        &[Check::instruction_err(
+            InstructionError::UnsupportedProgramId,
+        )],
+    );
+}
+
+#[test]
+fn test_loader_options() {
+    std::env::set_var("SBF_OUT_DIR", "../target/deploy");
+
+    let program_id = Pubkey::new_unique();
+    let elf = file::load_program_elf("test_program_primary");
+
+    let mut mollusk = Mollusk::default();
+    mollusk.program_cache.add_program_with_options(
+        &program_id,
+        &bpf_loader_upgradeable::id(),
+        &elf,
+        &mollusk.compute_budget,
+        &mollusk.feature_set,
+        LoaderOptions {
+            reject_broken_elfs: true,
+            debugging_features: true,
+        },
+    );
+    mollusk.program_id = program_id;
+    mollusk.program_account = program_account(&program_id);
+
+    let instruction = Instruction::new_with_bytes(program_id, &[5, 1, 2, 3], vec![]);
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &[],
+        &[Check::success(), Check::return_data(&[1, 2, 3])],
+    );
+
+    // Programs are recompiled with the options they were added with.
+    mollusk.deactivate_feature(&feature_set::bpf_account_data_direct_mapping::id());
+    mollusk.process_and_validate_instruction(&instruction, &[], &[Check::success()]);
+
+    // An ELF calling a syscall that doesn't exist, made by renaming
+    // `sol_log_` in its dynamic symbol names, is only rejected when broken
+    // ELFs are.
+    let broken_elf = {
+        let (from, to) = (b"\0sol_log_\0", b"\0sol_lox_\0");
+        let offset = elf
+            .windows(from.len())
+            .position(|window| window == from)
+            .unwrap();
+        let mut broken_elf = elf.clone();
+        broken_elf[offset..offset + to.len()].copy_from_slice(to);
+        broken_elf
+    };
+    let add_broken_program = |mollusk: &mut Mollusk, reject_broken_elfs| {
+        let (compute_budget, feature_set) = (mollusk.compute_budget, mollusk.feature_set.clone());
+        mollusk.program_cache.try_add_program_with_options(
+            &program_id,
+            &bpf_loader_upgradeable::id(),
+            &broken_elf,
+            &compute_budget,
+            &feature_set,
+            LoaderOptions {
+                reject_broken_elfs,
+                ..LoaderOptions::default()
+            },
+        )
+    };
+    assert!(matches!(
+        add_broken_program(&mut mollusk, true),
+        Err(MolluskError::InvalidElf { error, .. }) if error.contains("sol_lox_")
+    ));
+    add_broken_program(&mut mollusk, false).unwrap();
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &[],
+        &[Check::success(), Check::return_data(&[1, 2, 3])],
+    );
+}
+
+#[test]
+fn test_instructions_sysvar() {
+    std::env::set_var("SBF_OUT_DIR", "../target/deploy");
+
+    let program_id = Pubkey::new_unique();
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let mollusk = Mollusk::new(&program_id, "test_program_primary");
+
+    let instruction = Instruction::new_with_bytes(
+        program_id,
+        &[9],
+        vec![AccountMeta::new_readonly(
+            solana_sdk::sysvar::instructions::id(),
+            false,
+        )],
+    );
+    let introspection_return_data = |index: u16, first_program_id: &Pubkey| {
+        let mut data = index.to_le_bytes().to_vec();
+        data.extend_from_slice(first_program_id.as_ref());
+        data
+    };
+
+    // A single instruction sees only itself.
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &[(
+            solana_sdk::sysvar::instructions::id(),
+            AccountSharedData::default(),
+        )],
+        &[
+            Check::success(),
+            Check::return_data(&introspection_return_data(0, &program_id)),
+        ],
+    );
+
+    // In a chain, it sees its siblings.
+    let base_lamports = 100_000_000u64;
+    let result = mollusk.process_instruction_chain(
+        &[
+            solana_sdk::system_instruction::transfer(&sender, &recipient, 42_000),
+            instruction,
+        ],
+        &[
+            (
+                sender,
+                AccountSharedData::new(base_lamports, 0, &system_program::id()),
+            ),
+            (
+                recipient,
+                AccountSharedData::new(base_lamports, 0, &system_program::id()),
+            ),
+        ],
+    );
+    assert_eq!(result.program_result, ProgramResult::Success);
+    assert_eq!(
+        result.return_data,
+        introspection_return_data(1, &system_program::id())
+    );
+}
+
+#[test]
+fn test_loader_v4() {
+    std::env::set_var("SBF_OUT_DIR", "../target/deploy");
+
+    let program_id = Pubkey::new_unique();
+    let elf = file::load_program_elf("test_program_primary");
+
+    let mut mollusk = Mollusk::default();
+    mollusk.add_program_with_elf(&program_id, &loader_v4::id(), &elf);
+    mollusk.program_id = program_id;
+    mollusk.program_account =
+        program_account_loader_v4(&elf, Pubkey::new_unique(), LoaderV4Status::Deployed);
+
+    let return_data = &[1, 2, 3];
+
+    let instruction = {
+        let mut instruction_data = vec![5];
+        instruction_data.extend_from_slice(return_data);
+        Instruction::new_with_bytes(program_id, &instruction_data, vec![])
+    };
+
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &[],
+        &[Check::success(), Check::return_data(return_data)],
+    );
+}
+
+#[test]
+fn test_process_instruction_at_stack_height() {
+    std::env::set_var("SBF_OUT_DIR", "../target/deploy");
+
+    let program_id = Pubkey::new_unique();
+
+    let mollusk = Mollusk::new(&program_id, "test_program_primary");
+
+    let key = Pubkey::new_unique();
+    let instruction =
+        Instruction::new_with_bytes(program_id, &[13], vec![AccountMeta::new(key, false)]);
+    let accounts = [(key, AccountSharedData::new(1_000_000, 0, &program_id))];
+
+    for height in 1..=mollusk.compute_budget.max_instruction_stack_depth {
+        let result = mollusk.process_instruction_at_stack_height(&instruction, &accounts, height);
+        assert_eq!(result.program_result, ProgramResult::Success);
+        assert_eq!(result.return_data, (height as u64).to_le_bytes());
+        assert_eq!(result.max_stack_depth, height);
+        assert!(result.inner_instructions.is_empty());
+        assert_eq!(result.resulting_accounts, accounts);
+    }
+}
+
+#[test]
+#[should_panic(expected = "outside the permitted range")]
+fn test_process_instruction_at_stack_height_too_deep() {
+    std::env::set_var("SBF_OUT_DIR", "../target/deploy");
+
+    let program_id = Pubkey::new_unique();
+
+    let mollusk = Mollusk::new(&program_id, "test_program_primary");
+
+    let instruction = Instruction::new_with_bytes(program_id, &[13], vec![]);
+    let height = mollusk.compute_budget.max_instruction_stack_depth + 1;
+    mollusk.process_instruction_at_stack_height(&instruction, &[], height);
+}
+
+#[test]
+fn test_add_program_bytes() {
+    let program_id = Pubkey::new_unique();
+    let elf = std::fs::read("../target/deploy/test_program_primary.so").unwrap();
+
+    let mut mollusk = Mollusk::default();
+    mollusk.add_program_bytes(&program_id, &elf);
+
+    let return_data = &[4, 5, 6];
+
+    let instruction = {
+        let mut instruction_data = vec![5];
+        instruction_data.extend_from_slice(return_data);
+        Instruction::new_with_bytes(program_id, &instruction_data, vec![])
+    };
+
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &[],
+        &[Check::success(), Check::return_data(return_data)],
+    );
+}
+
+#[cfg(feature = "anchor")]
+#[test]
+fn test_anchor_events() {
+    use anchor_lang::{event, AnchorDeserialize, AnchorSerialize, Discriminator, Event};
+
+    #[event]
+    #[derive(Debug, PartialEq)]
+    struct Deposit {
+        amount: u64,
+    }
+
+    #[event]
+    #[derive(Debug, PartialEq)]
+    struct Withdrawal {
+        amount: u64,
+    }
+
+    std::env::set_var("SBF_OUT_DIR", "../target/deploy");
+
+    let program_id = Pubkey::new_unique();
+
+    let mollusk = Mollusk::new(&program_id, "test_program_primary");
+
+    // The discriminator is Anchor's own.
+    assert_eq!(
+        Deposit::DISCRIMINATOR,
+        mollusk_svm::result::anchor_event_discriminator("Deposit")
+    );
+
+    // Log an event as Anchor's `emit!` would.
+    let emit = |event: &Deposit| {
+        let mut instruction_data = vec![12];
+        instruction_data.extend_from_slice(&event.data());
+        Instruction::new_with_bytes(program_id, &instruction_data, vec![])
+    };
+
+    let event = Deposit { amount: 42 };
+    let result = mollusk.process_and_validate_instruction(
+        &emit(&event),
+        &[],
+        &[Check::success(), Check::emitted_event(&event)],
+    );
+    assert_eq!(
+        result.anchor_events::<Deposit>(),
+        vec![Deposit { amount: 42 }]
+    );
+    assert_eq!(result.anchor_events::<Withdrawal>(), vec![]);
+
+    let failures = mollusk
+        .try_process_and_validate_instruction(
+            &emit(&event),
+            &[],
+            &[
+                Check::emitted_event(&Deposit { amount: 7 }),
+                Check::emitted_event(&Withdrawal { amount: 42 }),
+            ],
+        )
+        .unwrap_err();
+    assert_eq!(failures.len(), 2);
+    assert_eq!(failures[0].check, "anchor event Deposit");
+    assert_eq!(failures[1].check, "anchor event Withdrawal");
+    assert_eq!(failures[1].actual, "[]");
+}
+
+#[test]
+fn test_add_program_from_accounts() {
+    std::env::set_var("SBF_OUT_DIR", "../target/deploy");
+
+    let program_id = Pubkey::new_unique();
+    let elf = file::load_program_elf("test_program_primary");
+    let (program_account, programdata_account) = program_accounts(&program_id, &elf);
+
+    let mut mollusk = Mollusk::default();
+    mollusk.add_program_from_accounts(&program_id, &program_account, &programdata_account);
+    mollusk.program_id = program_id;
+    mollusk.program_account = program_account;
+
+    let return_data = &[7, 8, 9];
+
+    let instruction = {
+        let mut instruction_data = vec![5];
+        instruction_data.extend_from_slice(return_data);
+        Instruction::new_with_bytes(program_id, &instruction_data, vec![])
+    };
+
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &[],
+        &[Check::success(), Check::return_data(return_data)],
+    );
+}
+
+#[test]
+fn test_missing_elf() {
+    let program_id = Pubkey::new_unique();
+
+    let err = file::try_load_program_elf("does_not_exist").unwrap_err();
+    let FileError::NotFound {
+        file_name,
+        searched_dirs,
+    } = &err
+    else {
+        panic!("Expected FileError::NotFound, got {:?}", err);
+    };
+    assert_eq!(file_name, "does_not_exist.so");
+    assert!(!searched_dirs.is_empty());
+
+    let message = err.to_string();
+    for dir in searched_dirs {
+        assert!(message.contains(&dir.display().to_string()));
+    }
+
+    let err = Mollusk::try_new(&program_id, "does_not_exist")
+        .err()
+        .unwrap();
+    assert!(matches!(
+        err,
+        MolluskError::File(FileError::NotFound { .. })
+    ));
+
+    let err = Mollusk::builder()
+        .program(&program_id, "does_not_exist")
+        .try_build()
+        .err()
+        .unwrap();
+    assert!(matches!(
+        err,
+        MolluskError::File(FileError::NotFound { .. })
+    ));
+}
+
+#[test]
+fn test_invalid_elf() {
+    let program_id = Pubkey::new_unique();
+
+    let mut mollusk = Mollusk::default();
+    let err = mollusk
+        .try_add_program_with_elf(&program_id, &bpf_loader_upgradeable::id(), &[1, 2, 3, 4])
+        .unwrap_err();
+    let MolluskError::InvalidElf {
+        program_id: invalid_program_id,
+        error,
+    } = &err
+    else {
+        panic!("Expected MolluskError::InvalidElf, got {:?}", err);
+    };
+    assert_eq!(invalid_program_id, &program_id);
+    assert!(!error.is_empty());
+    assert!(err.to_string().contains(&program_id.to_string()));
+    assert!(!mollusk.program_cache.contains(&program_id));
+}
+
+#[test]
+#[should_panic(expected = "Invalid ELF for program")]
+fn test_invalid_elf_panics() {
+    Mollusk::default().add_program_with_elf(
+        &Pubkey::new_unique(),
+        &bpf_loader_upgradeable::id(),
+        &[1, 2, 3, 4],
+    );
+}
+
+#[test]
+fn test_search_paths() {
+    let program_id = Pubkey::new_unique();
+
+    // The ELF is copied under a unique name into a directory of its own, so
+    // it can't be found through `SBF_OUT_DIR` or any other default path.
+    let search_path = std::env::temp_dir().join(format!("mollusk_search_path_{}", program_id));
+    std::fs::create_dir_all(&search_path).unwrap();
+    let program_name: &'static str = format!("test_program_{}", program_id).leak();
+    std::fs::copy(
+        "../target/deploy/test_program_primary.so",
+        search_path.join(format!("{}.so", program_name)),
+    )
+    .unwrap();
+
+    let mut mollusk = Mollusk::default();
+    mollusk.add_search_path(&search_path);
+    mollusk.add_program(&program_id, program_name);
+    mollusk.program_id = program_id;
+    mollusk.program_account = program_account(&program_id);
+
+    mollusk.process_and_validate_instruction(
+        &Instruction::new_with_bytes(program_id, &[0], vec![]),
+        &[],
+        &[Check::success()],
+    );
+
+    let mollusk = Mollusk::builder()
+        .search_path(&search_path)
+        .program(&program_id, program_name)
+        .build();
+
+    mollusk.process_and_validate_instruction(
+        &Instruction::new_with_bytes(program_id, &[0], vec![]),
+        &[],
+        &[Check::success()],
+    );
+
+    std::fs::remove_dir_all(&search_path).unwrap();
+}
+
+#[test]
+fn test_process_instruction_raw_arbitrary_data() {
+    std::env::set_var("SBF_OUT_DIR", "../target/deploy");
+
+    let program_id = Pubkey::new_unique();
+
+    let mollusk = Mollusk::new(&program_id, "test_program_primary");
+
+    let key = Pubkey::new_unique();
+    let account_metas = [AccountMeta::new(key, true)];
+    let accounts = [(key, AccountSharedData::new(1_000_000, 8, &program_id))];
+
+    // A simple deterministic generator, so failures are reproducible.
+    let mut seed = 0x2545_f491_4f6c_dd1du64;
+    let mut next = || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed
+    };
+
+    for _ in 0..64 {
+        let len = (next() % 48) as usize;
+        let data = (0..len).map(|_| next() as u8).collect::<Vec<_>>();
+
+        let result = mollusk.process_instruction_raw(&program_id, &data, &account_metas, &accounts);
+
+        // Whatever the outcome, the harness returns a result.
+        assert_eq!(result.resulting_accounts.len(), accounts.len());
+    }
+
+    // Unknown instructions are reported through the program result.
+    mollusk.process_and_validate_instruction(
+        &Instruction::new_with_bytes(program_id, &[255], vec![]),
+        &[],
+        &[Check::err(ProgramError::InvalidInstructionData)],
+    );
+}
+
+#[test]
+fn test_account_diffs_data() {
+    std::env::set_var("SBF_OUT_DIR", "../target/deploy");
+
+    let program_id = Pubkey::new_unique();
+
+    let mollusk = Mollusk::new(&program_id, "test_program_primary");
+
+    let key = Pubkey::new_unique();
+    let mut account = AccountSharedData::new(1_000_000, 6, &program_id);
+    account.set_data_from_slice(&[1, 1, 1, 1, 1, 1]);
+    let accounts = [(key, account)];
+
+    let instruction = Instruction::new_with_bytes(
+        program_id,
+        &[1, 1, 2, 2, 1, 3, 3],
+        vec![AccountMeta::new(key, true)],
+    );
+
+    let result = mollusk.process_instruction(&instruction, &accounts);
+    let diffs = result.account_diffs(&accounts);
+    assert_eq!(diffs.len(), 1);
+    assert_eq!(diffs[0].data_changes, vec![1..3, 4..6]);
+    assert!(diffs[0]
+        .to_string()
+        .contains("data [1..3]:\n  - [1, 1]\n  + [2, 2]"));
 }
@@ -0,0 +1,137 @@
+use {
+    mollusk_svm::{
+        context::{AccountStore, MolluskContext},
+        result::{Check, ProgramResult},
+        Mollusk,
+    },
+    solana_sdk::{
+        account::{AccountSharedData, ReadableAccount},
+        pubkey::Pubkey,
+        system_instruction, system_program,
+    },
+    std::{cell::Cell, collections::HashMap},
+};
+
+#[test]
+fn test_transfers_accumulate() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+    let transfer_amount = 42_000u64;
+
+    let mut context = MolluskContext::new(Mollusk::default());
+    context.set_account(
+        &sender,
+        AccountSharedData::new(base_lamports, 0, &system_program::id()),
+    );
+
+    // The recipient is not in the store, so it starts as a default account.
+    context.process_and_validate_instruction(
+        &system_instruction::transfer(&sender, &recipient, transfer_amount),
+        &[
+            Check::success(),
+            Check::account(&sender)
+                .lamports(base_lamports - transfer_amount)
+                .build(),
+            Check::account(&recipient).lamports(transfer_amount).build(),
+        ],
+    );
+
+    // The second transfer sees the state left behind by the first.
+    context.process_and_validate_instruction(
+        &system_instruction::transfer(&sender, &recipient, transfer_amount),
+        &[
+            Check::success(),
+            Check::account(&sender)
+                .lamports(base_lamports - 2 * transfer_amount)
+                .build(),
+            Check::account(&recipient)
+                .lamports(2 * transfer_amount)
+                .build(),
+        ],
+    );
+
+    assert_eq!(
+        context.get_account(&recipient).unwrap().lamports(),
+        2 * transfer_amount
+    );
+}
+
+#[test]
+fn test_failed_instruction_leaves_store_untouched() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+
+    let mut context = MolluskContext::new(Mollusk::default());
+    context.set_account(
+        &sender,
+        AccountSharedData::new(base_lamports, 0, &system_program::id()),
+    );
+
+    // Transfer more than the sender has.
+    let result = context.process_instruction(&system_instruction::transfer(
+        &sender,
+        &recipient,
+        base_lamports + 1,
+    ));
+    assert_ne!(result.program_result, ProgramResult::Success);
+
+    assert_eq!(
+        context.get_account(&sender).unwrap().lamports(),
+        base_lamports
+    );
+    assert!(context.get_account(&recipient).is_none());
+
+    let removed = context.remove_account(&sender).unwrap();
+    assert_eq!(removed.lamports(), base_lamports);
+    assert!(context.get_account(&sender).is_none());
+}
+
+// A store which lazily funds any account it hasn't seen before, counting the
+// accounts it generates.
+#[derive(Default)]
+struct FundedAccountStore {
+    accounts: HashMap<Pubkey, AccountSharedData>,
+    generated: Cell<usize>,
+}
+
+impl AccountStore for FundedAccountStore {
+    fn load(&self, pubkey: &Pubkey) -> Option<AccountSharedData> {
+        self.accounts.get(pubkey).cloned().or_else(|| {
+            self.generated.set(self.generated.get() + 1);
+            Some(AccountSharedData::new(1_000_000, 0, &system_program::id()))
+        })
+    }
+
+    fn store(&mut self, pubkey: Pubkey, account: AccountSharedData) {
+        self.accounts.insert(pubkey, account);
+    }
+}
+
+#[test]
+fn test_custom_account_store() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let mut context =
+        MolluskContext::with_account_store(Mollusk::default(), FundedAccountStore::default());
+
+    let instruction = system_instruction::transfer(&sender, &recipient, 1_000);
+    context.process_and_validate_instruction(&instruction, &[Check::success()]);
+    assert_eq!(context.account_store().generated.get(), 2);
+
+    // Both accounts were written back, so nothing more is generated.
+    context.process_and_validate_instruction(
+        &instruction,
+        &[
+            Check::success(),
+            Check::account(&sender).lamports(998_000).build(),
+            Check::account(&recipient).lamports(1_002_000).build(),
+        ],
+    );
+    assert_eq!(context.account_store().generated.get(), 2);
+    assert_eq!(context.account_store_mut().accounts.len(), 2);
+}
@@ -0,0 +1,134 @@
+use {
+    mollusk_svm::{fixture::Fixture, Mollusk},
+    solana_sdk::{account::AccountSharedData, pubkey::Pubkey, system_instruction, system_program},
+};
+
+#[test]
+fn test_fixture_round_trip() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+    let transfer_amount = 42_000u64;
+
+    let instruction = system_instruction::transfer(&sender, &recipient, transfer_amount);
+    let accounts = vec![
+        (
+            sender,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (
+            recipient,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+    ];
+
+    let mut mollusk = Mollusk::default();
+    mollusk.warp_to_slot(42);
+
+    let result = mollusk.process_instruction(&instruction, &accounts);
+    let fixture = Fixture::from_execution(&mollusk, &instruction, &accounts, &result);
+
+    let path = std::env::temp_dir().join(format!("mollusk_fixture_{}.bin", sender));
+    fixture.dump(&path);
+    let loaded = Fixture::load(&path);
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(loaded.instruction, instruction);
+    assert_eq!(loaded.accounts, accounts);
+    assert_eq!(loaded.sysvars.clock.slot, 42);
+
+    // Replay on a fresh instance, which picks up the fixture's configuration.
+    let replayed = Mollusk::default().process_fixture(&loaded);
+    loaded.assert_matches(&replayed);
+}
+
+#[test]
+#[should_panic(expected = "account lamports")]
+fn test_fixture_mismatch() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+
+    let instruction = system_instruction::transfer(&sender, &recipient, 42_000);
+    let accounts = vec![
+        (
+            sender,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (
+            recipient,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+    ];
+
+    let mollusk = Mollusk::default();
+    let result = mollusk.process_instruction(&instruction, &accounts);
+    let fixture = Fixture::from_execution(&mollusk, &instruction, &accounts, &result);
+
+    // Execute a different transfer amount and compare against the fixture.
+    let other = mollusk.process_instruction(
+        &system_instruction::transfer(&sender, &recipient, 1),
+        &accounts,
+    );
+    fixture.assert_matches(&other);
+}
+
+#[cfg(feature = "fixture-proto")]
+#[test]
+fn test_fixture_proto_round_trip() {
+    use {mollusk_svm::result::ProgramResult, solana_sdk::instruction::InstructionError};
+
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+
+    let instruction = system_instruction::transfer(&sender, &recipient, 42_000);
+    let accounts = vec![
+        (
+            sender,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (
+            recipient,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+    ];
+
+    let mut mollusk = Mollusk::default();
+    mollusk.warp_to_slot(42);
+
+    let result = mollusk.process_instruction(&instruction, &accounts);
+    let fixture = Fixture::from_execution(&mollusk, &instruction, &accounts, &result);
+
+    let decoded = Fixture::from_proto_bytes(&fixture.to_proto_bytes()).unwrap();
+
+    assert_eq!(decoded.compute_unit_limit, fixture.compute_unit_limit);
+    assert_eq!(decoded.feature_set, fixture.feature_set);
+    assert_eq!(decoded.sysvars.clock, fixture.sysvars.clock);
+    assert_eq!(decoded.instruction, fixture.instruction);
+    assert_eq!(decoded.accounts, fixture.accounts);
+    assert_eq!(decoded.program_result, fixture.program_result);
+    assert_eq!(decoded.resulting_accounts, fixture.resulting_accounts);
+
+    let replayed = Mollusk::default().process_fixture(&decoded);
+    decoded.assert_matches(&replayed);
+
+    // Errors without a `ProgramError` equivalent round-trip as an
+    // `InstructionError`, including those carrying data.
+    let mut fixture = decoded;
+    for err in [
+        InstructionError::ExecutableModified,
+        InstructionError::Custom(42),
+        InstructionError::BorshIoError("unexpected end of input".to_string()),
+    ] {
+        fixture.program_result = ProgramResult::UnknownError(err);
+        let decoded = Fixture::from_proto_bytes(&fixture.to_proto_bytes()).unwrap();
+        assert_eq!(decoded.program_result, fixture.program_result);
+    }
+
+    // Malformed bytes are an error, rather than a panic.
+    assert!(Fixture::from_proto_bytes(&[0xff, 0xff]).is_err());
+}
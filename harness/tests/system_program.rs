@@ -1,10 +1,20 @@
 use {
-    mollusk_svm::{result::Check, Mollusk},
+    mollusk_svm::{
+        error::MolluskError,
+        result::{anchor_error_code, Check, CheckFailure, ProgramResult, ANCHOR_ERROR_CODE_OFFSET},
+        Mollusk,
+    },
     solana_sdk::{
-        account::AccountSharedData, instruction::InstructionError, pubkey::Pubkey,
-        system_instruction, system_program,
+        account::{AccountSharedData, ReadableAccount, WritableAccount},
+        hash::Hash,
+        instruction::{AccountMeta, InstructionError},
+        nonce::state::{DurableNonce, State, Versions},
+        pubkey::Pubkey,
+        system_instruction::{self, SystemError, MAX_PERMITTED_DATA_LENGTH},
+        system_program, sysvar,
     },
     solana_system_program::system_processor::DEFAULT_COMPUTE_UNITS,
+    std::collections::HashMap,
 };
 
 #[test]
@@ -40,6 +50,60 @@ fn test_transfer() {
     Mollusk::default().process_and_validate_instruction(&instruction, &accounts, &checks);
 }
 
+#[test]
+fn test_compute_units_bounds() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+
+    let instruction = system_instruction::transfer(&sender, &recipient, 42_000);
+    let accounts = [
+        (
+            sender,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (
+            recipient,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+    ];
+    let checks = vec![
+        Check::success(),
+        Check::compute_units_less_than(DEFAULT_COMPUTE_UNITS + 1),
+        Check::compute_units_at_most(DEFAULT_COMPUTE_UNITS),
+        Check::compute_units_between(DEFAULT_COMPUTE_UNITS - 1, DEFAULT_COMPUTE_UNITS + 1),
+    ];
+
+    Mollusk::default().process_and_validate_instruction(&instruction, &accounts, &checks);
+}
+
+#[test]
+fn test_builtin_cu() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+
+    let instruction = system_instruction::transfer(&sender, &recipient, 42_000);
+    let accounts = [
+        (
+            sender,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (
+            recipient,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+    ];
+    let checks = vec![
+        Check::success(),
+        Check::builtin_cu(&system_program::id(), DEFAULT_COMPUTE_UNITS),
+    ];
+
+    Mollusk::default().process_and_validate_instruction(&instruction, &accounts, &checks);
+}
+
 #[test]
 fn test_transfer_bad_owner() {
     let sender = Pubkey::new_unique();
@@ -66,3 +130,1770 @@ fn test_transfer_bad_owner() {
 
     Mollusk::default().process_and_validate_instruction(&instruction, &accounts, &checks);
 }
+
+#[test]
+fn test_transfer_logs() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+    let transfer_amount = 42_000u64;
+
+    let instruction = system_instruction::transfer(&sender, &recipient, transfer_amount);
+    let accounts = [
+        (
+            sender,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (
+            recipient,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+    ];
+
+    let invoke_log = format!("Program {} invoke [1]", system_program::id());
+    let success_log = format!("Program {} success", system_program::id());
+    let framed_logs = [invoke_log.as_str(), success_log.as_str()];
+    let checks = vec![
+        Check::success(),
+        Check::log(&invoke_log),
+        Check::log(&success_log),
+        Check::log_contains("invoke [1]"),
+        Check::log_not_containing("failed"),
+        Check::logs(&[]),
+        Check::logs_with_framing(&framed_logs),
+    ];
+
+    let mollusk = Mollusk::default();
+    let result = mollusk.process_and_validate_instruction(&instruction, &accounts, &checks);
+    assert_eq!(result.logs, vec![invoke_log, success_log]);
+
+    let failures = mollusk
+        .try_process_and_validate_instruction(
+            &instruction,
+            &accounts,
+            &[Check::log_not_containing("success")],
+        )
+        .unwrap_err();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].check, "log not containing");
+}
+
+#[test]
+fn test_transfer_check_failures() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+    let transfer_amount = 42_000u64;
+
+    let instruction = system_instruction::transfer(&sender, &recipient, transfer_amount);
+    let accounts = [
+        (
+            sender,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (
+            recipient,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+    ];
+    let checks = vec![
+        Check::success(),
+        Check::compute_units(DEFAULT_COMPUTE_UNITS + 1), // <-- Wrong.
+        Check::compute_units_less_than(DEFAULT_COMPUTE_UNITS), // <-- Wrong.
+        Check::account(&recipient).lamports(base_lamports).build(), // <-- Wrong.
+    ];
+
+    let failures = Mollusk::default()
+        .try_process_and_validate_instruction(&instruction, &accounts, &checks)
+        .unwrap_err();
+
+    assert_eq!(
+        failures,
+        vec![
+            CheckFailure {
+                check: "compute units consumed".to_string(),
+                expected: (DEFAULT_COMPUTE_UNITS + 1).to_string(),
+                actual: DEFAULT_COMPUTE_UNITS.to_string(),
+            },
+            CheckFailure {
+                check: "compute units consumed".to_string(),
+                expected: format!("less than {}", DEFAULT_COMPUTE_UNITS),
+                actual: DEFAULT_COMPUTE_UNITS.to_string(),
+            },
+            CheckFailure {
+                check: "account lamports".to_string(),
+                expected: base_lamports.to_string(),
+                actual: (base_lamports + transfer_amount).to_string(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_create_account_owner() {
+    let payer = Pubkey::new_unique();
+    let new_account = Pubkey::new_unique();
+    let owner = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+    let space = 16;
+
+    let mollusk = Mollusk::default();
+    let lamports = mollusk.sysvars.rent.minimum_balance(space);
+
+    let instruction =
+        system_instruction::create_account(&payer, &new_account, lamports, space as u64, &owner);
+    let accounts = [
+        (
+            payer,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (new_account, AccountSharedData::default()),
+    ];
+    let checks = vec![
+        Check::success(),
+        Check::account(&new_account)
+            .lamports(lamports)
+            .owner(&owner)
+            .data(&[0; 16])
+            .build(),
+    ];
+
+    mollusk.process_and_validate_instruction(&instruction, &accounts, &checks);
+}
+
+#[test]
+fn test_assign_owner() {
+    let key = Pubkey::new_unique();
+    let owner = Pubkey::new_unique();
+
+    let instruction = system_instruction::assign(&key, &owner);
+    let accounts = [(
+        key,
+        AccountSharedData::new(100_000_000, 0, &system_program::id()),
+    )];
+    let checks = vec![Check::success(), Check::account(&key).owner(&owner).build()];
+
+    Mollusk::default().process_and_validate_instruction(&instruction, &accounts, &checks);
+}
+
+#[test]
+fn test_account_check_absent() {
+    let key = Pubkey::new_unique();
+    let missing = Pubkey::new_unique();
+    let owner = Pubkey::new_unique();
+
+    let instruction = system_instruction::assign(&key, &owner);
+    let accounts = [(
+        key,
+        AccountSharedData::new(100_000_000, 0, &system_program::id()),
+    )];
+    let checks = vec![Check::account(&missing).owner(&owner).build()];
+
+    let failures = Mollusk::default()
+        .try_process_and_validate_instruction(&instruction, &accounts, &checks)
+        .unwrap_err();
+
+    assert_eq!(
+        failures,
+        vec![CheckFailure {
+            check: format!("account {} in resulting accounts", missing),
+            expected: "present".to_string(),
+            actual: "absent".to_string(),
+        }]
+    );
+}
+
+#[test]
+fn test_account_data_slice() {
+    let payer = Pubkey::new_unique();
+    let new_account = Pubkey::new_unique();
+    let owner = Pubkey::new_unique();
+
+    let mollusk = Mollusk::default();
+    let lamports = mollusk.sysvars.rent.minimum_balance(8);
+
+    let instruction = system_instruction::create_account(&payer, &new_account, lamports, 8, &owner);
+    let accounts = [
+        (
+            payer,
+            AccountSharedData::new(100_000_000, 0, &system_program::id()),
+        ),
+        (new_account, AccountSharedData::default()),
+    ];
+
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &accounts,
+        &[
+            Check::success(),
+            Check::account_data_slice(&new_account, 4, &[0; 4]),
+        ],
+    );
+
+    // Out of bounds.
+    let failures = mollusk
+        .try_process_and_validate_instruction(
+            &instruction,
+            &accounts,
+            &[Check::account_data_slice(&new_account, 6, &[0; 4])],
+        )
+        .unwrap_err();
+    assert_eq!(
+        failures,
+        vec![CheckFailure {
+            check: "account data at offset 6".to_string(),
+            expected: "4 bytes".to_string(),
+            actual: "out of bounds for data length 8".to_string(),
+        }]
+    );
+}
+
+#[test]
+fn test_transfer_custom_err() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+
+    let mollusk = Mollusk::default();
+
+    let instruction = system_instruction::transfer(&sender, &recipient, base_lamports + 1);
+    let accounts = [
+        (
+            sender,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (
+            recipient,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+    ];
+
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &accounts,
+        &[Check::custom_err(
+            SystemError::ResultWithNegativeLamports as u32,
+        )],
+    );
+
+    // Only the System program's own message is logged, between the runtime's
+    // framing.
+    let message = format!(
+        "Transfer: insufficient lamports {}, need {}",
+        base_lamports,
+        base_lamports + 1
+    );
+    mollusk.process_and_validate_instruction(&instruction, &accounts, &[Check::logs(&[&message])]);
+    let failures = mollusk
+        .try_process_and_validate_instruction(&instruction, &accounts, &[Check::logs(&[])])
+        .unwrap_err();
+    assert_eq!(
+        failures,
+        vec![CheckFailure {
+            check: "logs".to_string(),
+            expected: "[]".to_string(),
+            actual: format!("{:?}", [&message]),
+        }]
+    );
+
+    // The failure reports both the expected and actual program result.
+    let failures = mollusk
+        .try_process_and_validate_instruction(&instruction, &accounts, &[Check::custom_err(42)])
+        .unwrap_err();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].check, "program result");
+    assert_eq!(failures[0].expected, "Failure(Custom(42))");
+    assert_eq!(
+        failures[0].actual,
+        format!(
+            "Failure(Custom({}))",
+            SystemError::ResultWithNegativeLamports as u32
+        )
+    );
+
+    // The exact error can also be matched on directly.
+    let result = mollusk.process_instruction(&instruction, &accounts);
+    assert!(result.is_err());
+    assert!(!result.is_ok());
+    assert!(matches!(
+        result.instruction_error(),
+        Some(InstructionError::Custom(code))
+            if *code == SystemError::ResultWithNegativeLamports as u32
+    ));
+
+    // Expected results can be built from the error, and converted back.
+    let error = InstructionError::Custom(SystemError::ResultWithNegativeLamports as u32);
+    assert_eq!(result.program_result, ProgramResult::error(error.clone()));
+    assert_eq!(
+        Result::<(), InstructionError>::from(result.program_result.clone()),
+        Err(error)
+    );
+    assert_eq!(
+        ProgramResult::error(InstructionError::ComputationalBudgetExceeded),
+        ProgramResult::UnknownError(InstructionError::ComputationalBudgetExceeded)
+    );
+
+    let instruction = system_instruction::transfer(&sender, &recipient, base_lamports);
+    let result = mollusk.process_instruction(&instruction, &accounts);
+    assert!(result.is_ok());
+    assert_eq!(result.instruction_error(), None);
+    assert_eq!(result.program_result, ProgramResult::success());
+    assert_eq!(
+        Result::<(), InstructionError>::from(result.program_result),
+        Ok(())
+    );
+}
+
+#[test]
+fn test_anchor_err() {
+    // Mirrors the `Into<u32>` implementation generated by Anchor's
+    // `#[error_code]` attribute.
+    #[derive(Clone, Copy)]
+    enum MyError {
+        _First,
+        Second,
+    }
+
+    impl From<MyError> for u32 {
+        fn from(error: MyError) -> u32 {
+            error as u32 + ANCHOR_ERROR_CODE_OFFSET
+        }
+    }
+
+    assert_eq!(anchor_error_code(1), 6001);
+
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+
+    let mollusk = Mollusk::default();
+
+    let instruction = system_instruction::transfer(&sender, &recipient, base_lamports + 1);
+    let accounts = [
+        (
+            sender,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (
+            recipient,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+    ];
+
+    let failures = mollusk
+        .try_process_and_validate_instruction(
+            &instruction,
+            &accounts,
+            &[
+                Check::anchor_err(MyError::Second),
+                Check::custom_err(anchor_error_code(1)),
+            ],
+        )
+        .unwrap_err();
+    assert_eq!(failures.len(), 2);
+    assert!(failures
+        .iter()
+        .all(|failure| failure.expected == "Failure(Custom(6001))"));
+}
+
+#[test]
+fn test_transfer_lamports_delta() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+    let transfer_amount = 42_000u64;
+
+    let mollusk = Mollusk::default();
+
+    let instruction = system_instruction::transfer(&sender, &recipient, transfer_amount);
+    let accounts = [
+        (
+            sender,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (
+            recipient,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+    ];
+
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &accounts,
+        &[
+            Check::success(),
+            Check::account_lamports_delta(&sender, -(transfer_amount as i128)),
+            Check::account_lamports_delta(&recipient, transfer_amount as i128),
+            Check::total_lamports_conserved(),
+        ],
+    );
+
+    let failures = mollusk
+        .try_process_and_validate_instruction(
+            &instruction,
+            &accounts,
+            &[Check::account_lamports_delta(&recipient, 1)],
+        )
+        .unwrap_err();
+    assert_eq!(
+        failures,
+        vec![CheckFailure {
+            check: format!("account {} lamports delta", recipient),
+            expected: "1".to_string(),
+            actual: transfer_amount.to_string(),
+        }]
+    );
+}
+
+#[test]
+fn test_only_accounts_changed() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+    let bystander = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+
+    let mollusk = Mollusk::default();
+
+    let mut instruction = system_instruction::transfer(&sender, &recipient, 42_000);
+    instruction
+        .accounts
+        .push(solana_sdk::instruction::AccountMeta::new(bystander, false));
+    let accounts = [
+        (
+            sender,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (
+            recipient,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (
+            bystander,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+    ];
+
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &accounts,
+        &[
+            Check::success(),
+            Check::only_accounts_changed(&[sender, recipient]),
+        ],
+    );
+
+    let failures = mollusk
+        .try_process_and_validate_instruction(
+            &instruction,
+            &accounts,
+            &[Check::only_accounts_changed(&[sender])],
+        )
+        .unwrap_err();
+    assert_eq!(
+        failures,
+        vec![CheckFailure {
+            check: format!("account {} unchanged", recipient),
+            expected: "unchanged".to_string(),
+            actual: "changed lamports".to_string(),
+        }]
+    );
+}
+
+#[test]
+fn test_account_diffs() {
+    let payer = Pubkey::new_unique();
+    let new_account = Pubkey::new_unique();
+    let owner = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+    let space = 16;
+
+    let mollusk = Mollusk::default();
+    let lamports = mollusk.sysvars.rent.minimum_balance(space);
+
+    let instruction =
+        system_instruction::create_account(&payer, &new_account, lamports, space as u64, &owner);
+    let accounts = [
+        (
+            payer,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (new_account, AccountSharedData::default()),
+    ];
+
+    let result = mollusk.process_instruction(&instruction, &accounts);
+    let diffs = result.account_diffs(&accounts);
+    assert_eq!(diffs.len(), 2);
+
+    assert_eq!(diffs[0].pubkey, payer);
+    assert_eq!(diffs[0].lamports_delta, -(lamports as i128));
+    assert_eq!(diffs[0].owner, None);
+
+    assert_eq!(diffs[1].pubkey, new_account);
+    assert_eq!(diffs[1].lamports, Some((0, lamports)));
+    assert_eq!(diffs[1].owner, Some((system_program::id(), owner)));
+    assert_eq!(diffs[1].data_len, Some((0, space)));
+    assert!(diffs[1].data_changes.is_empty());
+
+    let display = diffs[1].to_string();
+    assert!(display.contains(&format!("lamports: 0 -> {} (+{})", lamports, lamports)));
+    assert!(display.contains(&format!("owner: {} -> {}", system_program::id(), owner)));
+    assert!(display.contains("data length: 0 -> 16"));
+
+    // Unchanged accounts produce no diff.
+    let result = mollusk.process_instruction(
+        &system_instruction::transfer(&payer, &new_account, 0),
+        &accounts,
+    );
+    assert!(result.account_diffs(&accounts).is_empty());
+}
+
+#[test]
+fn test_process_instruction_recorded() {
+    let payer = Pubkey::new_unique();
+    let new_account = Pubkey::new_unique();
+    let owner = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+    let space = 16;
+
+    let mollusk = Mollusk::default();
+    let lamports = mollusk.sysvars.rent.minimum_balance(space);
+
+    let instruction =
+        system_instruction::create_account(&payer, &new_account, lamports, space as u64, &owner);
+    let accounts = [
+        (
+            payer,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (new_account, AccountSharedData::default()),
+    ];
+
+    let recorded = mollusk.process_instruction_recorded(&instruction, &accounts);
+    assert_eq!(recorded.accounts, accounts);
+    assert_eq!(
+        recorded.result.resulting_accounts,
+        mollusk
+            .process_instruction(&instruction, &accounts)
+            .resulting_accounts
+    );
+    assert_eq!(recorded.diffs, recorded.result.account_diffs(&accounts));
+
+    let path = std::env::temp_dir().join(format!("mollusk_recording_{}.json", payer));
+    recorded.write_json(&path);
+    let json: serde_json::Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(json, recorded.to_json());
+    assert_eq!(json["programResult"], "Success");
+    assert_eq!(json["accounts"][1]["pubkey"], new_account.to_string());
+    assert_eq!(
+        json["resultingAccounts"][1]["account"]["owner"],
+        owner.to_string()
+    );
+    assert_eq!(json["diffs"][1]["lamports"][1], lamports);
+    assert_eq!(json["diffs"][1]["dataLen"][1], space);
+}
+
+#[test]
+fn test_rent_exempt_account_custom_rent() {
+    let owner = Pubkey::new_unique();
+
+    let mut mollusk = Mollusk::default();
+    mollusk.sysvars.rent.lamports_per_byte_year *= 2;
+
+    let account = mollusk.rent_exempt_account(&owner, 100);
+    assert_eq!(
+        account.lamports(),
+        mollusk.sysvars.rent.minimum_balance(100)
+    );
+    assert_eq!(account.data(), &[0; 100]);
+
+    let account = mollusk.rent_exempt_account_with_data(&owner, &[1, 2, 3]);
+    assert_eq!(account.lamports(), mollusk.sysvars.rent.minimum_balance(3));
+    assert_eq!(account.data(), &[1, 2, 3]);
+    assert_eq!(account.owner(), &owner);
+}
+
+#[test]
+fn test_snapshot() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+    let bystander = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+
+    let mollusk = Mollusk::default();
+
+    let mut instruction = system_instruction::transfer(&sender, &recipient, 42_000);
+    instruction
+        .accounts
+        .push(solana_sdk::instruction::AccountMeta::new(bystander, false));
+    let accounts = [
+        (
+            sender,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (
+            recipient,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (
+            bystander,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+    ];
+
+    let result = mollusk.process_instruction(&instruction, &accounts);
+    let snapshot = result.snapshot();
+    assert!(snapshot.contains(&format!(
+        "{}:\n  lamports: {}\n  owner: {}\n  executable: false\n  data: \n",
+        recipient,
+        base_lamports + 42_000,
+        system_program::id(),
+    )));
+    assert!(snapshot.contains(&bystander.to_string()));
+
+    let path = std::env::temp_dir().join(format!("mollusk_snapshot_{}.txt", sender));
+
+    // The first run writes the snapshot, and subsequent runs compare against
+    // it. The unchanged bystander is omitted.
+    for _ in 0..2 {
+        mollusk.process_and_validate_instruction(
+            &instruction,
+            &accounts,
+            &[Check::matches_snapshot(&path)],
+        );
+    }
+    let stored = std::fs::read_to_string(&path).unwrap();
+    assert!(stored.contains(&recipient.to_string()));
+    assert!(!stored.contains(&bystander.to_string()));
+
+    let failures = mollusk
+        .try_process_and_validate_instruction(
+            &system_instruction::transfer(&sender, &recipient, 1),
+            &accounts,
+            &[Check::matches_snapshot(&path)],
+        )
+        .unwrap_err();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].check, format!("snapshot {}", path.display()));
+    assert_eq!(failures[0].expected, stored);
+
+    // A snapshot that can't be written fails the check, rather than
+    // panicking, here because its parent is a file.
+    let unwritable = path.join("snapshot.txt");
+    let failures = mollusk
+        .try_process_and_validate_instruction(
+            &instruction,
+            &accounts,
+            &[Check::matches_snapshot(&unwritable)],
+        )
+        .unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(
+        failures[0].check,
+        format!("snapshot {}", unwritable.display())
+    );
+    assert!(failures[0].actual.starts_with("Failed to write"));
+}
+
+#[test]
+fn test_compute_units_baseline() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+
+    let instruction = system_instruction::transfer(&sender, &recipient, 42_000);
+    let accounts = [
+        (
+            sender,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (
+            recipient,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+    ];
+
+    let mollusk = Mollusk::default();
+
+    let path = std::env::temp_dir().join(format!("mollusk_baseline_{}.txt", sender));
+
+    // The first run writes the baseline, and subsequent runs compare against
+    // it.
+    for _ in 0..2 {
+        mollusk.process_and_validate_instruction(
+            &instruction,
+            &accounts,
+            &[Check::compute_units_baseline(&path, 0.0)],
+        );
+    }
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        format!("{}\n", DEFAULT_COMPUTE_UNITS)
+    );
+
+    // A baseline within the tolerance passes, and one beyond it fails.
+    std::fs::write(&path, (DEFAULT_COMPUTE_UNITS + 10).to_string()).unwrap();
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &accounts,
+        &[Check::compute_units_baseline(&path, 10.0)],
+    );
+    let failures = mollusk
+        .try_process_and_validate_instruction(
+            &instruction,
+            &accounts,
+            &[Check::compute_units_baseline(&path, 1.0)],
+        )
+        .unwrap_err();
+    assert_eq!(
+        failures,
+        vec![CheckFailure {
+            check: format!("compute units baseline {}", path.display()),
+            expected: format!("{} (within 1%)", DEFAULT_COMPUTE_UNITS + 10),
+            actual: DEFAULT_COMPUTE_UNITS.to_string(),
+        }]
+    );
+
+    // An unparsable baseline fails the check, rather than panicking.
+    std::fs::write(&path, "many").unwrap();
+    let failures = mollusk
+        .try_process_and_validate_instruction(
+            &instruction,
+            &accounts,
+            &[Check::compute_units_baseline(&path, 10.0)],
+        )
+        .unwrap_err();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(
+        failures[0].check,
+        format!("compute units baseline {}", path.display())
+    );
+    assert!(failures[0]
+        .actual
+        .starts_with("Invalid compute units baseline"));
+
+    // As does a baseline that can't be written, here because its parent is a
+    // file.
+    let unwritable = path.join("baseline.txt");
+    let failures = mollusk
+        .try_process_and_validate_instruction(
+            &instruction,
+            &accounts,
+            &[Check::compute_units_baseline(&unwritable, 10.0)],
+        )
+        .unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(failures.len(), 1);
+    assert!(failures[0].actual.starts_with("Failed to write"));
+}
+
+#[test]
+fn test_set_compute_unit_limit() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+
+    let instruction = system_instruction::transfer(&sender, &recipient, 42_000);
+    let accounts = [
+        (
+            sender,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (
+            recipient,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+    ];
+
+    let mut mollusk = Mollusk::default();
+    let default_heap_size = mollusk.compute_budget.heap_size;
+
+    mollusk.set_compute_unit_limit(DEFAULT_COMPUTE_UNITS - 1);
+    assert_eq!(mollusk.compute_budget.heap_size, default_heap_size);
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &accounts,
+        &[
+            Check::instruction_err(InstructionError::ComputationalBudgetExceeded),
+            Check::compute_budget_exceeded(),
+        ],
+    );
+
+    mollusk.set_compute_unit_limit(DEFAULT_COMPUTE_UNITS);
+    let result =
+        mollusk.process_and_validate_instruction(&instruction, &accounts, &[Check::success()]);
+    assert!(!result.compute_budget_exceeded);
+
+    mollusk.set_heap_size(64 * 1024);
+    assert_eq!(mollusk.compute_budget.heap_size, 64 * 1024);
+    assert_eq!(
+        mollusk.compute_budget.compute_unit_limit,
+        DEFAULT_COMPUTE_UNITS
+    );
+}
+
+#[test]
+fn test_account_space() {
+    let payer = Pubkey::new_unique();
+    let new_account = Pubkey::new_unique();
+    let owner = Pubkey::new_unique();
+
+    let space = 16;
+
+    let mollusk = Mollusk::default();
+    let lamports = mollusk.sysvars.rent.minimum_balance(space);
+
+    let instruction =
+        system_instruction::create_account(&payer, &new_account, lamports, space as u64, &owner);
+    let accounts = [
+        (
+            payer,
+            AccountSharedData::new(100_000_000, 0, &system_program::id()),
+        ),
+        (new_account, AccountSharedData::default()),
+    ];
+
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &accounts,
+        &[
+            Check::success(),
+            Check::account_space(&new_account, space),
+            Check::account_space(&payer, 0),
+        ],
+    );
+
+    let failures = mollusk
+        .try_process_and_validate_instruction(
+            &instruction,
+            &accounts,
+            &[Check::account_space(&new_account, 32)],
+        )
+        .unwrap_err();
+    assert_eq!(
+        failures,
+        vec![CheckFailure {
+            check: "account space".to_string(),
+            expected: "32".to_string(),
+            actual: "16".to_string(),
+        }]
+    );
+}
+
+#[test]
+fn test_account_rent_exempt() {
+    let payer = Pubkey::new_unique();
+    let new_account = Pubkey::new_unique();
+    let owner = Pubkey::new_unique();
+
+    let space = 16;
+    let base_lamports = 100_000_000u64;
+
+    let mut mollusk = Mollusk::default();
+    let minimum_balance = mollusk.sysvars.rent.minimum_balance(space);
+
+    let accounts = [
+        (
+            payer,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (new_account, AccountSharedData::default()),
+    ];
+
+    let create_account = |lamports: u64| {
+        system_instruction::create_account(&payer, &new_account, lamports, space as u64, &owner)
+    };
+
+    mollusk.process_and_validate_instruction(
+        &create_account(minimum_balance),
+        &accounts,
+        &[
+            Check::success(),
+            Check::account_rent_exempt(&new_account),
+            Check::account_rent_exempt_strict(&payer),
+        ],
+    );
+
+    // The System program doesn't enforce rent exemption, so the account can
+    // be created below the minimum balance.
+    let failures = mollusk
+        .try_process_and_validate_instruction(
+            &create_account(minimum_balance - 1),
+            &accounts,
+            &[Check::account_rent_exempt(&new_account)],
+        )
+        .unwrap_err();
+    assert_eq!(
+        failures,
+        vec![CheckFailure {
+            check: format!("account {} rent exempt", new_account),
+            expected: format!("at least {} lamports", minimum_balance),
+            actual: (minimum_balance - 1).to_string(),
+        }]
+    );
+
+    // The harness's configured rent is used.
+    mollusk.sysvars.rent.lamports_per_byte_year /= 2;
+    mollusk.process_and_validate_instruction(
+        &create_account(minimum_balance - 1),
+        &accounts,
+        &[Check::success(), Check::account_rent_exempt(&new_account)],
+    );
+
+    // A closed account is exempt, unless the check is strict.
+    let drain = system_instruction::transfer(&payer, &new_account, base_lamports);
+    mollusk.process_and_validate_instruction(
+        &drain,
+        &accounts,
+        &[Check::success(), Check::account_rent_exempt(&payer)],
+    );
+    let failures = mollusk
+        .try_process_and_validate_instruction(
+            &drain,
+            &accounts,
+            &[Check::account_rent_exempt_strict(&payer)],
+        )
+        .unwrap_err();
+    assert_eq!(failures.len(), 1);
+}
+
+#[test]
+fn test_dump() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+
+    let instruction = system_instruction::transfer(&sender, &recipient, 42_000);
+    let accounts = [
+        (
+            sender,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (
+            recipient,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+    ];
+
+    let mollusk = Mollusk::default();
+    let result = mollusk.process_instruction(&instruction, &accounts);
+
+    let dump = result.dump();
+    assert!(dump.contains(&format!(
+        "Compute units consumed: {}\n",
+        DEFAULT_COMPUTE_UNITS
+    )));
+    assert!(dump.contains("Program result: Success\n"));
+    assert!(dump.contains(&format!(
+        "  {}: lamports {}, owner {}, data length 0\n",
+        recipient,
+        base_lamports + 42_000,
+        system_program::id(),
+    )));
+    for log in result.logs.iter() {
+        assert!(dump.contains(log));
+    }
+}
+
+#[test]
+#[should_panic(expected = "Resulting accounts:")]
+fn test_check_failure_includes_dump() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let instruction = system_instruction::transfer(&sender, &recipient, 42_000);
+    let accounts = [
+        (
+            sender,
+            AccountSharedData::new(100_000_000, 0, &system_program::id()),
+        ),
+        (recipient, AccountSharedData::default()),
+    ];
+
+    Mollusk::default().process_and_validate_instruction(
+        &instruction,
+        &accounts,
+        &[Check::compute_units(0)],
+    );
+}
+
+#[test]
+fn test_with_logs_disabled() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let instruction = system_instruction::transfer(&sender, &recipient, 42_000);
+    let accounts = [
+        (
+            sender,
+            AccountSharedData::new(100_000_000, 0, &system_program::id()),
+        ),
+        (recipient, AccountSharedData::default()),
+    ];
+
+    // Program logs are still collected without a logger installed.
+    let mollusk = Mollusk::with_logs_disabled();
+    let result = mollusk.process_and_validate_instruction(
+        &instruction,
+        &accounts,
+        &[
+            Check::success(),
+            Check::compute_units(DEFAULT_COMPUTE_UNITS),
+        ],
+    );
+    assert!(!result.logs.is_empty());
+}
+
+#[test]
+fn test_account_equals() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+
+    let instruction = system_instruction::transfer(&sender, &recipient, 42_000);
+    let accounts = [
+        (
+            sender,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (
+            recipient,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+    ];
+
+    let mollusk = Mollusk::default();
+
+    let expected = AccountSharedData::new(base_lamports + 42_000, 0, &system_program::id());
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &accounts,
+        &[
+            Check::success(),
+            Check::account_equals(&recipient, expected),
+        ],
+    );
+
+    let owner = Pubkey::new_unique();
+    let failures = mollusk
+        .try_process_and_validate_instruction(
+            &instruction,
+            &accounts,
+            &[Check::account_equals(
+                &recipient,
+                AccountSharedData::new(base_lamports, 1, &owner),
+            )],
+        )
+        .unwrap_err();
+    assert_eq!(
+        failures,
+        vec![
+            CheckFailure {
+                check: format!("account {} lamports", recipient),
+                expected: base_lamports.to_string(),
+                actual: (base_lamports + 42_000).to_string(),
+            },
+            CheckFailure {
+                check: format!("account {} owner", recipient),
+                expected: owner.to_string(),
+                actual: system_program::id().to_string(),
+            },
+            CheckFailure {
+                check: format!("account {} data", recipient),
+                expected: "[0]".to_string(),
+                actual: "[]".to_string(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_process_instruction_raw_context() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+
+    let instruction = system_instruction::transfer(&sender, &recipient, 42_000);
+    let accounts = [
+        (
+            sender,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (
+            recipient,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+    ];
+
+    let mollusk = Mollusk::default();
+    let (result, transaction_context) =
+        mollusk.process_instruction_raw_context(&instruction, &accounts);
+
+    assert_eq!(result.program_result, ProgramResult::Success);
+    assert_eq!(result.compute_units_consumed, DEFAULT_COMPUTE_UNITS);
+    assert_eq!(transaction_context.get_instruction_trace_length(), 1);
+
+    // The program account comes first, followed by the provided accounts.
+    let context_accounts = transaction_context.deconstruct_without_keys().unwrap();
+    assert_eq!(context_accounts.len(), 3);
+    assert_eq!(&context_accounts[1], result.get_account(&sender).unwrap());
+    assert_eq!(
+        &context_accounts[2],
+        result.get_account(&recipient).unwrap()
+    );
+}
+
+#[test]
+fn test_process_instruction_owned() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+    let transfer_amount = 42_000u64;
+
+    let instruction = system_instruction::transfer(&sender, &recipient, transfer_amount);
+    let accounts = vec![
+        (
+            sender,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (
+            recipient,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+    ];
+
+    let mollusk = Mollusk::default();
+    let borrowed = mollusk.process_instruction(&instruction, &accounts);
+    let owned = mollusk.process_instruction_owned(&instruction, accounts);
+
+    assert_eq!(owned.program_result, borrowed.program_result);
+    assert_eq!(
+        owned.compute_units_consumed,
+        borrowed.compute_units_consumed
+    );
+    assert_eq!(owned.resulting_accounts, borrowed.resulting_accounts);
+    assert_eq!(
+        owned.get_account(&sender).unwrap().lamports(),
+        base_lamports - transfer_amount
+    );
+}
+
+#[test]
+fn test_resulting_accounts_map() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+    let transfer_amount = 42_000u64;
+
+    let instruction = system_instruction::transfer(&sender, &recipient, transfer_amount);
+    let accounts = [
+        (
+            sender,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (
+            recipient,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+    ];
+
+    let result = Mollusk::default().process_instruction(&instruction, &accounts);
+    let map = result.resulting_accounts_map();
+    assert_eq!(map.len(), 2);
+    assert_eq!(map[&sender].lamports(), base_lamports - transfer_amount);
+    assert_eq!(map[&recipient], result.get_account(&recipient).unwrap());
+}
+
+#[test]
+fn test_account_closed() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+    let drained = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+
+    // Drain the sender, and pass along an extra account, left with no
+    // lamports and zeroed data but still assigned to another program.
+    let mut instruction = system_instruction::transfer(&sender, &recipient, base_lamports);
+    instruction
+        .accounts
+        .push(AccountMeta::new_readonly(drained, false));
+    let accounts = [
+        (
+            sender,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (recipient, AccountSharedData::default()),
+        (drained, AccountSharedData::new(0, 8, &Pubkey::new_unique())),
+    ];
+
+    let mollusk = Mollusk::default();
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &accounts,
+        &[
+            Check::success(),
+            Check::account_closed(&sender),
+            Check::account_closed_any_owner(&sender),
+            Check::account_closed_any_owner(&drained),
+        ],
+    );
+
+    let failures = mollusk
+        .try_process_and_validate_instruction(
+            &instruction,
+            &accounts,
+            &[
+                Check::account_closed(&drained),
+                Check::account_closed_any_owner(&recipient),
+            ],
+        )
+        .unwrap_err();
+    assert_eq!(failures.len(), 2);
+    assert!(failures
+        .iter()
+        .all(|failure| failure.check == "account closed"));
+}
+
+#[test]
+fn test_duplicate_accounts() {
+    let sender = Pubkey::new_unique();
+    let other = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+
+    let mut instruction = system_instruction::transfer(&sender, &sender, 42_000);
+    instruction
+        .accounts
+        .push(AccountMeta::new_readonly(other, false));
+    let sender_account = AccountSharedData::new(base_lamports, 0, &system_program::id());
+    let accounts = [
+        (sender, sender_account.clone()),
+        (sender, sender_account.clone()),
+        (other, AccountSharedData::default()),
+    ];
+
+    let result = Mollusk::default().process_and_validate_instruction(
+        &instruction,
+        &accounts,
+        &[
+            Check::success(),
+            Check::account(&sender).lamports(base_lamports).build(),
+            Check::total_lamports_conserved(),
+        ],
+    );
+
+    // Each account appears once, in the order it is first referenced.
+    let pubkeys = result
+        .resulting_accounts
+        .iter()
+        .map(|(pubkey, _)| *pubkey)
+        .collect::<Vec<_>>();
+    assert_eq!(pubkeys, vec![sender, other]);
+    assert_eq!(result.get_account(&sender), Some(&sender_account));
+    assert!(result.account_diffs(&accounts).is_empty());
+}
+
+#[test]
+fn test_get_account_mut() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+    let transfer_amount = 42_000u64;
+
+    let instruction = system_instruction::transfer(&sender, &recipient, transfer_amount);
+    let accounts = [
+        (
+            sender,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (
+            recipient,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+    ];
+
+    let mut result = Mollusk::default().process_instruction(&instruction, &accounts);
+    assert!(result.get_account_mut(&Pubkey::new_unique()).is_none());
+
+    result
+        .get_account_mut(&recipient)
+        .unwrap()
+        .set_lamports(base_lamports);
+    assert_eq!(
+        result.get_account(&recipient).unwrap().lamports(),
+        base_lamports
+    );
+}
+
+#[cfg(feature = "all-builtins")]
+#[test]
+fn test_optional_builtins() {
+    use {mollusk_svm::program, solana_sdk::instruction::Instruction};
+
+    let instruction = Instruction::new_with_bytes(solana_sdk::stake::program::id(), &[], vec![]);
+
+    let mut mollusk = Mollusk::default();
+    let unsupported = mollusk.process_instruction(&instruction, &[]);
+    assert_eq!(
+        unsupported.program_result,
+        ProgramResult::from(Err::<(), _>(InstructionError::UnsupportedProgramId)),
+    );
+
+    // Once added, the program is found and rejects the empty instruction.
+    mollusk.add_stake_program();
+    let result = mollusk.process_instruction(&instruction, &[]);
+    assert_ne!(result.program_result, ProgramResult::Success);
+    assert_ne!(result.program_result, unsupported.program_result);
+
+    let mut mollusk = Mollusk::default();
+    mollusk.add_all_builtins();
+    for (program_id, _) in [
+        program::stake_program(),
+        program::vote_program(),
+        program::config_program(),
+        program::address_lookup_table_program(),
+    ] {
+        let instruction = Instruction::new_with_bytes(program_id, &[], vec![]);
+        let result = mollusk.process_instruction(&instruction, &[]);
+        assert_ne!(result.program_result, unsupported.program_result);
+    }
+}
+
+#[test]
+fn test_account_map() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+    let transfer_amount = 42_000u64;
+
+    let instruction = system_instruction::transfer(&sender, &recipient, transfer_amount);
+    let accounts = HashMap::from([
+        (
+            recipient,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (
+            sender,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+    ]);
+
+    let mollusk = Mollusk::default();
+    let result = mollusk.process_instruction_with_account_map(&instruction, &accounts);
+    assert_eq!(result.program_result, ProgramResult::Success);
+    assert_eq!(
+        result.get_account(&sender).unwrap().lamports(),
+        base_lamports - transfer_amount
+    );
+    assert_eq!(
+        result.get_account(&recipient).unwrap().lamports(),
+        base_lamports + transfer_amount
+    );
+
+    // A missing account is provided as a default account.
+    let accounts = HashMap::from([(
+        sender,
+        AccountSharedData::new(base_lamports, 0, &system_program::id()),
+    )]);
+    let result = mollusk.process_instruction_with_account_map(&instruction, &accounts);
+    assert_eq!(result.program_result, ProgramResult::Success);
+    assert_eq!(
+        result.get_account(&recipient).unwrap().lamports(),
+        transfer_amount
+    );
+}
+
+#[test]
+fn test_missing_accounts() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let instruction = system_instruction::transfer(&sender, &recipient, 42_000);
+    let accounts = [(
+        sender,
+        AccountSharedData::new(100_000_000, 0, &system_program::id()),
+    )];
+
+    let mut mollusk = Mollusk::default();
+    let err = mollusk
+        .try_process_instruction(&instruction, &accounts)
+        .unwrap_err();
+    assert!(matches!(&err, MolluskError::MissingAccounts(missing) if *missing == vec![recipient]));
+    assert!(err.to_string().contains(&recipient.to_string()));
+
+    let failures = mollusk
+        .try_process_and_validate_instruction(&instruction, &accounts, &[Check::success()])
+        .unwrap_err();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].check, "instruction accounts provided");
+
+    // Opting out processes the instruction without the account.
+    mollusk.allow_missing_accounts = true;
+    let result = mollusk
+        .try_process_instruction(&instruction, &accounts)
+        .unwrap();
+    assert_eq!(
+        result.program_result,
+        mollusk
+            .process_instruction(&instruction, &accounts)
+            .program_result
+    );
+}
+
+#[test]
+#[should_panic(expected = "were not provided")]
+fn test_missing_accounts_panics() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    Mollusk::default().process_and_validate_instruction(
+        &system_instruction::transfer(&sender, &recipient, 42_000),
+        &[(
+            sender,
+            AccountSharedData::new(100_000_000, 0, &system_program::id()),
+        )],
+        &[Check::success()],
+    );
+}
+
+#[test]
+fn test_account_data_too_large() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let instruction = system_instruction::transfer(&sender, &recipient, 42_000);
+    let accounts = [
+        (
+            sender,
+            AccountSharedData::new(100_000_000, 0, &system_program::id()),
+        ),
+        (
+            recipient,
+            AccountSharedData::new(
+                100_000_000,
+                MAX_PERMITTED_DATA_LENGTH as usize + 1,
+                &system_program::id(),
+            ),
+        ),
+    ];
+
+    let err = Mollusk::default()
+        .try_process_instruction(&instruction, &accounts)
+        .unwrap_err();
+    assert!(matches!(
+        &err,
+        MolluskError::AccountDataTooLarge { pubkey, len }
+            if *pubkey == recipient && *len == MAX_PERMITTED_DATA_LENGTH as usize + 1
+    ));
+    assert!(err.to_string().contains(&recipient.to_string()));
+}
+
+#[test]
+#[should_panic(expected = "exceeding the maximum")]
+fn test_account_data_too_large_panics() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let instruction = system_instruction::transfer(&sender, &recipient, 42_000);
+    let accounts = [
+        (
+            sender,
+            AccountSharedData::new(
+                100_000_000,
+                MAX_PERMITTED_DATA_LENGTH as usize + 1,
+                &system_program::id(),
+            ),
+        ),
+        (
+            recipient,
+            AccountSharedData::new(100_000_000, 0, &system_program::id()),
+        ),
+    ];
+
+    Mollusk::default().process_instruction(&instruction, &accounts);
+}
+
+#[test]
+fn test_set_lamports_per_signature() {
+    let payer = Pubkey::new_unique();
+    let nonce = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+
+    let mut mollusk = Mollusk::default();
+
+    #[allow(deprecated)]
+    let recent_blockhashes = mollusk.sysvar_account(&sysvar::recent_blockhashes::id());
+    let rent = mollusk.sysvar_account(&sysvar::rent::id());
+
+    // The System program records the fee in a nonce account as it's
+    // initialized.
+    let lamports = mollusk.sysvars.rent.minimum_balance(State::size());
+    let initialize =
+        system_instruction::create_nonce_account(&payer, &nonce, &authority, lamports)[1].clone();
+    let accounts = [
+        (
+            nonce,
+            AccountSharedData::new(lamports, State::size(), &system_program::id()),
+        ),
+        recent_blockhashes,
+        rent,
+    ];
+    let lamports_per_signature = |mollusk: &Mollusk| {
+        let result =
+            mollusk.process_and_validate_instruction(&initialize, &accounts, &[Check::success()]);
+        let versions: Versions =
+            bincode::deserialize(result.get_account(&nonce).unwrap().data()).unwrap();
+        match versions.state() {
+            State::Initialized(data) => data.fee_calculator.lamports_per_signature,
+            State::Uninitialized => panic!("Nonce account is uninitialized"),
+        }
+    };
+
+    assert_eq!(
+        lamports_per_signature(&mollusk),
+        mollusk.fee_structure.lamports_per_signature
+    );
+
+    mollusk.set_lamports_per_signature(12_345);
+    assert_eq!(lamports_per_signature(&mollusk), 12_345);
+}
+
+#[test]
+fn test_set_blockhash() {
+    let payer = Pubkey::new_unique();
+    let nonce = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+
+    let mut mollusk = Mollusk::default();
+    let blockhash = Hash::new_unique();
+    mollusk.set_blockhash(blockhash);
+    mollusk.set_lamports_per_signature(5_000);
+
+    #[allow(deprecated)]
+    let recent_blockhashes = mollusk.sysvar_account(&sysvar::recent_blockhashes::id());
+    let rent = mollusk.sysvar_account(&sysvar::rent::id());
+
+    let nonce_state = |account: &AccountSharedData| {
+        let versions: Versions = bincode::deserialize(account.data()).unwrap();
+        match versions.state() {
+            State::Initialized(data) => data.clone(),
+            State::Uninitialized => panic!("Nonce account is uninitialized"),
+        }
+    };
+
+    // Initializing the nonce account records the blockhash.
+    let lamports = mollusk.sysvars.rent.minimum_balance(State::size());
+    let initialize =
+        system_instruction::create_nonce_account(&payer, &nonce, &authority, lamports)[1].clone();
+    let result = mollusk.process_and_validate_instruction(
+        &initialize,
+        &[
+            (
+                nonce,
+                AccountSharedData::new(lamports, State::size(), &system_program::id()),
+            ),
+            recent_blockhashes.clone(),
+            rent,
+        ],
+        &[Check::success()],
+    );
+    let nonce_account = result.get_account(&nonce).unwrap().clone();
+    let data = nonce_state(&nonce_account);
+    assert_eq!(data.durable_nonce, DurableNonce::from_blockhash(&blockhash));
+    assert_eq!(data.fee_calculator.lamports_per_signature, 5_000);
+
+    // The nonce can't advance until the blockhash changes.
+    let advance = system_instruction::advance_nonce_account(&nonce, &authority);
+    let accounts = [
+        (nonce, nonce_account),
+        recent_blockhashes,
+        (
+            authority,
+            AccountSharedData::new(0, 0, &system_program::id()),
+        ),
+    ];
+    mollusk.process_and_validate_instruction(
+        &advance,
+        &accounts,
+        &[Check::custom_err(
+            SystemError::NonceBlockhashNotExpired as u32,
+        )],
+    );
+
+    let new_blockhash = Hash::new_unique();
+    mollusk.set_blockhash(new_blockhash);
+    let result = mollusk.process_and_validate_instruction(&advance, &accounts, &[Check::success()]);
+    let data = nonce_state(result.get_account(&nonce).unwrap());
+    assert_eq!(
+        data.durable_nonce,
+        DurableNonce::from_blockhash(&new_blockhash)
+    );
+}
+
+#[test]
+fn test_account_exists() {
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+    let unreferenced = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+
+    // Transfer the sender's entire balance, leaving it empty.
+    let instruction = system_instruction::transfer(&sender, &recipient, base_lamports);
+    let accounts = [
+        (
+            sender,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (recipient, AccountSharedData::default()),
+    ];
+
+    let mollusk = Mollusk::default();
+    mollusk.process_and_validate_instruction(
+        &instruction,
+        &accounts,
+        &[
+            Check::success(),
+            Check::account_exists(&recipient),
+            Check::account_absent(&sender),
+            Check::account_absent(&unreferenced),
+        ],
+    );
+
+    let failures = mollusk
+        .try_process_and_validate_instruction(
+            &instruction,
+            &accounts,
+            &[
+                Check::account_exists(&sender),
+                Check::account_exists(&unreferenced),
+                Check::account_absent(&recipient),
+            ],
+        )
+        .unwrap_err();
+    assert_eq!(
+        failures,
+        vec![
+            CheckFailure {
+                check: format!("account {} existence", sender),
+                expected: "exists".to_string(),
+                actual: "absent (0 lamports, 0 bytes of data)".to_string(),
+            },
+            CheckFailure {
+                check: format!("account {} existence", unreferenced),
+                expected: "exists".to_string(),
+                actual: "absent (not in resulting accounts)".to_string(),
+            },
+            CheckFailure {
+                check: format!("account {} existence", recipient),
+                expected: "absent".to_string(),
+                actual: "exists".to_string(),
+            },
+        ]
+    );
+}
+
+#[cfg(feature = "macros")]
+#[test]
+fn test_mollusk_test_macro() {
+    use mollusk_svm::mollusk_test;
+
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+    let accounts = [
+        (
+            sender,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (
+            recipient,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+    ];
+
+    mollusk_test!(
+        Mollusk::default(),
+        [
+            (
+                "transfer",
+                system_instruction::transfer(&sender, &recipient, 42_000),
+                accounts,
+                [
+                    Check::success(),
+                    Check::account(&recipient)
+                        .lamports(base_lamports + 42_000)
+                        .build(),
+                ],
+            ),
+            (
+                "overdraw",
+                system_instruction::transfer(&sender, &recipient, base_lamports + 1),
+                accounts,
+                [Check::custom_err(
+                    SystemError::ResultWithNegativeLamports as u32
+                )],
+            ),
+        ]
+    );
+}
+
+#[cfg(feature = "macros")]
+#[test]
+#[should_panic(expected = "Case \"overdraw\" failed:\n  Checking program result")]
+fn test_mollusk_test_macro_failure() {
+    use mollusk_svm::mollusk_test;
+
+    let sender = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+
+    let accounts = [
+        (
+            sender,
+            AccountSharedData::new(100_000_000, 0, &system_program::id()),
+        ),
+        (recipient, AccountSharedData::default()),
+    ];
+
+    let mollusk = Mollusk::default();
+    mollusk_test!(
+        mollusk,
+        [
+            (
+                "transfer",
+                system_instruction::transfer(&sender, &recipient, 42_000),
+                accounts,
+                [Check::success()],
+            ),
+            (
+                "overdraw",
+                system_instruction::transfer(&sender, &recipient, 100_000_001),
+                accounts,
+                [Check::success()],
+            ),
+        ]
+    );
+}
@@ -0,0 +1,639 @@
+use {
+    mollusk_svm::{
+        error::MolluskError,
+        result::{Check, CheckFailure, InstructionResult, ProgramResult},
+        Mollusk,
+    },
+    solana_sdk::{
+        account::{AccountSharedData, ReadableAccount},
+        address_lookup_table::{
+            self,
+            state::{AddressLookupTable, LookupTableMeta},
+            AddressLookupTableAccount,
+        },
+        compute_budget::ComputeBudgetInstruction,
+        hash::Hash,
+        instruction::InstructionError,
+        message::{v0, Message, VersionedMessage},
+        program_error::ProgramError,
+        pubkey::Pubkey,
+        system_instruction, system_program, sysvar,
+        transaction::VersionedTransaction,
+    },
+    solana_system_program::system_processor::DEFAULT_COMPUTE_UNITS,
+    std::borrow::Cow,
+};
+
+fn unsigned_transaction(message: VersionedMessage) -> VersionedTransaction {
+    VersionedTransaction {
+        signatures: vec![Default::default(); message.header().num_required_signatures as usize],
+        message,
+    }
+}
+
+fn lamports(result: &InstructionResult, pubkey: &Pubkey) -> u64 {
+    result.get_account(pubkey).unwrap().lamports()
+}
+
+#[test]
+fn test_instruction_chain() {
+    let alice = Pubkey::new_unique();
+    let bob = Pubkey::new_unique();
+    let carol = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+
+    let instructions = [
+        system_instruction::transfer(&alice, &bob, 42_000),
+        system_instruction::transfer(&bob, &carol, 10_000),
+    ];
+    let accounts = [
+        (
+            alice,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (
+            bob,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+    ];
+
+    let mollusk = Mollusk::default();
+    let result = mollusk.process_instruction_chain(&instructions, &accounts);
+
+    assert_eq!(result.program_result, ProgramResult::Success);
+    assert_eq!(result.compute_units_consumed, DEFAULT_COMPUTE_UNITS * 2);
+
+    // Carol wasn't provided, so she starts as a default account.
+    assert_eq!(result.resulting_accounts.len(), 3);
+    assert_eq!(lamports(&result, &alice), base_lamports - 42_000);
+    assert_eq!(lamports(&result, &bob), base_lamports + 42_000 - 10_000);
+    assert_eq!(lamports(&result, &carol), 10_000);
+}
+
+#[test]
+fn test_instruction_chain_stops_on_failure() {
+    let alice = Pubkey::new_unique();
+    let bob = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+
+    let instructions = [
+        system_instruction::transfer(&alice, &bob, base_lamports * 2),
+        system_instruction::transfer(&alice, &bob, 42_000),
+    ];
+    let accounts = [
+        (
+            alice,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (
+            bob,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+    ];
+
+    let mollusk = Mollusk::default();
+    let result = mollusk.process_instruction_chain(&instructions, &accounts);
+
+    assert_eq!(
+        result.program_result,
+        ProgramResult::Failure(ProgramError::Custom(1))
+    );
+    assert_eq!(result.compute_units_consumed, DEFAULT_COMPUTE_UNITS);
+    assert_eq!(lamports(&result, &alice), base_lamports);
+    assert_eq!(lamports(&result, &bob), base_lamports);
+}
+
+#[test]
+fn test_process_and_validate_instruction_chain() {
+    let alice = Pubkey::new_unique();
+    let bob = Pubkey::new_unique();
+    let carol = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+
+    let instructions = [
+        system_instruction::transfer(&alice, &bob, 42_000),
+        system_instruction::transfer(&bob, &carol, 10_000),
+    ];
+    let accounts = [
+        (
+            alice,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (
+            bob,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+    ];
+
+    let mollusk = Mollusk::default();
+
+    // Each instruction's checks see its own intermediate result.
+    mollusk.process_and_validate_instruction_chain(
+        &instructions,
+        &accounts,
+        &[
+            &[
+                Check::success(),
+                Check::compute_units(DEFAULT_COMPUTE_UNITS),
+                Check::account(&bob)
+                    .lamports(base_lamports + 42_000)
+                    .build(),
+            ],
+            &[
+                Check::success(),
+                Check::account_lamports_delta(&bob, -10_000),
+                Check::account(&carol).lamports(10_000).build(),
+            ],
+            &[
+                Check::compute_units(DEFAULT_COMPUTE_UNITS * 2),
+                Check::account(&bob)
+                    .lamports(base_lamports + 42_000 - 10_000)
+                    .build(),
+            ],
+        ],
+    );
+
+    // The final slice of checks is optional.
+    let failures = mollusk
+        .try_process_and_validate_instruction_chain(
+            &instructions,
+            &accounts,
+            &[
+                &[Check::success()],
+                &[Check::account(&carol).lamports(0).build()],
+            ],
+        )
+        .unwrap_err();
+    assert_eq!(
+        failures,
+        vec![CheckFailure {
+            check: "instruction 1 account lamports".to_string(),
+            expected: "0".to_string(),
+            actual: "10000".to_string(),
+        }]
+    );
+}
+
+#[test]
+fn test_legacy_transaction() {
+    let alice = Pubkey::new_unique();
+    let bob = Pubkey::new_unique();
+    let carol = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+
+    let message = Message::new(
+        &[
+            system_instruction::transfer(&alice, &bob, 42_000),
+            system_instruction::transfer(&alice, &carol, 10_000),
+        ],
+        Some(&alice),
+    );
+    let transaction = unsigned_transaction(VersionedMessage::Legacy(message));
+
+    let accounts = [
+        (
+            alice,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (
+            bob,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (
+            carol,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+    ];
+
+    let mollusk = Mollusk::default();
+    let result = mollusk.process_transaction(&transaction, &accounts);
+
+    assert_eq!(result.program_result, ProgramResult::Success);
+    assert_eq!(result.compute_units_consumed, DEFAULT_COMPUTE_UNITS * 2);
+    assert_eq!(lamports(&result, &alice), base_lamports - 52_000);
+    assert_eq!(lamports(&result, &bob), base_lamports + 42_000);
+    assert_eq!(lamports(&result, &carol), base_lamports + 10_000);
+}
+
+#[test]
+fn test_failed_transaction() {
+    let alice = Pubkey::new_unique();
+    let bob = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+
+    // The first transfer succeeds, but the second overdraws.
+    let message = Message::new(
+        &[
+            system_instruction::transfer(&alice, &bob, 42_000),
+            system_instruction::transfer(&alice, &bob, base_lamports),
+        ],
+        Some(&alice),
+    );
+    let transaction = unsigned_transaction(VersionedMessage::Legacy(message));
+
+    let accounts = [
+        (
+            alice,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (
+            bob,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+    ];
+
+    let mollusk = Mollusk::default();
+    let result = mollusk.process_transaction(&transaction, &accounts);
+
+    // Unlike a chain, no account is changed by the first transfer.
+    assert_eq!(
+        result.program_result,
+        ProgramResult::Failure(ProgramError::Custom(1))
+    );
+    assert_eq!(result.compute_units_consumed, DEFAULT_COMPUTE_UNITS * 2);
+    assert_eq!(result.resulting_accounts, accounts.to_vec());
+
+    let chain_result = mollusk.process_instruction_chain(
+        &[
+            system_instruction::transfer(&alice, &bob, 42_000),
+            system_instruction::transfer(&alice, &bob, base_lamports),
+        ],
+        &accounts,
+    );
+    assert_eq!(lamports(&chain_result, &alice), base_lamports - 42_000);
+}
+
+#[test]
+fn test_v0_transaction_with_lookup_table() {
+    let alice = Pubkey::new_unique();
+    let bob = Pubkey::new_unique();
+    let table_key = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+
+    let table = AddressLookupTable {
+        meta: LookupTableMeta::default(),
+        addresses: Cow::Owned(vec![Pubkey::new_unique(), bob]),
+    };
+    let table_data = table.serialize_for_tests().unwrap();
+    let mut table_account = AccountSharedData::new(
+        base_lamports,
+        table_data.len(),
+        &address_lookup_table::program::id(),
+    );
+    table_account.set_data_from_slice(&table_data);
+
+    let message = v0::Message::try_compile(
+        &alice,
+        &[system_instruction::transfer(&alice, &bob, 42_000)],
+        &[AddressLookupTableAccount {
+            key: table_key,
+            addresses: table.addresses.to_vec(),
+        }],
+        Hash::default(),
+    )
+    .unwrap();
+    // Bob should be loaded from the table, not included statically.
+    assert!(!message.account_keys.contains(&bob));
+    let transaction = unsigned_transaction(VersionedMessage::V0(message));
+
+    let accounts = [
+        (
+            alice,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (
+            bob,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (table_key, table_account),
+    ];
+
+    let mollusk = Mollusk::default();
+    let result = mollusk.process_transaction(&transaction, &accounts);
+
+    assert_eq!(result.program_result, ProgramResult::Success);
+    assert_eq!(lamports(&result, &alice), base_lamports - 42_000);
+    assert_eq!(lamports(&result, &bob), base_lamports + 42_000);
+}
+
+#[test]
+#[should_panic(expected = "not provided")]
+fn test_v0_transaction_missing_lookup_table() {
+    let alice = Pubkey::new_unique();
+    let bob = Pubkey::new_unique();
+    let table_key = Pubkey::new_unique();
+
+    let message = v0::Message::try_compile(
+        &alice,
+        &[system_instruction::transfer(&alice, &bob, 42_000)],
+        &[AddressLookupTableAccount {
+            key: table_key,
+            addresses: vec![bob],
+        }],
+        Hash::default(),
+    )
+    .unwrap();
+    let transaction = unsigned_transaction(VersionedMessage::V0(message));
+
+    let accounts = [(
+        alice,
+        AccountSharedData::new(100_000_000, 0, &system_program::id()),
+    )];
+
+    Mollusk::default().process_transaction(&transaction, &accounts);
+}
+
+#[test]
+fn test_transaction_writable_demotion() {
+    let alice = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+
+    let mollusk = Mollusk::default();
+
+    // The clock sysvar is writable in the message, but reserved, so the
+    // runtime demotes it to read-only.
+    let instruction = system_instruction::transfer(&alice, &sysvar::clock::id(), 42_000);
+    assert!(instruction.accounts[1].is_writable);
+    let message = Message::new(&[instruction.clone()], Some(&alice));
+    let transaction = unsigned_transaction(VersionedMessage::Legacy(message));
+
+    let accounts = [
+        (
+            alice,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        mollusk.sysvar_account(&sysvar::clock::id()),
+    ];
+
+    let result = mollusk.process_transaction(&transaction, &accounts);
+    assert_eq!(
+        result.program_result,
+        ProgramResult::UnknownError(InstructionError::ReadonlyLamportChange)
+    );
+
+    // Processed directly, the instruction's own metas are used.
+    let result = mollusk.process_instruction(&instruction, &accounts);
+    assert_eq!(result.program_result, ProgramResult::Success);
+}
+
+#[test]
+fn test_malformed_transaction() {
+    let alice = Pubkey::new_unique();
+    let bob = Pubkey::new_unique();
+
+    let accounts = [
+        (
+            alice,
+            AccountSharedData::new(100_000_000, 0, &system_program::id()),
+        ),
+        (
+            bob,
+            AccountSharedData::new(100_000_000, 0, &system_program::id()),
+        ),
+    ];
+
+    let mollusk = Mollusk::default();
+    let message = Message::new(
+        &[system_instruction::transfer(&alice, &bob, 42_000)],
+        Some(&alice),
+    );
+
+    // More read-only signers than signers.
+    let mut malformed = message.clone();
+    malformed.header.num_readonly_signed_accounts = 2;
+    let transaction = unsigned_transaction(VersionedMessage::Legacy(malformed));
+    assert!(matches!(
+        mollusk.try_process_transaction(&transaction, &accounts),
+        Err(MolluskError::InvalidTransaction(_))
+    ));
+
+    // A program ID index out of bounds.
+    let mut malformed = message.clone();
+    malformed.instructions[0].program_id_index = 3;
+    let transaction = unsigned_transaction(VersionedMessage::Legacy(malformed));
+    assert!(matches!(
+        mollusk.try_process_transaction(&transaction, &accounts),
+        Err(MolluskError::InvalidTransaction(_))
+    ));
+
+    // An account index out of bounds.
+    let mut malformed = message;
+    malformed.instructions[0].accounts[1] = 3;
+    let transaction = unsigned_transaction(VersionedMessage::Legacy(malformed));
+    assert!(matches!(
+        mollusk.try_process_transaction(&transaction, &accounts),
+        Err(MolluskError::InvalidTransaction(_))
+    ));
+}
+
+#[test]
+#[should_panic(expected = "Invalid transaction")]
+fn test_malformed_transaction_panics() {
+    let alice = Pubkey::new_unique();
+
+    let mut message = Message::new(
+        &[system_instruction::transfer(
+            &alice,
+            &Pubkey::new_unique(),
+            42_000,
+        )],
+        Some(&alice),
+    );
+    message.header.num_required_signatures = 0;
+    let transaction = unsigned_transaction(VersionedMessage::Legacy(message));
+
+    Mollusk::default().process_transaction(&transaction, &[]);
+}
+
+#[test]
+fn test_compute_budget_instructions() {
+    let alice = Pubkey::new_unique();
+    let bob = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+
+    let accounts = [
+        (
+            alice,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (
+            bob,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+    ];
+
+    let mollusk = Mollusk::default();
+
+    // A limit below the cost of a transfer applies to the rest of the chain.
+    let result = mollusk.process_instruction_chain(
+        &[
+            system_instruction::transfer(&alice, &bob, 42_000),
+            ComputeBudgetInstruction::set_compute_unit_limit(100),
+            system_instruction::transfer(&alice, &bob, 42_000),
+        ],
+        &accounts,
+    );
+    assert_eq!(
+        result.program_result,
+        ProgramResult::UnknownError(InstructionError::ComputationalBudgetExceeded)
+    );
+    assert_eq!(lamports(&result, &alice), base_lamports - 42_000);
+
+    // As on-chain, an invalid heap frame size rejects the whole chain.
+    for bytes in [1_000, 16 * 1024, 512 * 1024] {
+        let result = mollusk.process_instruction_chain(
+            &[
+                system_instruction::transfer(&alice, &bob, 42_000),
+                ComputeBudgetInstruction::request_heap_frame(bytes),
+            ],
+            &accounts,
+        );
+        assert_eq!(
+            result.program_result,
+            ProgramResult::Failure(ProgramError::InvalidInstructionData)
+        );
+        assert_eq!(result.compute_units_consumed, 0);
+        assert_eq!(lamports(&result, &alice), base_lamports);
+    }
+
+    // The compute budget instructions themselves aren't executed.
+    let message = Message::new(
+        &[
+            ComputeBudgetInstruction::set_compute_unit_limit(1_000),
+            ComputeBudgetInstruction::set_compute_unit_price(5),
+            system_instruction::transfer(&alice, &bob, 42_000),
+        ],
+        Some(&alice),
+    );
+    let transaction = unsigned_transaction(VersionedMessage::Legacy(message));
+    let result = mollusk.process_transaction(&transaction, &accounts);
+    assert_eq!(result.program_result, ProgramResult::Success);
+    assert_eq!(result.compute_units_consumed, DEFAULT_COMPUTE_UNITS);
+
+    // The prioritization fee is rounded up to the nearest lamport.
+    assert_eq!(result.prioritization_fee, 1);
+
+    let result = mollusk.process_instruction_chain(
+        &[
+            ComputeBudgetInstruction::set_compute_unit_price(2_000_000),
+            system_instruction::transfer(&alice, &bob, 42_000),
+        ],
+        &accounts,
+    );
+    assert_eq!(result.prioritization_fee, 2 * DEFAULT_COMPUTE_UNITS);
+
+    let result = mollusk.process_instruction(
+        &system_instruction::transfer(&alice, &bob, 42_000),
+        &accounts,
+    );
+    assert_eq!(result.prioritization_fee, 0);
+
+    // The harness's own compute budget is left untouched.
+    assert_eq!(
+        mollusk.compute_budget.compute_unit_limit,
+        Mollusk::default().compute_budget.compute_unit_limit
+    );
+}
+
+#[test]
+fn test_merge() {
+    let alice = Pubkey::new_unique();
+    let bob = Pubkey::new_unique();
+    let carol = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+
+    let mollusk = Mollusk::default();
+
+    let first_accounts = [
+        (
+            alice,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+        (
+            bob,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+    ];
+    let mut result = mollusk.process_instruction(
+        &system_instruction::transfer(&alice, &bob, 42_000),
+        &first_accounts,
+    );
+
+    // Run a second step against the resulting state, with a new account.
+    let second_accounts = [
+        (bob, result.get_account(&bob).unwrap().clone()),
+        (
+            carol,
+            AccountSharedData::new(base_lamports, 0, &system_program::id()),
+        ),
+    ];
+    let second = mollusk.process_instruction(
+        &system_instruction::transfer(&bob, &carol, 10_000),
+        &second_accounts,
+    );
+    let second_logs = second.logs.clone();
+    result.merge(second);
+
+    assert_eq!(result.program_result, ProgramResult::Success);
+    assert_eq!(result.compute_units_consumed, DEFAULT_COMPUTE_UNITS * 2);
+    assert!(result.logs.ends_with(&second_logs));
+
+    // Bob is updated in place, and Carol is appended.
+    let keys = result
+        .resulting_accounts
+        .iter()
+        .map(|(key, _)| *key)
+        .collect::<Vec<_>>();
+    assert_eq!(keys, vec![alice, bob, carol]);
+    assert_eq!(lamports(&result, &alice), base_lamports - 42_000);
+    assert_eq!(lamports(&result, &bob), base_lamports + 42_000 - 10_000);
+    assert_eq!(lamports(&result, &carol), base_lamports + 10_000);
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn test_process_batch() {
+    let alice = Pubkey::new_unique();
+    let bob = Pubkey::new_unique();
+
+    let base_lamports = 100_000_000u64;
+
+    // Every case transfers from the same account, so any state leaking from
+    // one case to another would show up in the results.
+    let cases = (1..=64u64)
+        .map(|amount| {
+            (
+                system_instruction::transfer(&alice, &bob, amount),
+                vec![
+                    (
+                        alice,
+                        AccountSharedData::new(base_lamports, 0, &system_program::id()),
+                    ),
+                    (
+                        bob,
+                        AccountSharedData::new(base_lamports, 0, &system_program::id()),
+                    ),
+                ],
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let mollusk = Mollusk::default();
+    let results = mollusk.process_batch(&cases);
+
+    assert_eq!(results.len(), cases.len());
+    for (amount, result) in (1..=64u64).zip(results.iter()) {
+        assert_eq!(result.program_result, ProgramResult::Success);
+        assert_eq!(lamports(result, &alice), base_lamports - amount);
+        assert_eq!(lamports(result, &bob), base_lamports + amount);
+    }
+}
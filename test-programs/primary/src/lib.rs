@@ -1,12 +1,20 @@
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    clock::Clock,
     entrypoint::ProgramResult,
     incinerator,
-    instruction::{AccountMeta, Instruction},
-    program::invoke,
+    instruction::{get_stack_height, AccountMeta, Instruction},
+    log::sol_log_data,
+    msg,
+    program::{invoke, set_return_data},
     program_error::ProgramError,
     pubkey::{Pubkey, PUBKEY_BYTES},
+    rent::Rent,
     system_instruction, system_program,
+    sysvar::{
+        instructions::{load_current_index_checked, load_instruction_at_checked},
+        Sysvar,
+    },
 };
 
 solana_program::declare_id!("239vxAL9Q7e3uLoinJpJ873r3bvT9sPFxH7yekwPppNF");
@@ -14,7 +22,7 @@ solana_program::declare_id!("239vxAL9Q7e3uLoinJpJ873r3bvT9sPFxH7yekwPppNF");
 solana_program::entrypoint!(process_instruction);
 
 fn process_instruction(
-    _program_id: &Pubkey,
+    program_id: &Pubkey,
     accounts: &[AccountInfo],
     input: &[u8],
 ) -> ProgramResult {
@@ -92,6 +100,106 @@ fn process_instruction(
 
             invoke(&instruction, &[account_info.clone()])?;
         }
+        Some((5, rest)) => {
+            // Set the remaining data as the program's return data.
+            set_return_data(rest);
+        }
+        Some((6, _)) => {
+            // Return the `Clock` sysvar's slot and Unix timestamp.
+            let clock = Clock::get()?;
+
+            let mut data = [0u8; 16];
+            data[..8].copy_from_slice(&clock.slot.to_le_bytes());
+            data[8..].copy_from_slice(&clock.unix_timestamp.to_le_bytes());
+
+            set_return_data(&data);
+        }
+        Some((7, _)) => {
+            // Fail unless the first account is rent-exempt.
+            let account_info = next_account_info(accounts_iter)?;
+
+            let rent = Rent::get()?;
+            if !rent.is_exempt(account_info.lamports(), account_info.data_len()) {
+                return Err(ProgramError::AccountNotRentExempt);
+            }
+        }
+        Some((8, rest)) if rest.len() == 4 => {
+            // Allocate the requested number of bytes on the heap.
+            let len = u32::from_le_bytes(rest.try_into().unwrap()) as usize;
+            let buffer = vec![1u8; len];
+            std::hint::black_box(buffer);
+        }
+        Some((9, _)) => {
+            // Return the index of the executing instruction, followed by the
+            // program ID of the first instruction, read from the
+            // `Instructions` sysvar.
+            let instructions_info = next_account_info(accounts_iter)?;
+
+            let current_index = load_current_index_checked(instructions_info)?;
+            let first_instruction = load_instruction_at_checked(0, instructions_info)?;
+
+            let mut data = current_index.to_le_bytes().to_vec();
+            data.extend_from_slice(first_instruction.program_id.as_ref());
+
+            set_return_data(&data);
+        }
+        Some((10, rest)) if rest.len() == 4 => {
+            // Resize the first account to the requested length, zeroing any
+            // extension.
+            let account_info = next_account_info(accounts_iter)?;
+
+            let new_len = u32::from_le_bytes(rest.try_into().unwrap()) as usize;
+            account_info.realloc(new_len, true)?;
+        }
+        Some((11, rest)) if rest.len() == 4 => {
+            // Create the second account with the requested length, funded by
+            // the first, and assign it to this program.
+            let payer_info = next_account_info(accounts_iter)?;
+            let new_account_info = next_account_info(accounts_iter)?;
+            let _system_program = next_account_info(accounts_iter)?;
+
+            let space = u32::from_le_bytes(rest.try_into().unwrap()) as usize;
+            let lamports = Rent::get()?.minimum_balance(space);
+
+            invoke(
+                &system_instruction::create_account(
+                    payer_info.key,
+                    new_account_info.key,
+                    lamports,
+                    space as u64,
+                    program_id,
+                ),
+                &[payer_info.clone(), new_account_info.clone()],
+            )?;
+        }
+        Some((12, rest)) => {
+            // Log the remaining data, as Anchor's `emit!` does for an event.
+            sol_log_data(&[rest]);
+        }
+        Some((13, _)) => {
+            // Return the height of the invocation stack, which is 1 at the
+            // top level.
+            set_return_data(&(get_stack_height() as u64).to_le_bytes());
+        }
+        Some((14, rest)) if rest.len() == 4 => {
+            // Log the requested number of 100-byte messages.
+            let count = u32::from_le_bytes(rest.try_into().unwrap());
+            let message = "x".repeat(100);
+            for _ in 0..count {
+                msg!(&message);
+            }
+        }
+        Some((15, rest)) if rest.len() == 4 => {
+            // Set the first account's serialized data length directly,
+            // bypassing the checks in `AccountInfo::realloc`, so only the
+            // loader enforces the limit.
+            let account_info = next_account_info(accounts_iter)?;
+
+            let new_len = u32::from_le_bytes(rest.try_into().unwrap()) as u64;
+            let mut data = account_info.try_borrow_mut_data()?;
+            // The length is serialized immediately before the data.
+            unsafe { *(data.as_mut_ptr().offset(-8) as *mut u64) = new_len };
+        }
         _ => return Err(ProgramError::InvalidInstructionData),
     }
 